@@ -0,0 +1,60 @@
+//! Heap vs arena decoding of an order book account.
+//!
+//! cargo bench --features arena --bench arena_decode
+
+use std::{hint::black_box, time::Instant};
+
+use atlas_idl_schema::schema::{DecodeOptions, SchemaNode, SchemaType};
+use bumpalo::Bump;
+
+const ORDERS: usize = 1_000;
+const ITERATIONS: u32 = 2_000;
+
+fn main() {
+    let order = SchemaNode::new_struct(
+        "Order",
+        vec![
+            ("owner", SchemaType::Pubkey),
+            ("price", SchemaType::U64),
+            ("size", SchemaType::U64),
+            ("clientId", SchemaType::String),
+        ],
+    );
+    let book = SchemaNode::new_struct("Book", vec![("orders", SchemaType::vec(order.typ))]);
+
+    let mut data = (ORDERS as u32).to_le_bytes().to_vec();
+    for i in 0..ORDERS {
+        data.extend_from_slice(&[i as u8; 32]);
+        data.extend_from_slice(&(i as u64).to_le_bytes());
+        data.extend_from_slice(&(2 * i as u64).to_le_bytes());
+        let client_id = format!("client-{}", i);
+        data.extend_from_slice(&(client_id.len() as u32).to_le_bytes());
+        data.extend_from_slice(client_id.as_bytes());
+    }
+    let options = DecodeOptions::default();
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        let value = book
+            .deserialize_bytes_with_options(&mut data.as_slice(), &options)
+            .unwrap();
+        black_box(serde_json::to_vec(&value).unwrap());
+    }
+    let heap = start.elapsed() / ITERATIONS;
+
+    let mut bump = Bump::new();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        let value = book
+            .deserialize_bytes_in(&mut data.as_slice(), &bump, &options)
+            .unwrap();
+        black_box(serde_json::to_vec(&value).unwrap());
+        bump.reset();
+    }
+    let arena = start.elapsed() / ITERATIONS;
+
+    println!(
+        "{} orders: heap {:?}/decode, arena {:?}/decode",
+        ORDERS, heap, arena
+    );
+}
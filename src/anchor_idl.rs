@@ -0,0 +1,394 @@
+//! Conversion of an `OnChainIdl` back into an approximate Anchor-style JSON IDL
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    on_chain_idl::{DiscPosition, OnChainIdl},
+    schema::{IntWidth, SchemaNode, SchemaType, SmallVecLen},
+};
+
+impl OnChainIdl {
+    /// Reconstructs an Anchor-style IDL (legacy layout) from the internal
+    /// schema, so tools that consume Anchor IDLs can use reverse-engineered
+    /// programs.
+    ///
+    /// This is lossy: discriminators become explicit byte arrays, hidden flags
+    /// are dropped, and inline structs or enums that don't match a defined
+    /// type are emitted as synthesized types named after the field holding
+    /// them. Instruction accounts keep their `isMut`, `isSigner` and
+    /// `isOptional` flags.
+    pub fn to_anchor_idl_json(&self) -> Value {
+        let mut writer = AnchorTypeWriter::new(&self.types);
+
+        let instructions: Vec<Value> = self
+            .instruction_params
+            .iter()
+            .map(|(disc, decoder)| {
+                let name = &decoder.instruction_args_parser.name;
+                let accounts: Vec<Value> = decoder
+                    .accounts
+                    .iter()
+                    .map(|account| {
+                        let mut meta = json!({
+                            "name": account.name,
+                            "isMut": account.is_mut,
+                            "isSigner": account.is_signer,
+                        });
+                        if account.is_optional {
+                            meta["isOptional"] = json!(true);
+                        }
+                        meta
+                    })
+                    .collect();
+                let mut instruction = json!({
+                    "name": name,
+                    "discriminator": disc,
+                    "accounts": accounts,
+                    "args": writer.fields(name, &decoder.instruction_args_parser.typ),
+                });
+                if !decoder.docs.is_empty() {
+                    instruction["docs"] = json!(decoder.docs);
+                }
+                if decoder.deprecated {
+                    instruction["deprecated"] = json!(true);
+                }
+                instruction
+            })
+            .collect();
+
+        let accounts: Vec<Value> = self
+            .accounts
+            .iter()
+            .map(|(disc, schema)| {
+                let mut account = Map::new();
+                account.insert("name".to_string(), json!(schema.name));
+                account.insert("discriminator".to_string(), json!(disc));
+                if let Some(docs) = self.account_docs(&schema.name) {
+                    account.insert("docs".to_string(), json!(docs));
+                }
+                if self.deprecated_accounts.contains(&*schema.name) {
+                    account.insert("deprecated".to_string(), json!(true));
+                }
+                if let Some(space) = self.account_spaces.get(&*schema.name) {
+                    account.insert("space".to_string(), json!(space));
+                }
+                // the layout lives in `types` when the account has a defined type
+                if self.get_type(&schema.name).map(|typ| &typ.typ) != Some(&schema.typ) {
+                    account.insert(
+                        "type".to_string(),
+                        writer.type_def(&schema.name, &schema.typ),
+                    );
+                }
+                Value::Object(account)
+            })
+            .collect();
+
+        let events: Vec<Value> = self
+            .events
+            .iter()
+            .map(|(disc, schema)| {
+                json!({
+                    "name": schema.name,
+                    "discriminator": disc,
+                    "fields": writer.fields(&schema.name, &schema.typ),
+                })
+            })
+            .collect();
+
+        let mut types: Vec<Value> = self
+            .types
+            .iter()
+            .map(|typ| json!({ "name": typ.name, "type": writer.type_def(&typ.name, &typ.typ) }))
+            .collect();
+        types.append(&mut writer.synthesized);
+
+        let mut idl = json!({
+            "version": "0.0.0",
+            "name": self.program_name,
+            "instructions": instructions,
+            "accounts": accounts,
+            "types": types,
+            "events": events,
+        });
+        if !self.docs.is_empty() {
+            idl["docs"] = json!(self.docs);
+        }
+        if self.account_disc_position == DiscPosition::Suffix {
+            idl["accountDiscriminatorPosition"] = json!("suffix");
+        }
+        if self.account_version_prefix {
+            idl["accountVersionPrefix"] = json!(true);
+        }
+        if self.enum_disc_width != IntWidth::U8 {
+            idl["enumDiscriminant"] = json!(self.enum_disc_width.name());
+        }
+        idl
+    }
+}
+
+struct AnchorTypeWriter<'a> {
+    defined: &'a [SchemaNode],
+    /// inline structs and enums that had no defined type, by layout
+    synthesized_names: Vec<(SchemaType, String)>,
+    synthesized: Vec<Value>,
+}
+
+impl<'a> AnchorTypeWriter<'a> {
+    fn new(defined: &'a [SchemaNode]) -> Self {
+        Self {
+            defined,
+            synthesized_names: Vec::new(),
+            synthesized: Vec::new(),
+        }
+    }
+
+    /// `{ "kind": ..., ... }` body of a type definition
+    fn type_def(&mut self, name: &str, typ: &SchemaType) -> Value {
+        match typ {
+            SchemaType::Struct(_) => json!({ "kind": "struct", "fields": self.fields(name, typ) }),
+            SchemaType::Enum(variants) => json!({
+                "kind": "enum",
+                "variants": self.variants(variants),
+            }),
+            SchemaType::TaggedEnum(width, variants) => json!({
+                "kind": "enum",
+                "discriminant": width.name(),
+                "variants": self.variants(variants),
+            }),
+            other => json!({ "kind": "alias", "value": self.field_type(name, other) }),
+        }
+    }
+
+    fn variants(&mut self, variants: &[SchemaNode]) -> Vec<Value> {
+        variants
+            .iter()
+            .map(|variant| match &variant.typ {
+                SchemaType::Empty => json!({ "name": variant.name }),
+                typ => json!({ "name": variant.name, "fields": self.fields(&variant.name, typ) }),
+            })
+            .collect()
+    }
+
+    /// Field list of a struct-like schema; anything else becomes a single
+    /// unnamed field
+    fn fields(&mut self, parent: &str, typ: &SchemaType) -> Vec<Value> {
+        match typ {
+            SchemaType::Empty => vec![],
+            SchemaType::Struct(fields) => fields
+                .iter()
+                .map(|field| {
+                    let hint = format!("{}_{}", parent, field.name);
+                    let mut value =
+                        json!({ "name": field.name, "type": self.field_type(&hint, &field.typ) });
+                    if let Some(order) = field.serialization_order {
+                        value["serializationOrder"] = json!(order);
+                    }
+                    value
+                })
+                .collect(),
+            other => vec![self.field_type(parent, other)],
+        }
+    }
+
+    fn field_type(&mut self, hint: &str, typ: &SchemaType) -> Value {
+        match typ {
+            SchemaType::Empty => Value::Null,
+            SchemaType::Pubkey => json!("publicKey"),
+            SchemaType::String => json!("string"),
+            SchemaType::I8 => json!("i8"),
+            SchemaType::U8 => json!("u8"),
+            SchemaType::I16 => json!("i16"),
+            SchemaType::U16 => json!("u16"),
+            SchemaType::I32 => json!("i32"),
+            SchemaType::U32 => json!("u32"),
+            SchemaType::I64 => json!("i64"),
+            SchemaType::U64 => json!("u64"),
+            SchemaType::I128 => json!("i128"),
+            SchemaType::U128 => json!("u128"),
+            SchemaType::F32 => json!("f32"),
+            SchemaType::F64 => json!("f64"),
+            SchemaType::Bool => json!("bool"),
+            SchemaType::RemainingBytes => json!("bytes_remaining"),
+            SchemaType::Utf8Remaining => json!("utf8_remaining"),
+            SchemaType::RawString => json!("raw_string"),
+            SchemaType::UnixTimestamp => json!({ "defined": "UnixTimestamp" }),
+            SchemaType::Duration => json!({ "defined": "Duration" }),
+            SchemaType::Option(inner) => json!({ "option": self.field_type(hint, inner) }),
+            SchemaType::LenientOption(inner) => {
+                json!({ "lenientOption": self.field_type(hint, inner) })
+            }
+            SchemaType::Vec(inner) => json!({ "vec": self.field_type(hint, inner) }),
+            SchemaType::Map(key, value) => json!({
+                "hashMap": [self.field_type(hint, key), self.field_type(hint, value)]
+            }),
+            SchemaType::Array(len, inner) => {
+                json!({ "array": [self.field_type(hint, inner), len] })
+            }
+            SchemaType::Bitmap(bits) => json!({ "bitmap": bits }),
+            SchemaType::SmallVec(len, inner) => json!({
+                "smallVec": {
+                    "len": match len {
+                        SmallVecLen::U8 => "u8",
+                        SmallVecLen::U16 => "u16",
+                        SmallVecLen::ShortU16 => "shortU16",
+                    },
+                    "elem": self.field_type(hint, inner),
+                }
+            }),
+            SchemaType::FlaggedOption { flag_width, inner } => json!({
+                "flaggedOption": { "flag": flag_width.name(), "type": self.field_type(hint, inner) }
+            }),
+            // no Anchor equivalent, kept so the shape is still visible
+            SchemaType::Tuple(types) => json!({
+                "tuple": types.iter().map(|typ| self.field_type(hint, typ)).collect::<Vec<_>>()
+            }),
+            SchemaType::RemainingVec(inner) => {
+                json!({ "remainingVec": self.field_type(hint, inner) })
+            }
+            SchemaType::CountedArray {
+                count_from,
+                element,
+            } => json!({
+                "countedArray": { "countFrom": count_from, "type": self.field_type(hint, element) }
+            }),
+            SchemaType::Tlv(entries) => json!({
+                "tlv": entries
+                    .iter()
+                    .map(|(tag, node)| json!({
+                        "tag": tag,
+                        "name": node.name,
+                        "type": self.field_type(&format!("{}_{}", hint, node.name), &node.typ),
+                    }))
+                    .collect::<Vec<_>>()
+            }),
+            SchemaType::Struct(_) | SchemaType::Enum(_) | SchemaType::TaggedEnum(_, _) => {
+                json!({ "defined": self.defined_name(hint, typ) })
+            }
+        }
+    }
+
+    /// Name of the defined type with this layout, synthesizing one if needed
+    fn defined_name(&mut self, hint: &str, typ: &SchemaType) -> String {
+        if let Some(defined) = self.defined.iter().find(|defined| defined.typ == *typ) {
+            return defined.name.to_string();
+        }
+        if let Some((_, name)) = self.synthesized_names.iter().find(|(t, _)| t == typ) {
+            return name.clone();
+        }
+
+        let name = hint.to_string();
+        self.synthesized_names.push((typ.clone(), name.clone()));
+        let def = self.type_def(&name, typ);
+        self.synthesized.push(json!({ "name": name, "type": def }));
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        parse_idl::{parse_idl, parse_idl_with_options, ParseOptions},
+        schema::IntWidth,
+    };
+
+    #[test]
+    fn round_trips_through_anchor_json() {
+        let json = r#"{
+          "version": "0.1.0",
+          "name": "vault",
+          "docs": ["Holds deposits until their lock expires"],
+          "instructions": [
+            {
+              "name": "deposit",
+              "docs": ["Adds `amount` to the vault"],
+              "accounts": [
+                { "name": "vault", "isMut": true, "isSigner": false },
+                { "name": "owner", "isMut": false, "isSigner": true }
+              ],
+              "args": [
+                { "name": "amount", "type": "u64" },
+                { "name": "params", "type": { "defined": "DepositParams" } }
+              ]
+            },
+            {
+              "name": "close",
+              "accounts": [{ "name": "vault", "isMut": true, "isSigner": false }],
+              "args": []
+            }
+          ],
+          "accounts": [
+            {
+              "name": "Vault",
+              "docs": ["One per owner"],
+              "type": {
+                "kind": "struct",
+                "fields": [
+                  { "name": "owner", "type": "publicKey" },
+                  { "name": "balances", "type": { "vec": "u64" } },
+                  { "name": "state", "type": { "defined": "State" } }
+                ]
+              }
+            }
+          ],
+          "types": [
+            {
+              "name": "DepositParams",
+              "type": {
+                "kind": "struct",
+                "fields": [
+                  { "name": "memo", "type": { "option": "string" } },
+                  { "name": "seed", "type": { "array": ["u8", 32] } }
+                ]
+              }
+            },
+            {
+              "name": "State",
+              "type": {
+                "kind": "enum",
+                "variants": [
+                  { "name": "Open" },
+                  { "name": "Locked", "fields": [{ "name": "until", "type": "i64" }] }
+                ]
+              }
+            }
+          ],
+          "events": [
+            {
+              "name": "Deposited",
+              "fields": [{ "name": "amount", "type": "u64", "index": false }]
+            }
+          ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+
+        let anchor_json = idl.to_anchor_idl_json();
+        let close = anchor_json["instructions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|ix| ix["name"] == "close")
+            .unwrap();
+        assert_eq!(close["args"], serde_json::json!([]));
+        assert_eq!(
+            anchor_json["accounts"][0]["discriminator"]
+                .as_array()
+                .unwrap()
+                .len(),
+            8
+        );
+
+        let reparsed = parse_idl(anchor_json.to_string()).expect("reparse ok");
+        assert_eq!(reparsed, idl);
+
+        // so does an IDL-wide enum width
+        let options = ParseOptions {
+            enum_disc_width: Some(IntWidth::U16),
+            ..Default::default()
+        };
+        let wide = parse_idl_with_options(json.to_string(), &options).expect("parse_idl ok");
+        let anchor_json = wide.to_anchor_idl_json();
+        assert_eq!(anchor_json["enumDiscriminant"], "u16");
+        let reparsed = parse_idl(anchor_json.to_string()).expect("reparse ok");
+        assert_eq!(reparsed, wide);
+    }
+}
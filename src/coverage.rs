@@ -0,0 +1,195 @@
+//! Checks a (reverse-engineered) IDL against sample account and instruction data
+
+use std::collections::BTreeMap;
+
+use crate::{on_chain_idl::OnChainIdl, schema::DecodeOptions};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct CoverageReport {
+    /// samples that decoded cleanly, by account name
+    pub decoded: BTreeMap<String, usize>,
+    /// samples whose discriminator matched but whose data failed to decode,
+    /// by account name
+    pub failed: BTreeMap<String, usize>,
+    /// samples too short for a discriminator or matching no account
+    pub unmatched: usize,
+    /// account schemas no sample matched
+    pub unexercised: Vec<String>,
+    /// number of decoded samples by how many bytes were left over after the
+    /// schema; anything but 0 hints at missing trailing fields
+    pub leftover_bytes: BTreeMap<usize, usize>,
+    /// instruction samples whose args consumed the data exactly, by
+    /// instruction name
+    pub instructions_passed: BTreeMap<String, usize>,
+    /// instruction samples whose discriminator matched but whose args failed
+    /// to decode or left bytes over, by instruction name
+    pub instructions_failed: BTreeMap<String, usize>,
+    /// why each failing instruction sample failed, by sample index
+    pub instruction_errors: BTreeMap<usize, String>,
+    /// instruction samples too short for a discriminator or matching no
+    /// instruction
+    pub unmatched_instructions: usize,
+}
+
+impl OnChainIdl {
+    /// Decodes every sample account buffer and reports which account schemas
+    /// they exercised, which failed, and how many bytes each left undecoded.
+    pub fn coverage(&self, samples: &[Vec<u8>]) -> CoverageReport {
+        let mut report = CoverageReport::default();
+        let options = self.decode_options(true);
+
+        for sample in samples {
+            let Some((schema, mut bytes)) = self.match_account(sample) else {
+                report.unmatched += 1;
+                continue;
+            };
+            match schema.deserialize_bytes_with_options(&mut bytes, &options) {
+                Ok(_) => {
+                    *report.decoded.entry(schema.name.to_string()).or_default() += 1;
+                    *report.leftover_bytes.entry(bytes.len()).or_default() += 1;
+                }
+                Err(_) => *report.failed.entry(schema.name.to_string()).or_default() += 1,
+            }
+        }
+
+        report.unexercised = self
+            .accounts
+            .iter()
+            .map(|(_, schema)| &*schema.name)
+            .filter(|name| {
+                !report.decoded.contains_key(*name) && !report.failed.contains_key(*name)
+            })
+            .map(str::to_string)
+            .collect();
+        report
+    }
+
+    /// `coverage` of the account `samples`, plus the `self_check` results of
+    /// `instruction_samples` counted by instruction name
+    pub fn coverage_with_instructions(
+        &self,
+        samples: &[Vec<u8>],
+        instruction_samples: &[(Vec<u8>, Vec<String>)],
+    ) -> CoverageReport {
+        let mut report = self.coverage(samples);
+        let results = self.self_check(instruction_samples);
+        for (index, ((data, _), result)) in instruction_samples.iter().zip(results).enumerate() {
+            let Some(decoder) = self.match_instruction(data) else {
+                report.unmatched_instructions += 1;
+                continue;
+            };
+            let name = decoder.instruction_args_parser.name.to_string();
+            match result {
+                Ok(()) => *report.instructions_passed.entry(name).or_default() += 1,
+                Err(e) => {
+                    *report.instructions_failed.entry(name).or_default() += 1;
+                    report.instruction_errors.insert(index, e.to_string());
+                }
+            }
+        }
+        report
+    }
+
+    /// Decodes every sample instruction (data and account keys) and checks
+    /// its args consume the data exactly, returning one result per sample in
+    /// order. Leftover bytes usually mean the IDL is missing trailing args.
+    pub fn self_check(
+        &self,
+        instruction_samples: &[(Vec<u8>, Vec<String>)],
+    ) -> Vec<anyhow::Result<()>> {
+        let options = DecodeOptions {
+            reject_trailing_bytes: true,
+            ..self.decode_options(true)
+        };
+        instruction_samples
+            .iter()
+            .map(|(data, account_keys)| {
+                self.get_parsed_instruction_with_options(data.clone(), account_keys, &options)
+                    .map(|_| ())
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::{
+        parse_idl::parse_idl,
+        test_fixtures::{counter_account, counter_idl, increment_instruction},
+    };
+
+    #[test]
+    fn counts_decoded_failed_and_unmatched_samples() {
+        let json = r#"{
+          "version": "0.1.0",
+          "name": "counter",
+          "instructions": [],
+          "accounts": [
+            {
+              "name": "Counter",
+              "type": { "kind": "struct", "fields": [{ "name": "count", "type": "u64" }] }
+            },
+            {
+              "name": "Config",
+              "type": { "kind": "struct", "fields": [{ "name": "admin", "type": "publicKey" }] }
+            }
+          ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+
+        let mut counter = counter_account(7);
+        counter.extend_from_slice(&[0; 4]);
+        let truncated = counter[..12].to_vec();
+        let unknown = vec![0xff; 16];
+
+        let report = idl.coverage(&[counter, truncated, unknown]);
+        assert_eq!(report.decoded, BTreeMap::from([("Counter".to_string(), 1)]));
+        assert_eq!(report.failed, BTreeMap::from([("Counter".to_string(), 1)]));
+        assert_eq!(report.unmatched, 1);
+        assert_eq!(report.unexercised, vec!["Config".to_string()]);
+        assert_eq!(report.leftover_bytes, BTreeMap::from([(4, 1)]));
+    }
+
+    #[test]
+    fn counts_passed_failed_and_unmatched_instructions() {
+        let idl = counter_idl();
+
+        let exact = increment_instruction(3);
+        let mut leftover = exact.clone();
+        leftover.push(0);
+        let keys = vec!["11111111111111111111111111111111".to_string()];
+
+        let samples = [
+            (exact.clone(), keys.clone()),
+            (leftover, keys.clone()),
+            (vec![0xff; 16], keys.clone()),
+            (exact, keys),
+        ];
+        let results = idl.self_check(&samples);
+        assert_eq!(results.len(), 4);
+        assert!(results[0].is_ok());
+        assert_eq!(
+            results[1].as_ref().unwrap_err().to_string(),
+            "1 bytes left over after decoding increment"
+        );
+        assert!(results[2].is_err());
+        assert!(results[3].is_ok());
+
+        let report = idl.coverage_with_instructions(&[], &samples);
+        assert_eq!(
+            report.instructions_passed,
+            BTreeMap::from([("increment".to_string(), 2)])
+        );
+        assert_eq!(
+            report.instructions_failed,
+            BTreeMap::from([("increment".to_string(), 1)])
+        );
+        assert_eq!(
+            report.instruction_errors,
+            BTreeMap::from([(1, "1 bytes left over after decoding increment".to_string())])
+        );
+        assert_eq!(report.unmatched_instructions, 1);
+    }
+}
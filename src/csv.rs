@@ -0,0 +1,166 @@
+//! Spreadsheet export of decoded values: nested struct fields become dotted
+//! column paths, everything else a single cell
+
+use crate::{
+    schema::{SchemaNode, SchemaType},
+    value::{to_hex, TypedValue, ValueNode},
+};
+
+impl SchemaNode {
+    /// Column paths of this schema's CSV rows, e.g. `["owner", "fees.maker"]`.
+    /// Struct fields are flattened; collections, enums and options stay in
+    /// one column each. A non-struct schema has a single column named after
+    /// the node.
+    pub fn csv_header(&self) -> Vec<String> {
+        match &self.typ {
+            SchemaType::Struct(fields) => {
+                let mut header = Vec::new();
+                for field in fields {
+                    push_columns(&field.typ, field.name.to_string(), &mut header);
+                }
+                header
+            }
+            _ => vec![self.name.to_string()],
+        }
+    }
+}
+
+fn push_columns(typ: &SchemaType, path: String, header: &mut Vec<String>) {
+    match typ {
+        SchemaType::Struct(fields) => {
+            for field in fields {
+                push_columns(&field.typ, format!("{}.{}", path, field.name), header);
+            }
+        }
+        _ => header.push(path),
+    }
+}
+
+impl ValueNode {
+    /// Cells of this value for the columns in `header` (see
+    /// `SchemaNode::csv_header`). Columns the value has no field for, such
+    /// as hidden fields, are left empty.
+    pub fn to_csv_row(&self, header: &[String]) -> Vec<String> {
+        let mut cells = Vec::new();
+        match &self.value {
+            TypedValue::Struct(fields) => {
+                for field in fields {
+                    push_cells(&field.value, field.name.to_string(), &mut cells);
+                }
+            }
+            value => cells.push((self.name.to_string(), csv_cell(value))),
+        }
+        header
+            .iter()
+            .map(|column| {
+                cells
+                    .iter()
+                    .find(|(path, _)| path == column)
+                    .map(|(_, cell)| cell.clone())
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+}
+
+fn push_cells(value: &TypedValue, path: String, cells: &mut Vec<(String, String)>) {
+    match value {
+        TypedValue::Struct(fields) => {
+            for field in fields {
+                push_cells(&field.value, format!("{}.{}", path, field.name), cells);
+            }
+        }
+        value => cells.push((path, csv_cell(value))),
+    }
+}
+
+fn csv_cell(value: &TypedValue) -> String {
+    match value {
+        TypedValue::Empty => String::new(),
+        TypedValue::Pubkey(v) | TypedValue::String(v) => v.clone(),
+        TypedValue::Utf8 { text, .. } => text.clone(),
+        TypedValue::Bytes(v) => to_hex(v),
+        TypedValue::Option(v) => match v.as_ref() {
+            Some(TypedValue::Empty) => "true".to_string(),
+            v => v.as_ref().map(csv_cell).unwrap_or_default(),
+        },
+        TypedValue::Enum(v) if matches!(v.value, TypedValue::Empty) => v.name.to_string(),
+        // scalars render as themselves, collections as compact JSON
+        value => match serde_json::to_value(value) {
+            Ok(serde_json::Value::String(s)) => s,
+            Ok(other) => other.to_string(),
+            Err(_) => String::new(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::schema::{SchemaNode, SchemaType};
+
+    #[test]
+    fn header_and_row_line_up() {
+        let schema = SchemaNode::new_struct(
+            "MarketSizeParams",
+            vec![
+                ("bidsSize", SchemaType::U64),
+                ("asksSize", SchemaType::U64),
+                ("numSeats", SchemaType::U64),
+            ],
+        );
+        let mut data = Vec::new();
+        for v in [10u64, 20, 4096] {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        let value = schema
+            .deserialize_bytes(&mut data.as_slice(), false)
+            .unwrap()
+            .unwrap();
+
+        let header = schema.csv_header();
+        assert_eq!(header, vec!["bidsSize", "asksSize", "numSeats"]);
+        assert_eq!(value.to_csv_row(&header), vec!["10", "20", "4096"]);
+    }
+
+    #[test]
+    fn nested_structs_flatten_into_dotted_columns() {
+        let fees = SchemaNode::new_struct(
+            "Fees",
+            vec![("maker", SchemaType::I16), ("taker", SchemaType::U16)],
+        );
+        let schema = SchemaNode::new_struct(
+            "Market",
+            vec![
+                ("authority", SchemaType::Pubkey),
+                ("fees", fees.typ),
+                ("seed", SchemaType::array(2, SchemaType::U8)),
+                ("memo", SchemaType::option(SchemaType::String)),
+            ],
+        );
+        let authority = solana_program::pubkey::Pubkey::new_unique();
+        let mut data = authority.to_bytes().to_vec();
+        data.extend_from_slice(&(-2i16).to_le_bytes());
+        data.extend_from_slice(&5u16.to_le_bytes());
+        data.extend_from_slice(&[0xab, 0xcd, 0]);
+        let value = schema
+            .deserialize_bytes(&mut data.as_slice(), false)
+            .unwrap()
+            .unwrap();
+
+        let header = schema.csv_header();
+        assert_eq!(
+            header,
+            vec!["authority", "fees.maker", "fees.taker", "seed", "memo"]
+        );
+        assert_eq!(
+            value.to_csv_row(&header),
+            vec![
+                authority.to_string(),
+                "-2".into(),
+                "5".into(),
+                "abcd".into(),
+                "".into()
+            ]
+        );
+    }
+}
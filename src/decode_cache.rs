@@ -0,0 +1,147 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash as _, Hasher},
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use lru::LruCache;
+use solana_program::hash::{hashv, Hash};
+
+use crate::{on_chain_idl::ParsedAccountResult, schema::DecodeOptions};
+
+pub const DEFAULT_DECODE_CACHE_SIZE: usize = 1024;
+
+/// LRU cache of decoded accounts, keyed by a hash of the raw account bytes
+/// (discriminant included) and the decode options.
+pub struct DecodeCache {
+    entries: Mutex<LruCache<Hash, ParsedAccountResult>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecodeCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl DecodeCache {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn key(account_data: &[u8], options: &DecodeOptions) -> Hash {
+        let mut hasher = DefaultHasher::new();
+        options.hash(&mut hasher);
+        hashv(&[&hasher.finish().to_le_bytes(), account_data])
+    }
+
+    pub fn get(&self, key: &Hash) -> Option<ParsedAccountResult> {
+        let cached = self.entries.lock().ok()?.get(key).cloned();
+        match cached {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        cached
+    }
+
+    pub fn insert(&self, key: Hash, value: ParsedAccountResult) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.put(key, value);
+        }
+    }
+
+    pub fn stats(&self) -> DecodeCacheStats {
+        DecodeCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.entries
+            .lock()
+            .map(|entries| entries.cap().get())
+            .unwrap_or_default()
+    }
+}
+
+impl Default for DecodeCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_DECODE_CACHE_SIZE)
+    }
+}
+
+/// Lets `OnChainIdl` derive `Clone`. Cached results belong to the original,
+/// so clones start with an empty cache of the same capacity.
+impl Clone for DecodeCache {
+    fn clone(&self) -> Self {
+        Self::new(self.capacity())
+    }
+}
+
+/// Lets `OnChainIdl` derive `PartialEq`. The cache is not part of an IDL's
+/// identity, so any two caches compare equal, whatever they hold.
+impl PartialEq for DecodeCache {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for DecodeCache {}
+
+impl std::fmt::Debug for DecodeCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DecodeCache")
+            .field("capacity", &self.capacity())
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        decode_cache::DecodeCacheStats,
+        schema::IntWidth,
+        test_fixtures::{counter_account, counter_idl},
+    };
+
+    #[test]
+    fn second_decode_of_identical_bytes_hits_cache() {
+        let mut idl = counter_idl();
+        idl.enable_decode_cache(16);
+
+        let data = counter_account(7);
+
+        let first = idl.get_parsed_account(data.clone(), false).unwrap();
+        assert_eq!(
+            idl.decode_cache_stats(),
+            Some(DecodeCacheStats { hits: 0, misses: 1 })
+        );
+
+        let second = idl.get_parsed_account(data.clone(), false).unwrap();
+        assert_eq!(
+            idl.decode_cache_stats(),
+            Some(DecodeCacheStats { hits: 1, misses: 1 })
+        );
+        assert_eq!(first.value, second.value);
+
+        // decodes with another enum width aren't served from the cache
+        idl.enum_disc_width = IntWidth::U16;
+        idl.get_parsed_account(data, false).unwrap();
+        assert_eq!(
+            idl.decode_cache_stats(),
+            Some(DecodeCacheStats { hits: 1, misses: 2 })
+        );
+    }
+}
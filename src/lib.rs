@@ -1,4 +1,12 @@
+mod anchor_idl;
+pub mod coverage;
+mod csv;
+#[cfg(feature = "decode-cache")]
+pub mod decode_cache;
+mod native_schemas;
 pub mod on_chain_idl;
 pub mod parse_idl;
 pub mod schema;
+#[cfg(test)]
+mod test_fixtures;
 pub mod value;
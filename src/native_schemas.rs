@@ -0,0 +1,452 @@
+//! Schemas for well-known native Solana accounts that don't ship an IDL
+
+use crate::{
+    on_chain_idl::OnChainIdl,
+    schema::{IntWidth, SchemaNode, SchemaType, SmallVecLen},
+};
+
+impl OnChainIdl {
+    /// Address Lookup Table account: a 56-byte `LookupTableMeta` header
+    /// followed by the table's addresses.
+    ///
+    /// The header is bincode-encoded into a zeroed, fixed-size region, so the
+    /// `authority` option is modeled as a presence flag followed by the key
+    /// slot, which holds the default pubkey when there is no authority.
+    pub fn address_lookup_table_schema() -> SchemaNode {
+        let mut padding = SchemaNode::new("padding", SchemaType::U16);
+        padding.is_hidden = true;
+
+        SchemaNode::new(
+            "AddressLookupTable",
+            SchemaType::Struct(vec![
+                SchemaNode::new("typeIndex", SchemaType::U32),
+                SchemaNode::new("deactivationSlot", SchemaType::U64),
+                SchemaNode::new("lastExtendedSlot", SchemaType::U64),
+                SchemaNode::new("lastExtendedSlotStartIndex", SchemaType::U8),
+                SchemaNode::new("hasAuthority", SchemaType::Bool),
+                SchemaNode::new("authority", SchemaType::Pubkey),
+                padding,
+                SchemaNode::new(
+                    "addresses",
+                    SchemaType::RemainingVec(Box::new(SchemaType::Pubkey)),
+                ),
+            ]),
+        )
+    }
+
+    /// Metaplex Token Metadata `Metadata` account.
+    ///
+    /// `name`, `symbol` and `uri` are Borsh strings that Metaplex pads with
+    /// NUL bytes to 32, 10 and 200 bytes; the padding is kept in the decoded
+    /// text. Accounts are allocated at full size, so the optional trailing
+    /// fields of older accounts read as zeroes and decode as `None`.
+    pub fn metaplex_metadata_schema() -> SchemaNode {
+        let creator = SchemaNode::new_struct(
+            "Creator",
+            vec![
+                ("address", SchemaType::Pubkey),
+                ("verified", SchemaType::Bool),
+                ("share", SchemaType::U8),
+            ],
+        );
+        let collection = SchemaNode::new_struct(
+            "Collection",
+            vec![("verified", SchemaType::Bool), ("key", SchemaType::Pubkey)],
+        );
+        let uses = SchemaNode::new_struct(
+            "Uses",
+            vec![
+                ("useMethod", unit_enum(&["Burn", "Multiple", "Single"])),
+                ("remaining", SchemaType::U64),
+                ("total", SchemaType::U64),
+            ],
+        );
+        let collection_details = SchemaType::Enum(vec![
+            SchemaNode::new_struct("V1", vec![("size", SchemaType::U64)]),
+            SchemaNode::new_struct(
+                "V2",
+                vec![("padding", SchemaType::array(8, SchemaType::U8))],
+            ),
+        ]);
+        let programmable_config = SchemaType::Enum(vec![SchemaNode::new_struct(
+            "V1",
+            vec![("ruleSet", SchemaType::option(SchemaType::Pubkey))],
+        )]);
+
+        SchemaNode::new_struct(
+            "Metadata",
+            vec![
+                (
+                    "key",
+                    unit_enum(&[
+                        "Uninitialized",
+                        "EditionV1",
+                        "MasterEditionV1",
+                        "ReservationListV1",
+                        "MetadataV1",
+                        "ReservationListV2",
+                        "MasterEditionV2",
+                        "EditionMarker",
+                        "UseAuthorityRecord",
+                        "CollectionAuthorityRecord",
+                        "TokenOwnedEscrow",
+                        "TokenRecord",
+                        "MetadataDelegate",
+                        "EditionMarkerV2",
+                        "HolderDelegate",
+                    ]),
+                ),
+                ("updateAuthority", SchemaType::Pubkey),
+                ("mint", SchemaType::Pubkey),
+                ("name", SchemaType::String),
+                ("symbol", SchemaType::String),
+                ("uri", SchemaType::String),
+                ("sellerFeeBasisPoints", SchemaType::U16),
+                ("creators", SchemaType::option(SchemaType::vec(creator.typ))),
+                ("primarySaleHappened", SchemaType::Bool),
+                ("isMutable", SchemaType::Bool),
+                ("editionNonce", SchemaType::option(SchemaType::U8)),
+                (
+                    "tokenStandard",
+                    SchemaType::option(unit_enum(&[
+                        "NonFungible",
+                        "FungibleAsset",
+                        "Fungible",
+                        "NonFungibleEdition",
+                        "ProgrammableNonFungible",
+                        "ProgrammableNonFungibleEdition",
+                    ])),
+                ),
+                ("collection", SchemaType::option(collection.typ)),
+                ("uses", SchemaType::option(uses.typ)),
+                ("collectionDetails", SchemaType::option(collection_details)),
+                (
+                    "programmableConfig",
+                    SchemaType::option(programmable_config),
+                ),
+            ],
+        )
+    }
+
+    /// Stake program account (`StakeStateV2`).
+    ///
+    /// The state is bincode-encoded, so its variant tag is a `u32`. Tuple
+    /// variants are modeled as structs with one field per element.
+    pub fn stake_account_schema() -> SchemaNode {
+        let meta = SchemaNode::new_struct(
+            "Meta",
+            vec![
+                ("rentExemptReserve", SchemaType::U64),
+                (
+                    "authorized",
+                    SchemaNode::new_struct(
+                        "Authorized",
+                        vec![
+                            ("staker", SchemaType::Pubkey),
+                            ("withdrawer", SchemaType::Pubkey),
+                        ],
+                    )
+                    .typ,
+                ),
+                (
+                    "lockup",
+                    SchemaNode::new_struct(
+                        "Lockup",
+                        vec![
+                            ("unixTimestamp", SchemaType::UnixTimestamp),
+                            ("epoch", SchemaType::U64),
+                            ("custodian", SchemaType::Pubkey),
+                        ],
+                    )
+                    .typ,
+                ),
+            ],
+        );
+        let delegation = SchemaNode::new_struct(
+            "Delegation",
+            vec![
+                ("voterPubkey", SchemaType::Pubkey),
+                ("stake", SchemaType::U64),
+                ("activationEpoch", SchemaType::U64),
+                ("deactivationEpoch", SchemaType::U64),
+                ("warmupCooldownRate", SchemaType::F64),
+            ],
+        );
+        let stake = SchemaNode::new_struct(
+            "Stake",
+            vec![
+                ("delegation", delegation.typ),
+                ("creditsObserved", SchemaType::U64),
+            ],
+        );
+
+        SchemaNode::new(
+            "StakeStateV2",
+            SchemaType::TaggedEnum(
+                IntWidth::U32,
+                vec![
+                    SchemaNode::new("Uninitialized", SchemaType::Empty),
+                    SchemaNode::new_struct("Initialized", vec![("meta", meta.typ.clone())]),
+                    SchemaNode::new_struct(
+                        "Stake",
+                        vec![
+                            ("meta", meta.typ),
+                            ("stake", stake.typ),
+                            ("stakeFlags", SchemaType::U8),
+                        ],
+                    ),
+                    SchemaNode::new("RewardsPool", SchemaType::Empty),
+                ],
+            ),
+        )
+    }
+
+    /// Legacy (unversioned) transaction message, as found in a serialized
+    /// transaction after its signatures.
+    ///
+    /// Every list is prefixed with a compact-u16 length, and instructions
+    /// refer to their program and accounts by index into `accountKeys`.
+    pub fn legacy_message_schema() -> SchemaNode {
+        let short_vec = |elem| SchemaType::SmallVec(SmallVecLen::ShortU16, Box::new(elem));
+        let header = SchemaNode::new_struct(
+            "MessageHeader",
+            vec![
+                ("numRequiredSignatures", SchemaType::U8),
+                ("numReadonlySignedAccounts", SchemaType::U8),
+                ("numReadonlyUnsignedAccounts", SchemaType::U8),
+            ],
+        );
+        let instruction = SchemaNode::new_struct(
+            "CompiledInstruction",
+            vec![
+                ("programIdIndex", SchemaType::U8),
+                ("accounts", short_vec(SchemaType::U8)),
+                ("data", short_vec(SchemaType::U8)),
+            ],
+        );
+
+        SchemaNode::new_struct(
+            "Message",
+            vec![
+                ("header", header.typ),
+                ("accountKeys", short_vec(SchemaType::Pubkey)),
+                ("recentBlockhash", SchemaType::Pubkey),
+                ("instructions", short_vec(instruction.typ)),
+            ],
+        )
+    }
+}
+
+/// Borsh enum whose variants carry no data
+fn unit_enum(variants: &[&str]) -> SchemaType {
+    SchemaType::Enum(
+        variants
+            .iter()
+            .map(|name| SchemaNode::new(*name, SchemaType::Empty))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{on_chain_idl::OnChainIdl, value::TypedValue};
+    use solana_program::pubkey::Pubkey;
+
+    fn lookup_table(authority: Option<Pubkey>, addresses: &[Pubkey]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&1u32.to_le_bytes()); // ProgramState::LookupTable
+        data.extend_from_slice(&u64::MAX.to_le_bytes());
+        data.extend_from_slice(&250_000_000u64.to_le_bytes());
+        data.push(3);
+        match authority {
+            Some(authority) => {
+                data.push(1);
+                data.extend_from_slice(authority.as_ref());
+            }
+            None => data.push(0),
+        }
+        data.resize(56, 0);
+        for address in addresses {
+            data.extend_from_slice(address.as_ref());
+        }
+        data
+    }
+
+    #[test]
+    fn decodes_address_lookup_table_addresses() {
+        let schema = OnChainIdl::address_lookup_table_schema();
+        let authority = Pubkey::new_from_array([9; 32]);
+        let addresses = [
+            Pubkey::new_from_array([1; 32]),
+            Pubkey::new_from_array([2; 32]),
+        ];
+        let expected_addresses = TypedValue::Vec(
+            addresses
+                .iter()
+                .map(|address| TypedValue::from(*address))
+                .collect(),
+        );
+
+        let data = lookup_table(Some(authority), &addresses);
+        let value = schema
+            .deserialize_bytes(&mut data.as_slice(), false)
+            .unwrap()
+            .unwrap();
+        let TypedValue::Struct(fields) = value.value else {
+            panic!("expected a struct");
+        };
+        let field = |name: &str| &fields.iter().find(|f| &*f.name == name).unwrap().value;
+        assert_eq!(field("lastExtendedSlotStartIndex"), &TypedValue::U8(3));
+        assert_eq!(field("hasAuthority"), &TypedValue::Bool(true));
+        assert_eq!(field("authority"), &TypedValue::from(authority));
+        assert_eq!(field("addresses"), &expected_addresses);
+        assert!(fields.iter().all(|f| &*f.name != "padding"));
+
+        // a frozen table has no authority but the addresses stay at offset 56
+        let data = lookup_table(None, &addresses);
+        let value = schema
+            .deserialize_bytes(&mut data.as_slice(), false)
+            .unwrap()
+            .unwrap();
+        let TypedValue::Struct(fields) = value.value else {
+            panic!("expected a struct");
+        };
+        let field = |name: &str| &fields.iter().find(|f| &*f.name == name).unwrap().value;
+        assert_eq!(field("hasAuthority"), &TypedValue::Bool(false));
+        assert_eq!(field("addresses"), &expected_addresses);
+    }
+
+    #[test]
+    fn decodes_metaplex_metadata_strings() {
+        fn padded(text: &str, len: usize) -> Vec<u8> {
+            let mut out = (len as u32).to_le_bytes().to_vec();
+            out.extend_from_slice(text.as_bytes());
+            out.resize(4 + len, 0);
+            out
+        }
+
+        let schema = OnChainIdl::metaplex_metadata_schema();
+        let update_authority = Pubkey::new_from_array([7; 32]);
+        let mint = Pubkey::new_from_array([8; 32]);
+        let creator = Pubkey::new_from_array([9; 32]);
+
+        let mut data = vec![4]; // Key::MetadataV1
+        data.extend_from_slice(update_authority.as_ref());
+        data.extend_from_slice(mint.as_ref());
+        data.extend(padded("Degen Ape #1", 32));
+        data.extend(padded("DAPE", 10));
+        data.extend(padded("https://arweave.net/ape1.json", 200));
+        data.extend_from_slice(&420u16.to_le_bytes());
+        data.extend_from_slice(&[1, 1, 0, 0, 0]); // Some(vec![creator])
+        data.extend_from_slice(creator.as_ref());
+        data.extend_from_slice(&[1, 100]);
+        data.extend_from_slice(&[1, 1]); // primary sale happened, mutable
+        data.extend_from_slice(&[1, 255]); // edition nonce
+        data.extend_from_slice(&[1, 4]); // ProgrammableNonFungible
+        data.resize(679, 0);
+
+        let value = schema
+            .deserialize_bytes(&mut data.as_slice(), false)
+            .unwrap()
+            .unwrap();
+        let TypedValue::Struct(fields) = value.value else {
+            panic!("expected a struct");
+        };
+        let field = |name: &str| &fields.iter().find(|f| &*f.name == name).unwrap().value;
+        let text = |name: &str| match field(name) {
+            TypedValue::String(text) => text.trim_end_matches('\0').to_string(),
+            other => panic!("expected a string, got {:?}", other),
+        };
+
+        assert_eq!(field("mint"), &TypedValue::from(mint));
+        assert_eq!(text("name"), "Degen Ape #1");
+        assert_eq!(text("symbol"), "DAPE");
+        assert_eq!(text("uri"), "https://arweave.net/ape1.json");
+        assert_eq!(field("sellerFeeBasisPoints"), &TypedValue::U16(420));
+        assert_eq!(
+            serde_json::to_value(field("tokenStandard")).unwrap(),
+            serde_json::json!("ProgrammableNonFungible")
+        );
+        assert_eq!(field("collection"), &TypedValue::Option(Box::new(None)));
+    }
+
+    #[test]
+    fn decodes_initialized_stake_account() {
+        let staker = Pubkey::new_from_array([3; 32]);
+        let withdrawer = Pubkey::new_from_array([4; 32]);
+
+        let mut data = 1u32.to_le_bytes().to_vec(); // Initialized
+        data.extend_from_slice(&2_282_880u64.to_le_bytes());
+        data.extend_from_slice(staker.as_ref());
+        data.extend_from_slice(withdrawer.as_ref());
+        data.extend_from_slice(&0i64.to_le_bytes());
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.extend_from_slice(Pubkey::default().as_ref());
+        data.resize(200, 0);
+
+        let value = OnChainIdl::stake_account_schema()
+            .deserialize_bytes(&mut data.as_slice(), false)
+            .unwrap()
+            .unwrap();
+        let TypedValue::Enum(variant) = value.value else {
+            panic!("expected an enum");
+        };
+        assert_eq!(&*variant.name, "Initialized");
+        assert_eq!(
+            serde_json::to_value(&variant.value).unwrap()["meta"],
+            serde_json::json!({
+                "rentExemptReserve": "2282880",
+                "authorized": {
+                    "staker": staker.to_string(),
+                    "withdrawer": withdrawer.to_string(),
+                },
+                "lockup": {
+                    "unixTimestamp": "0",
+                    "epoch": "0",
+                    "custodian": Pubkey::default().to_string(),
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn decodes_legacy_message() {
+        let payer = Pubkey::new_from_array([1; 32]);
+        let program = Pubkey::new_from_array([2; 32]);
+        let blockhash = Pubkey::new_from_array([9; 32]);
+        let ix_data = vec![0xab; 130];
+
+        let mut data = vec![1, 0, 1]; // header
+        data.push(2);
+        data.extend_from_slice(payer.as_ref());
+        data.extend_from_slice(program.as_ref());
+        data.extend_from_slice(blockhash.as_ref());
+        data.push(1); // one instruction
+        data.push(1); // program id index
+        data.extend_from_slice(&[1, 0]); // accounts: [0]
+        data.extend_from_slice(&[0x82, 0x01]); // data length 130
+        data.extend_from_slice(&ix_data);
+
+        let schema = OnChainIdl::legacy_message_schema();
+        let mut rest = data.as_slice();
+        let value = schema.deserialize_bytes(&mut rest, false).unwrap().unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(
+            serde_json::to_value(&value.value).unwrap(),
+            serde_json::json!({
+                "header": {
+                    "numRequiredSignatures": 1,
+                    "numReadonlySignedAccounts": 0,
+                    "numReadonlyUnsignedAccounts": 1,
+                },
+                "accountKeys": [payer.to_string(), program.to_string()],
+                "recentBlockhash": blockhash.to_string(),
+                "instructions": [{
+                    "programIdIndex": 1,
+                    "accounts": serde_json::to_value(TypedValue::Bytes(vec![0])).unwrap(),
+                    "data": serde_json::to_value(TypedValue::Bytes(ix_data)).unwrap(),
+                }],
+            })
+        );
+    }
+}
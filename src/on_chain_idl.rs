@@ -1,79 +1,540 @@
+use base64::Engine;
 use borsh::{BorshDeserialize, BorshSerialize};
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    io::{Read, Write},
+};
 
+#[cfg(feature = "decode-cache")]
+use crate::decode_cache::{DecodeCache, DecodeCacheStats};
 use crate::{
-    schema::{SchemaNode, SchemaType},
+    schema::{DecodeOptions, IntWidth, NodeLayout, SchemaNode, SchemaType},
     value::{TypedValue, ValueNode},
 };
 
+/// An account an instruction declares, with the flags the IDL gives it
+#[derive(
+    Debug,
+    Clone,
+    Default,
+    PartialEq,
+    Eq,
+    BorshDeserialize,
+    BorshSerialize,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct AccountMeta {
+    /// dotted for accounts of a composite group, e.g. `pool.vault`
+    pub name: String,
+    pub is_mut: bool,
+    pub is_signer: bool,
+    pub is_optional: bool,
+}
+
+impl AccountMeta {
+    /// An account known only by name, with every flag unset
+    pub fn named(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Debug, Clone, BorshDeserialize, BorshSerialize, PartialEq, Eq)]
 pub struct InstructionDecoder {
-    pub accounts: Vec<String>,
+    pub accounts: Vec<AccountMeta>,
     pub instruction_args_parser: SchemaNode,
+    pub docs: Vec<String>,
+    /// kept for decoding old transactions, but no longer offered to callers
+    pub deprecated: bool,
+    /// `instruction_disc_len` of the owning IDL, copied in when it is parsed
+    /// or loaded since the stored layout doesn't repeat it per instruction
+    #[borsh_skip]
+    pub(crate) disc_len: u8,
+    /// `enum_disc_width` of the owning IDL, copied in alongside `disc_len`
+    #[borsh_skip]
+    pub(crate) enum_disc_width: IntWidth,
 }
 
-#[derive(Debug, Clone, BorshDeserialize, BorshSerialize, PartialEq, Eq)]
+impl InstructionDecoder {
+    /// Shortest instruction data, discriminator included, that could decode
+    /// against this instruction; anything shorter is certainly truncated.
+    pub fn min_data_len(&self) -> usize {
+        self.disc_len as usize
+            + self
+                .instruction_args_parser
+                .typ
+                .min_size_with(self.enum_disc_width)
+    }
+}
+
+/// Where the discriminator sits in account data
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, BorshDeserialize, BorshSerialize)]
+pub enum DiscPosition {
+    #[default]
+    Prefix,
+    /// the last `account_disc_len` bytes, for the few programs that tag
+    /// accounts at the end
+    Suffix,
+}
+
+/// How integer discriminators (`{ "type": "u64", "value": 1 }`) are laid
+/// out in data; byte-array discriminators are matched as written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, BorshDeserialize, BorshSerialize)]
+pub enum DiscByteOrder {
+    #[default]
+    LittleEndian,
+    /// the value is written big-endian, for programs that document
+    /// big-endian tags
+    BigEndian,
+}
+
+impl DiscByteOrder {
+    /// The `len` discriminator bytes of an integer value, as they appear on chain
+    pub(crate) fn bytes(self, key: u64, len: u8) -> Vec<u8> {
+        let len = (len as usize).min(8);
+        match self {
+            DiscByteOrder::LittleEndian => key.to_le_bytes()[..len].to_vec(),
+            DiscByteOrder::BigEndian => key.to_be_bytes()[8 - len..].to_vec(),
+        }
+    }
+}
+
+/// Discriminators are kept as the raw bytes data starts with, so they can be
+/// longer than 8 bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct OnChainIdl {
     pub program_name: String,
     pub account_disc_len: u8,
     pub instruction_disc_len: u8,
-    pub accounts: Vec<(u64, SchemaNode)>,
-    pub instruction_params: Vec<(u64, InstructionDecoder)>,
+    pub accounts: Vec<(Vec<u8>, SchemaNode)>,
+    pub instruction_params: Vec<(Vec<u8>, InstructionDecoder)>,
+    /// every defined type of the IDL, sorted by name
+    pub types: Vec<SchemaNode>,
+    pub event_disc_len: u8,
+    pub events: Vec<(Vec<u8>, SchemaNode)>,
+    /// accounts start with a `version: u8` byte ahead of the discriminator,
+    /// for programs that version their account layouts
+    pub account_version_prefix: bool,
+    pub account_disc_position: DiscPosition,
+    pub disc_byte_order: DiscByteOrder,
+    /// program-level docs
+    pub docs: Vec<String>,
+    /// docs of each account, by account name; accounts without docs are left out
+    pub account_docs: BTreeMap<String, Vec<String>>,
+    /// names of accounts marked deprecated; they still decode
+    pub deprecated_accounts: BTreeSet<String>,
+    /// bytes allocated for each account, by account name, when the IDL
+    /// declares them (e.g. Anchor's `8 + InitSpace`), discriminator included
+    pub account_spaces: BTreeMap<String, u64>,
+    /// width of the discriminant of every enum that doesn't declare one
+    /// (`TaggedEnum`), for programs that don't use Borsh's `u8`. Applies to
+    /// accounts, instruction args and events alike, for decoding and
+    /// encoding; `DecodeOptions::enum_disc_width` overrides it.
+    pub enum_disc_width: IntWidth,
+    #[cfg(feature = "decode-cache")]
+    pub decode_cache: Option<DecodeCache>,
+}
+
+/// Starts every versioned `OnChainIdl` layout. Unversioned layouts start with
+/// the length of `program_name`, which is never this large.
+const LAYOUT_MARKER: u32 = u32::MAX;
+/// Current layout: 2 stored discriminators as raw bytes, 3 added
+/// `account_spaces`, 4 added the flags of instruction accounts, 5 added
+/// `enum_disc_width`
+const LAYOUT_VERSION: u8 = 5;
+
+/// `InstructionDecoder` before its accounts carried flags (layouts up to 3)
+#[derive(BorshDeserialize, BorshSerialize)]
+struct LegacyInstructionDecoder {
+    accounts: Vec<String>,
+    instruction_args_parser: SchemaNode,
+    docs: Vec<String>,
+    deprecated: bool,
+}
+
+impl From<LegacyInstructionDecoder> for InstructionDecoder {
+    fn from(legacy: LegacyInstructionDecoder) -> Self {
+        Self {
+            accounts: legacy
+                .accounts
+                .into_iter()
+                .map(AccountMeta::named)
+                .collect(),
+            instruction_args_parser: legacy.instruction_args_parser,
+            docs: legacy.docs,
+            deprecated: legacy.deprecated,
+            disc_len: 0,
+            enum_disc_width: IntWidth::U8,
+        }
+    }
+}
+
+/// Layouts written before `OnChainIdl` started with `LAYOUT_MARKER`, oldest
+/// first. They keyed discriminators by their value as a `u64` and all start
+/// with `program_name`, so a blob is read with the newest layout that
+/// consumes it exactly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum UnversionedLayout {
+    /// the first layout: discriminator lengths, accounts and instructions
+    Baseline,
+    /// added `types`
+    Types,
+    /// added `event_disc_len` and `events`
+    Events,
+    /// added `SchemaNode::serialization_order`
+    SerializationOrder,
+    /// added `account_version_prefix`
+    VersionPrefix,
+    /// added `docs`, `account_docs` and the docs of instructions
+    Docs,
+    /// added `account_disc_position`
+    DiscPosition,
+    /// added `deprecated_accounts` and deprecated instructions
+    Deprecated,
+    /// added `disc_byte_order`; the last layout before versioning
+    ByteOrder,
+}
+
+impl UnversionedLayout {
+    const ALL: [UnversionedLayout; 9] = [
+        Self::Baseline,
+        Self::Types,
+        Self::Events,
+        Self::SerializationOrder,
+        Self::VersionPrefix,
+        Self::Docs,
+        Self::DiscPosition,
+        Self::Deprecated,
+        Self::ByteOrder,
+    ];
+
+    fn nodes(self) -> NodeLayout {
+        if self >= Self::SerializationOrder {
+            NodeLayout::Current
+        } else {
+            NodeLayout::WithoutSerializationOrder
+        }
+    }
+
+    /// Reads the fields following `program_name`
+    fn read(self, program_name: String, bytes: &mut &[u8]) -> std::io::Result<OnChainIdl> {
+        let nodes = self.nodes();
+        let node = |bytes: &mut &[u8]| SchemaNode::deserialize_with_layout(bytes, nodes);
+        let keyed = |bytes: &mut &[u8]| {
+            read_list(bytes, |bytes| {
+                Ok((u64::deserialize_reader(bytes)?, node(bytes)?))
+            })
+        };
+
+        let account_disc_len = u8::deserialize_reader(bytes)?;
+        let instruction_disc_len = u8::deserialize_reader(bytes)?;
+        let accounts = keyed(bytes)?;
+        let instruction_params = read_list(bytes, |bytes| {
+            let key = u64::deserialize_reader(bytes)?;
+            let decoder = LegacyInstructionDecoder {
+                accounts: Vec::deserialize_reader(bytes)?,
+                instruction_args_parser: node(bytes)?,
+                docs: if self >= Self::Docs {
+                    Vec::deserialize_reader(bytes)?
+                } else {
+                    Vec::new()
+                },
+                deprecated: self >= Self::Deprecated && bool::deserialize_reader(bytes)?,
+            };
+            Ok((key, decoder))
+        })?;
+        let mut idl = OnChainIdl {
+            program_name,
+            account_disc_len,
+            instruction_disc_len,
+            accounts: Vec::new(),
+            instruction_params: Vec::new(),
+            types: Vec::new(),
+            event_disc_len: 8,
+            events: Vec::new(),
+            account_version_prefix: false,
+            account_disc_position: DiscPosition::default(),
+            disc_byte_order: DiscByteOrder::default(),
+            docs: Vec::new(),
+            account_docs: BTreeMap::new(),
+            deprecated_accounts: BTreeSet::new(),
+            account_spaces: BTreeMap::new(),
+            enum_disc_width: IntWidth::U8,
+            #[cfg(feature = "decode-cache")]
+            decode_cache: None,
+        };
+        if self >= Self::Types {
+            idl.types = read_list(bytes, node)?;
+        }
+        let mut events = Vec::new();
+        if self >= Self::Events {
+            idl.event_disc_len = u8::deserialize_reader(bytes)?;
+            events = keyed(bytes)?;
+        }
+        if self >= Self::VersionPrefix {
+            idl.account_version_prefix = bool::deserialize_reader(bytes)?;
+        }
+        if self >= Self::DiscPosition {
+            idl.account_disc_position = DiscPosition::deserialize_reader(bytes)?;
+        }
+        if self >= Self::ByteOrder {
+            idl.disc_byte_order = DiscByteOrder::deserialize_reader(bytes)?;
+        }
+        if self >= Self::Docs {
+            idl.docs = Vec::deserialize_reader(bytes)?;
+            idl.account_docs = BTreeMap::deserialize_reader(bytes)?;
+        }
+        if self >= Self::Deprecated {
+            idl.deprecated_accounts = BTreeSet::deserialize_reader(bytes)?;
+        }
+
+        let order = idl.disc_byte_order;
+        let bytes = |entries: Vec<(u64, SchemaNode)>, len: u8| {
+            entries
+                .into_iter()
+                .map(|(key, schema)| (order.bytes(key, len), schema))
+                .collect()
+        };
+        idl.accounts = bytes(accounts, account_disc_len);
+        idl.events = bytes(events, idl.event_disc_len);
+        idl.instruction_params = instruction_params
+            .into_iter()
+            .map(|(key, decoder)| (order.bytes(key, instruction_disc_len), decoder.into()))
+            .collect();
+        idl.size_instruction_decoders();
+        Ok(idl)
+    }
+}
+
+/// A Borsh `Vec` whose elements are read by `read`
+fn read_list<T>(
+    bytes: &mut &[u8],
+    mut read: impl FnMut(&mut &[u8]) -> std::io::Result<T>,
+) -> std::io::Result<Vec<T>> {
+    let len = u32::deserialize_reader(bytes)?;
+    (0..len).map(|_| read(bytes)).collect()
+}
+
+impl BorshSerialize for OnChainIdl {
+    fn serialize<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        LAYOUT_MARKER.serialize(writer)?;
+        LAYOUT_VERSION.serialize(writer)?;
+        self.program_name.serialize(writer)?;
+        self.account_disc_len.serialize(writer)?;
+        self.instruction_disc_len.serialize(writer)?;
+        self.accounts.serialize(writer)?;
+        self.instruction_params.serialize(writer)?;
+        self.types.serialize(writer)?;
+        self.event_disc_len.serialize(writer)?;
+        self.events.serialize(writer)?;
+        self.account_version_prefix.serialize(writer)?;
+        self.account_disc_position.serialize(writer)?;
+        self.disc_byte_order.serialize(writer)?;
+        self.docs.serialize(writer)?;
+        self.account_docs.serialize(writer)?;
+        self.deprecated_accounts.serialize(writer)?;
+        self.account_spaces.serialize(writer)?;
+        self.enum_disc_width.serialize(writer)
+    }
+}
+
+impl BorshDeserialize for OnChainIdl {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+        let marker = u32::deserialize_reader(reader)?;
+        if marker != LAYOUT_MARKER {
+            // unversioned: the marker was the length of `program_name`, and
+            // the blob runs to the end of the input
+            let mut name = Vec::new();
+            reader.take(marker as u64).read_to_end(&mut name)?;
+            if name.len() != marker as usize {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+            let program_name = String::from_utf8(name)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+            let mut rest = Vec::new();
+            reader.read_to_end(&mut rest)?;
+            return UnversionedLayout::ALL
+                .iter()
+                .rev()
+                .find_map(|layout| {
+                    let mut bytes = rest.as_slice();
+                    let idl = layout.read(program_name.clone(), &mut bytes).ok()?;
+                    bytes.is_empty().then_some(idl)
+                })
+                .ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "Data matches no known OnChainIdl layout",
+                    )
+                });
+        }
+
+        let version = u8::deserialize_reader(reader)?;
+        if !(2..=LAYOUT_VERSION).contains(&version) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Unknown OnChainIdl layout version {}", version),
+            ));
+        }
+        let mut idl = Self {
+            program_name: BorshDeserialize::deserialize_reader(reader)?,
+            account_disc_len: BorshDeserialize::deserialize_reader(reader)?,
+            instruction_disc_len: BorshDeserialize::deserialize_reader(reader)?,
+            accounts: BorshDeserialize::deserialize_reader(reader)?,
+            instruction_params: match version {
+                2 | 3 => Vec::<(Vec<u8>, LegacyInstructionDecoder)>::deserialize_reader(reader)?
+                    .into_iter()
+                    .map(|(disc, decoder)| (disc, decoder.into()))
+                    .collect(),
+                _ => BorshDeserialize::deserialize_reader(reader)?,
+            },
+            types: BorshDeserialize::deserialize_reader(reader)?,
+            event_disc_len: BorshDeserialize::deserialize_reader(reader)?,
+            events: BorshDeserialize::deserialize_reader(reader)?,
+            account_version_prefix: BorshDeserialize::deserialize_reader(reader)?,
+            account_disc_position: BorshDeserialize::deserialize_reader(reader)?,
+            disc_byte_order: BorshDeserialize::deserialize_reader(reader)?,
+            docs: BorshDeserialize::deserialize_reader(reader)?,
+            account_docs: BorshDeserialize::deserialize_reader(reader)?,
+            deprecated_accounts: BorshDeserialize::deserialize_reader(reader)?,
+            account_spaces: match version {
+                2 => BTreeMap::new(),
+                _ => BorshDeserialize::deserialize_reader(reader)?,
+            },
+            enum_disc_width: match version {
+                2..=4 => IntWidth::U8,
+                _ => BorshDeserialize::deserialize_reader(reader)?,
+            },
+            #[cfg(feature = "decode-cache")]
+            decode_cache: None,
+        };
+        idl.size_instruction_decoders();
+        Ok(idl)
+    }
 }
 
 impl OnChainIdl {
+    /// Enables the decode cache for `get_parsed_account`, keeping at most
+    /// `capacity` decoded accounts.
+    #[cfg(feature = "decode-cache")]
+    pub fn enable_decode_cache(&mut self, capacity: usize) {
+        self.decode_cache = Some(DecodeCache::new(capacity));
+    }
+
+    #[cfg(feature = "decode-cache")]
+    pub fn decode_cache_stats(&self) -> Option<DecodeCacheStats> {
+        self.decode_cache.as_ref().map(|cache| cache.stats())
+    }
+
+    /// `options` with the discriminant width of untagged enums defaulting to
+    /// `enum_disc_width`
+    fn resolve_options(&self, options: &DecodeOptions) -> DecodeOptions {
+        DecodeOptions {
+            enum_disc_width: options.enum_disc_width.or(Some(self.enum_disc_width)),
+            ..options.clone()
+        }
+    }
+
+    /// Default options for decoding with this IDL
+    pub(crate) fn decode_options(&self, show_hidden: bool) -> DecodeOptions {
+        self.resolve_options(&DecodeOptions::new(show_hidden))
+    }
+
     pub fn get_parsed_instruction(
         &self,
         instruction_data: Vec<u8>,
         account_keys: &[String],
         show_hidden: bool,
     ) -> anyhow::Result<ParsedInstructionResult> {
+        self.get_parsed_instruction_with_options(
+            instruction_data,
+            account_keys,
+            &DecodeOptions::new(show_hidden),
+        )
+    }
+
+    pub fn get_parsed_instruction_with_options(
+        &self,
+        instruction_data: Vec<u8>,
+        account_keys: &[String],
+        options: &DecodeOptions,
+    ) -> anyhow::Result<ParsedInstructionResult> {
+        let options = &self.resolve_options(options);
         let data = instruction_data;
         if data.len() < self.instruction_disc_len as usize {
             return Err(anyhow::anyhow!("Instruction data is too short"));
         }
 
-        let mut padded_data = [0u8; 8];
-        let slice_len = std::cmp::min(self.instruction_disc_len as usize, 8);
-        padded_data[..slice_len].copy_from_slice(&data[..slice_len]);
-        let discriminant = u64::from_le_bytes(padded_data);
+        let discriminant = self.get_instruction_discriminator(&data);
 
         let instruction_decoder = self
-            .instruction_params
-            .iter()
-            .find(|(disc, _)| *disc == discriminant)
-            .map(|(_, decoder)| decoder)
+            .match_instruction(&data)
             .ok_or(anyhow::anyhow!("Instruction discriminant not found"))?;
 
-        let mut account_names = vec![];
-        let mut accounts_map = HashMap::new();
-        for (i, address) in account_keys.iter().enumerate() {
-            let name = instruction_decoder
-                .accounts
-                .get(i)
-                .cloned()
-                .unwrap_or(format!("Account {}", i + 1));
-            account_names.push(name.clone());
-            accounts_map.insert(name, address.clone());
-        }
+        let mut result = decode_instruction(
+            instruction_decoder,
+            &mut &data[self.instruction_disc_len as usize..],
+            account_keys,
+            options,
+        )?;
+        result.discriminator = discriminant.to_vec();
+        Ok(result)
+    }
 
-        let schema = instruction_decoder.instruction_args_parser.clone();
+    /// Like `get_parsed_instruction`, but returns the args decoded before a
+    /// mid-struct error alongside it, to help debug IDL mismatches
+    pub fn get_parsed_instruction_partial(
+        &self,
+        instruction_data: Vec<u8>,
+        show_hidden: bool,
+    ) -> (Option<ValueNode>, Option<anyhow::Error>) {
+        if instruction_data.len() < self.instruction_disc_len as usize {
+            return (None, Some(anyhow::anyhow!("Instruction data is too short")));
+        }
+        let discriminant = self.get_instruction_discriminator(&instruction_data);
+        let Some((_, instruction_decoder)) = self
+            .instruction_params
+            .iter()
+            .find(|(disc, _)| disc.as_slice() == discriminant)
+        else {
+            return (
+                None,
+                Some(anyhow::anyhow!("Instruction discriminant not found")),
+            );
+        };
 
-        let args: ValueNode = schema
-            .deserialize_bytes(
-                &mut &data[self.instruction_disc_len as usize..],
-                show_hidden,
-            )?
-            .ok_or(anyhow::anyhow!(
-                "is_hidden shouldn't be true in instructions"
-            ))?;
+        instruction_decoder
+            .instruction_args_parser
+            .deserialize_bytes_partial(
+                &mut &instruction_data[self.instruction_disc_len as usize..],
+                &self.decode_options(show_hidden),
+            )
+    }
 
-        Ok(ParsedInstructionResult::new(
-            schema,
-            account_names,
-            accounts_map,
-            args.value,
-        ))
+    /// Diagnostic for mystery instructions: decodes the payload after the
+    /// discriminator slot against every instruction schema, ignoring the
+    /// discriminator itself, and returns each decode that consumes the whole
+    /// buffer without error. Hidden fields are always shown.
+    pub fn brute_force_instruction(
+        &self,
+        data: &[u8],
+        accounts: &[String],
+    ) -> Vec<ParsedInstructionResult> {
+        let Some(payload) = data.get(self.instruction_disc_len as usize..) else {
+            return vec![];
+        };
+        let options = self.decode_options(true);
+        self.instruction_params
+            .iter()
+            .filter_map(|(_, decoder)| {
+                let mut bytes = payload;
+                let result = decode_instruction(decoder, &mut bytes, accounts, &options).ok()?;
+                bytes.is_empty().then_some(result)
+            })
+            .collect()
     }
 
     pub fn get_parsed_account(
@@ -81,45 +542,562 @@ impl OnChainIdl {
         account_data: Vec<u8>,
         show_hidden: bool,
     ) -> anyhow::Result<ParsedAccountResult> {
+        self.get_parsed_account_with_options(account_data, &DecodeOptions::new(show_hidden))
+    }
+
+    pub fn get_parsed_account_with_options(
+        &self,
+        account_data: Vec<u8>,
+        options: &DecodeOptions,
+    ) -> anyhow::Result<ParsedAccountResult> {
+        // resolved first so the cache key changes with `enum_disc_width`
+        let options = &self.resolve_options(options);
+        #[cfg(feature = "decode-cache")]
+        let cache_key = match &self.decode_cache {
+            Some(cache) => {
+                let key = DecodeCache::key(&account_data, options);
+                if let Some(cached) = cache.get(&key) {
+                    return Ok(cached);
+                }
+                Some(key)
+            }
+            None => None,
+        };
+
+        let (version, account_data) = self.split_account_version(&account_data)?;
         if account_data.len() < self.account_disc_len as usize {
             return Err(anyhow::anyhow!("Account data is too short"));
         }
 
-        let discriminant = self.get_account_discriminator(&account_data);
+        let discriminant = self.get_account_discriminator(account_data);
 
         let account_schema = self
             .accounts
             .iter()
-            .find(|(disc, _)| *disc == discriminant)
+            .find(|(disc, _)| disc.as_slice() == discriminant)
             .map(|(_, schema)| schema)
             .ok_or(anyhow::anyhow!("Account discriminant not found"))?
             .clone();
 
+        let mut body = self.account_body(account_data);
+        let body_len = body.len();
         let value: ValueNode = account_schema
-            .deserialize_bytes(
-                &mut &account_data[self.account_disc_len as usize..],
-                show_hidden,
+            .deserialize_bytes_with_options(&mut body, options)?
+            .ok_or(anyhow::anyhow!("Account type shouldn't be hidden"))?;
+        let warnings = self.account_space_warnings(&account_schema, body_len - body.len());
+
+        let mut result = ParsedAccountResult::new(account_schema, value.value);
+        result.version = version;
+        result.discriminator = discriminant.to_vec();
+        result.warnings = warnings;
+
+        #[cfg(feature = "decode-cache")]
+        if let (Some(cache), Some(key)) = (&self.decode_cache, cache_key) {
+            cache.insert(key, result.clone());
+        }
+
+        Ok(result)
+    }
+
+    /// Like `get_parsed_account`, but returns the fields decoded before a
+    /// mid-struct error alongside it, to help debug IDL mismatches
+    pub fn get_parsed_account_partial(
+        &self,
+        account_data: Vec<u8>,
+        show_hidden: bool,
+    ) -> (Option<ValueNode>, Option<anyhow::Error>) {
+        let account_data = match self.split_account_version(&account_data) {
+            Ok((_, account_data)) => account_data,
+            Err(e) => return (None, Some(e)),
+        };
+        if account_data.len() < self.account_disc_len as usize {
+            return (None, Some(anyhow::anyhow!("Account data is too short")));
+        }
+        let discriminant = self.get_account_discriminator(account_data);
+        let Some((_, account_schema)) = self
+            .accounts
+            .iter()
+            .find(|(disc, _)| disc.as_slice() == discriminant)
+        else {
+            return (
+                None,
+                Some(anyhow::anyhow!("Account discriminant not found")),
+            );
+        };
+
+        account_schema.deserialize_bytes_partial(
+            &mut self.account_body(account_data),
+            &self.decode_options(show_hidden),
+        )
+    }
+
+    /// Decodes an account against `schema` instead of the schema its
+    /// discriminator maps to, e.g. to try out a layout change without
+    /// rebuilding the IDL. The discriminator (and version byte, if any) is
+    /// stripped but not checked.
+    pub fn get_parsed_account_with_schema(
+        &self,
+        account_data: &[u8],
+        schema: &SchemaNode,
+        show_hidden: bool,
+    ) -> anyhow::Result<ParsedAccountResult> {
+        let (version, account_data) = self.split_account_version(account_data)?;
+        if account_data.len() < self.account_disc_len as usize {
+            return Err(anyhow::anyhow!("Account data is too short"));
+        }
+
+        let value: ValueNode = schema
+            .deserialize_bytes_with_options(
+                &mut self.account_body(account_data),
+                &self.decode_options(show_hidden),
             )?
             .ok_or(anyhow::anyhow!("Account type shouldn't be hidden"))?;
 
-        Ok(ParsedAccountResult::new(account_schema, value.value))
+        let mut result = ParsedAccountResult::new(schema.clone(), value.value);
+        result.version = version;
+        result.discriminator = self.get_account_discriminator(account_data).to_vec();
+        Ok(result)
+    }
+
+    /// Decodes many accounts in parallel on the rayon thread pool. Results are
+    /// in the same order as `data`.
+    #[cfg(feature = "rayon")]
+    pub fn par_get_parsed_accounts(
+        &self,
+        data: &[Vec<u8>],
+        show_hidden: bool,
+    ) -> Vec<anyhow::Result<ParsedAccountResult>> {
+        use rayon::prelude::*;
+
+        data.par_iter()
+            .map(|account_data| self.get_parsed_account(account_data.clone(), show_hidden))
+            .collect()
+    }
+
+    /// Decodes each account and writes it to `w` as one JSON object per line
+    /// (`{"name": ..., "value": ...}`), ready for loading into a data
+    /// warehouse. Accounts that fail to decode are skipped. Returns the
+    /// number of lines written, and the index and error of each skipped
+    /// account.
+    pub fn decode_accounts_jsonl(
+        &self,
+        accounts: impl Iterator<Item = Vec<u8>>,
+        w: &mut impl Write,
+    ) -> std::io::Result<(usize, Vec<(usize, anyhow::Error)>)> {
+        let mut written = 0;
+        let mut skipped = Vec::new();
+        for (index, account_data) in accounts.enumerate() {
+            let parsed = match self.get_parsed_account(account_data, false) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    skipped.push((index, e));
+                    continue;
+                }
+            };
+            serde_json::to_writer(
+                &mut *w,
+                &serde_json::json!({ "name": parsed.name, "value": parsed.value }),
+            )?;
+            w.write_all(b"\n")?;
+            written += 1;
+        }
+        Ok((written, skipped))
+    }
+
+    /// Decodes an Anchor event. `event_data` must already be base64-decoded
+    /// and start with the event discriminator; for `emit_cpi!` events, the
+    /// 8-byte event instruction tag ahead of it must be stripped too.
+    pub fn get_parsed_event(
+        &self,
+        event_data: Vec<u8>,
+        show_hidden: bool,
+    ) -> anyhow::Result<ParsedEventResult> {
+        if event_data.len() < self.event_disc_len as usize {
+            return Err(anyhow::anyhow!("Event data is too short"));
+        }
+
+        let discriminant = self.get_event_discriminator(&event_data);
+
+        let event_schema = self
+            .events
+            .iter()
+            .find(|(disc, _)| disc.as_slice() == discriminant)
+            .map(|(_, schema)| schema)
+            .ok_or(anyhow::anyhow!("Event discriminant not found"))?
+            .clone();
+
+        let value: ValueNode = event_schema
+            .deserialize_bytes_with_options(
+                &mut &event_data[self.event_disc_len as usize..],
+                &self.decode_options(show_hidden),
+            )?
+            .ok_or(anyhow::anyhow!("Event type shouldn't be hidden"))?;
+
+        Ok(ParsedEventResult::new(event_schema, value.value))
+    }
+
+    /// Decodes the events emitted in a transaction's program logs: every
+    /// `Program data: <base64>` line is base64-decoded and decoded against
+    /// the events table. Other log lines are skipped.
+    pub fn decode_logs(&self, log_lines: &[String]) -> Vec<anyhow::Result<ParsedEventResult>> {
+        log_lines
+            .iter()
+            .filter_map(|line| line.strip_prefix("Program data: "))
+            .map(|data| {
+                let event_data = base64::engine::general_purpose::STANDARD
+                    .decode(data.trim())
+                    .map_err(|e| anyhow::anyhow!("Invalid base64 in program data: {}", e))?;
+                self.get_parsed_event(event_data, false)
+            })
+            .collect()
+    }
+
+    /// Decodes `data` without knowing up front whether it's an account, an
+    /// instruction or an event, trying each table in that order. Hidden
+    /// fields are always shown since this is meant for debugging.
+    pub fn decode_any(&self, data: &[u8], accounts: &[String]) -> DecodeResult {
+        if let Ok(account) = self.get_parsed_account(data.to_vec(), true) {
+            return DecodeResult::Account(account);
+        }
+        if let Ok(instruction) = self.get_parsed_instruction(data.to_vec(), accounts, true) {
+            return DecodeResult::Instruction(instruction);
+        }
+        if let Ok(event) = self.get_parsed_event(data.to_vec(), true) {
+            return DecodeResult::Event(event);
+        }
+        DecodeResult::Unknown
+    }
+
+    pub fn get_type(&self, name: &str) -> Option<&SchemaNode> {
+        self.types.iter().find(|typ| *typ.name == *name)
+    }
+
+    pub fn instruction_docs(&self, name: &str) -> Option<&[String]> {
+        self.instruction_params
+            .iter()
+            .find(|(_, decoder)| *decoder.instruction_args_parser.name == *name)
+            .map(|(_, decoder)| decoder.docs.as_slice())
+    }
+
+    pub fn account_docs(&self, name: &str) -> Option<&[String]> {
+        self.account_docs.get(name).map(|docs| docs.as_slice())
+    }
+
+    /// Discriminator bytes of the named instruction as they appear on chain,
+    /// e.g. for matching raw instruction data
+    pub fn instruction_discriminator_bytes(&self, name: &str) -> Option<Vec<u8>> {
+        self.instruction_params
+            .iter()
+            .find(|(_, decoder)| *decoder.instruction_args_parser.name == *name)
+            .map(|(disc, _)| disc.clone())
+    }
+
+    /// Instruction data for the named instruction: its discriminator followed
+    /// by `args` encoded with its schema. `args` is a `TypedValue::Struct`
+    /// holding exactly the instruction's args; mismatches fail with a
+    /// `BuildInstructionError`, or a type error from `serialize_value`.
+    pub fn build_instruction(&self, name: &str, args: TypedValue) -> anyhow::Result<Vec<u8>> {
+        let (disc, decoder) = self
+            .instruction_params
+            .iter()
+            .find(|(_, decoder)| *decoder.instruction_args_parser.name == *name)
+            .ok_or_else(|| BuildInstructionError::UnknownInstruction(name.to_string()))?;
+        let schema = &decoder.instruction_args_parser.typ;
+
+        if let (SchemaType::Struct(fields), TypedValue::Struct(values)) = (schema, &args) {
+            if let Some(missing) = fields
+                .iter()
+                .find(|field| values.iter().all(|value| value.name != field.name))
+            {
+                return Err(BuildInstructionError::MissingArg {
+                    instruction: name.to_string(),
+                    arg: missing.name.to_string(),
+                }
+                .into());
+            }
+            if let Some(extra) = values
+                .iter()
+                .find(|value| fields.iter().all(|field| value.name != field.name))
+            {
+                return Err(BuildInstructionError::UnexpectedArg {
+                    instruction: name.to_string(),
+                    arg: extra.name.to_string(),
+                }
+                .into());
+            }
+        }
+
+        let mut data = disc.clone();
+        data.extend(schema.serialize_value_with(&args, self.enum_disc_width)?);
+        Ok(data)
     }
 
-    pub fn get_account_discriminator(&self, account_data: &[u8]) -> u64 {
-        let mut padded_data = [0u8; 8];
-        let slice_len = std::cmp::min(self.account_disc_len as usize, 8);
-        padded_data[..slice_len].copy_from_slice(&account_data[..slice_len]);
-        u64::from_le_bytes(padded_data)
+    /// `InstructionDecoder::min_data_len` of the named instruction
+    pub fn min_instruction_data_len(&self, name: &str) -> Option<usize> {
+        self.instruction_params
+            .iter()
+            .find(|(_, decoder)| *decoder.instruction_args_parser.name == *name)
+            .map(|(_, decoder)| decoder.min_data_len())
     }
 
-    pub fn get_instruction_discriminator(&self, data: &[u8]) -> u64 {
-        let mut padded_data = [0u8; 8];
-        let slice_len = std::cmp::min(self.instruction_disc_len as usize, 8);
-        padded_data[..slice_len].copy_from_slice(&data[..slice_len]);
-        u64::from_le_bytes(padded_data)
+    /// Copies the IDL-wide discriminator length and enum width into each
+    /// instruction decoder, for `InstructionDecoder::min_data_len`. Parsing
+    /// and loading do this; call it again after changing
+    /// `instruction_disc_len` or `enum_disc_width` by hand.
+    pub fn size_instruction_decoders(&mut self) {
+        for (_, decoder) in &mut self.instruction_params {
+            decoder.disc_len = self.instruction_disc_len;
+            decoder.enum_disc_width = self.enum_disc_width;
+        }
+    }
+
+    /// Discriminator bytes of the named account as they appear on chain,
+    /// e.g. for a `memcmp` filter
+    pub fn account_discriminator_bytes(&self, name: &str) -> Option<Vec<u8>> {
+        self.accounts
+            .iter()
+            .find(|(_, schema)| *schema.name == *name)
+            .map(|(disc, _)| disc.clone())
+    }
+
+    /// Names of the instructions not marked deprecated, in discriminator order
+    pub fn active_instruction_names(&self) -> Vec<&str> {
+        self.instruction_params
+            .iter()
+            .filter(|(_, decoder)| !decoder.deprecated)
+            .map(|(_, decoder)| &*decoder.instruction_args_parser.name)
+            .collect()
+    }
+
+    /// Names of the accounts not marked deprecated, in discriminator order
+    pub fn active_account_names(&self) -> Vec<&str> {
+        self.accounts
+            .iter()
+            .filter(|(_, schema)| !self.deprecated_accounts.contains(&*schema.name))
+            .map(|(_, schema)| &*schema.name)
+            .collect()
+    }
+
+    /// Decodes a slab: a `header_type` header followed by a packed array of
+    /// `node_type` nodes, whose length is read from the header's
+    /// `node_count_field`. `data` must start at the header, so any
+    /// discriminator has to be stripped by the caller.
+    pub fn decode_slab(
+        &self,
+        data: &[u8],
+        header_type: &str,
+        node_type: &str,
+        node_count_field: &str,
+    ) -> anyhow::Result<ParsedSlabResult> {
+        let header_schema = self
+            .get_type(header_type)
+            .ok_or_else(|| anyhow::anyhow!("Slab header type {} not found", header_type))?;
+        let node_schema = self
+            .get_type(node_type)
+            .ok_or_else(|| anyhow::anyhow!("Slab node type {} not found", node_type))?;
+
+        let options = self.decode_options(true);
+        let mut bytes = data;
+        let header = header_schema
+            .deserialize_bytes_with_options(&mut bytes, &options)?
+            .ok_or(anyhow::anyhow!("Slab header shouldn't be hidden"))?;
+
+        let node_count = match &header.value {
+            TypedValue::Struct(fields) => fields
+                .iter()
+                .find(|field| &*field.name == node_count_field)
+                .ok_or_else(|| anyhow::anyhow!("Slab header has no field {}", node_count_field))?
+                .value
+                .as_u64()
+                .ok_or_else(|| {
+                    anyhow::anyhow!("Slab node count {} is not an integer", node_count_field)
+                })?,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Slab header {} is not a struct",
+                    header_type
+                ))
+            }
+        };
+
+        // the count comes from the data: check the nodes could fit before
+        // trusting it, which also rules out looping over zero-sized nodes
+        let min_size = node_schema.typ.min_size_with(self.enum_disc_width);
+        if min_size == 0 {
+            return Err(anyhow::anyhow!(
+                "Slab node type {} can take no bytes",
+                node_type
+            ));
+        }
+        if node_count > (bytes.len() / min_size) as u64 {
+            return Err(anyhow::anyhow!(
+                "Slab node count {} doesn't fit in the {} bytes after the header",
+                node_count,
+                bytes.len()
+            ));
+        }
+        let mut nodes = Vec::with_capacity(node_count as usize);
+        for _ in 0..node_count {
+            nodes.push(
+                node_schema
+                    .typ
+                    .deserialize_bytes_with_options(&mut bytes, &options)?,
+            );
+        }
+
+        Ok(ParsedSlabResult { header, nodes })
+    }
+
+    /// The account schema for `account_data` and the data after its
+    /// discriminator (and version byte, if any)
+    pub(crate) fn match_account<'a>(
+        &self,
+        account_data: &'a [u8],
+    ) -> Option<(&SchemaNode, &'a [u8])> {
+        let (_, account_data) = self.split_account_version(account_data).ok()?;
+        if account_data.len() < self.account_disc_len as usize {
+            return None;
+        }
+        let discriminant = self.get_account_discriminator(account_data);
+        let (_, schema) = self
+            .accounts
+            .iter()
+            .find(|(disc, _)| disc.as_slice() == discriminant)?;
+        Some((schema, self.account_body(account_data)))
+    }
+
+    /// The decoder of the instruction `data` starts with, if any
+    pub(crate) fn match_instruction(&self, data: &[u8]) -> Option<&InstructionDecoder> {
+        if data.len() < self.instruction_disc_len as usize {
+            return None;
+        }
+        let discriminant = self.get_instruction_discriminator(data);
+        self.instruction_params
+            .iter()
+            .find(|(disc, _)| disc.as_slice() == discriminant)
+            .map(|(_, decoder)| decoder)
+    }
+
+    /// Splits off the leading version byte when `account_version_prefix` is set
+    fn split_account_version<'a>(
+        &self,
+        account_data: &'a [u8],
+    ) -> anyhow::Result<(Option<u8>, &'a [u8])> {
+        if !self.account_version_prefix {
+            return Ok((None, account_data));
+        }
+        let (version, rest) = account_data
+            .split_first()
+            .ok_or(anyhow::anyhow!("Account data is too short"))?;
+        Ok((Some(*version), rest))
+    }
+
+    pub fn get_account_discriminator<'a>(&self, account_data: &'a [u8]) -> &'a [u8] {
+        let disc_len = self.account_disc_len as usize;
+        match self.account_disc_position {
+            DiscPosition::Prefix => &account_data[..disc_len],
+            DiscPosition::Suffix => &account_data[account_data.len() - disc_len..],
+        }
+    }
+
+    /// Mismatches between the declared space of an account and its schema:
+    /// a fixed-size schema must fill the space exactly, and no account may
+    /// decode past it. Either hints the IDL drifted from the program.
+    fn account_space_warnings(&self, schema: &SchemaNode, decoded_len: usize) -> Vec<String> {
+        let Some(&space) = self.account_spaces.get(&*schema.name) else {
+            return vec![];
+        };
+        let header_len = self.account_version_prefix as usize + self.account_disc_len as usize;
+        match schema.typ.fixed_size_with(self.enum_disc_width) {
+            Some(size) if (header_len + size) as u64 != space => vec![format!(
+                "{} declares {} bytes of space, but its schema takes {}",
+                schema.name,
+                space,
+                header_len + size
+            )],
+            None if (header_len + decoded_len) as u64 > space => vec![format!(
+                "{} decoded {} bytes, more than its declared space of {}",
+                schema.name,
+                header_len + decoded_len,
+                space
+            )],
+            _ => vec![],
+        }
+    }
+
+    /// Account data without its discriminator
+    fn account_body<'a>(&self, account_data: &'a [u8]) -> &'a [u8] {
+        let disc_len = self.account_disc_len as usize;
+        match self.account_disc_position {
+            DiscPosition::Prefix => &account_data[disc_len..],
+            DiscPosition::Suffix => &account_data[..account_data.len() - disc_len],
+        }
+    }
+
+    pub fn get_event_discriminator<'a>(&self, data: &'a [u8]) -> &'a [u8] {
+        &data[..self.event_disc_len as usize]
+    }
+
+    pub fn get_instruction_discriminator<'a>(&self, data: &'a [u8]) -> &'a [u8] {
+        &data[..self.instruction_disc_len as usize]
     }
 }
 
+/// Decodes instruction args from `args_data`, advancing it past them, and
+/// names `account_keys` after the decoder's declared accounts
+fn decode_instruction(
+    instruction_decoder: &InstructionDecoder,
+    args_data: &mut &[u8],
+    account_keys: &[String],
+    options: &DecodeOptions,
+) -> anyhow::Result<ParsedInstructionResult> {
+    let mut account_names = vec![];
+    let mut accounts_map = HashMap::new();
+    for (i, address) in account_keys.iter().enumerate() {
+        let name = instruction_decoder
+            .accounts
+            .get(i)
+            .map(|account| account.name.clone())
+            .unwrap_or(format!("Account {}", i + 1));
+        account_names.push(name.clone());
+        accounts_map.insert(name, address.clone());
+    }
+
+    let schema = instruction_decoder.instruction_args_parser.clone();
+
+    let args: ValueNode = schema
+        .deserialize_bytes_with_options(args_data, options)?
+        .ok_or(anyhow::anyhow!(
+            "is_hidden shouldn't be true in instructions"
+        ))?;
+
+    let mut result = ParsedInstructionResult::new(schema, account_names, accounts_map, args.value);
+    // declared accounts past the provided keys (e.g. omitted optional accounts)
+    result.missing_accounts = instruction_decoder
+        .accounts
+        .iter()
+        .skip(account_keys.len())
+        .map(|account| account.name.clone())
+        .collect();
+    // keys past the declared accounts have no metadata
+    let declared = instruction_decoder.accounts.iter().cloned().chain(
+        (instruction_decoder.accounts.len()..account_keys.len())
+            .map(|i| AccountMeta::named(format!("Account {}", i + 1))),
+    );
+    result.resolved_accounts = declared
+        .enumerate()
+        .map(|(i, meta)| ResolvedAccount {
+            name: meta.name,
+            address: account_keys.get(i).cloned(),
+            is_mut: meta.is_mut,
+            is_signer: meta.is_signer,
+            is_optional: meta.is_optional,
+        })
+        .collect();
+
+    Ok(result)
+}
+
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct ParsedInstructionResult {
     pub name: String,
@@ -127,9 +1105,26 @@ pub struct ParsedInstructionResult {
     pub accounts: Vec<String>,
     #[serde(serialize_with = "serialize_accounts_map")]
     pub accounts_map: HashMap<String, String>,
+    /// declared accounts that weren't provided
+    pub missing_accounts: Vec<String>,
+    /// every declared (or provided) account in order, absent ones included
+    pub resolved_accounts: Vec<ResolvedAccount>,
+    /// the discriminator bytes the instruction data matched on
+    pub discriminator: Vec<u8>,
     pub value: TypedValue,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ResolvedAccount {
+    pub name: String,
+    /// `None` (`null` in JSON) when the account wasn't provided, e.g. an
+    /// omitted optional account
+    pub address: Option<String>,
+    pub is_mut: bool,
+    pub is_signer: bool,
+    pub is_optional: bool,
+}
+
 fn serialize_accounts_map<S>(
     map: &HashMap<String, String>,
     serializer: S,
@@ -138,8 +1133,10 @@ where
     S: serde::Serializer,
 {
     use serde::ser::SerializeMap;
-    let mut map_serializer = serializer.serialize_map(Some(map.len()))?;
-    for (k, v) in map {
+    // sorted by name, so the output is stable across runs
+    let sorted: std::collections::BTreeMap<_, _> = map.iter().collect();
+    let mut map_serializer = serializer.serialize_map(Some(sorted.len()))?;
+    for (k, v) in sorted {
         map_serializer.serialize_entry(k, v)?;
     }
     map_serializer.end()
@@ -153,28 +1150,1400 @@ impl ParsedInstructionResult {
         value: TypedValue,
     ) -> Self {
         Self {
-            name: schema.name,
+            name: schema.name.to_string(),
             schema: schema.typ,
             accounts,
             accounts_map,
+            missing_accounts: vec![],
+            resolved_accounts: vec![],
+            discriminator: vec![],
             value,
         }
     }
 }
 
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ParsedEventResult {
+    pub name: String,
+    pub schema: SchemaType,
+    pub value: TypedValue,
+}
+
+impl ParsedEventResult {
+    pub fn new(schema: SchemaNode, value: TypedValue) -> Self {
+        Self {
+            name: schema.name.to_string(),
+            schema: schema.typ,
+            value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub enum DecodeResult {
+    Account(ParsedAccountResult),
+    Instruction(ParsedInstructionResult),
+    Event(ParsedEventResult),
+    Unknown,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ParsedSlabResult {
+    pub header: ValueNode,
+    pub nodes: Vec<TypedValue>,
+}
+
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct ParsedAccountResult {
     pub name: String,
     pub schema: SchemaType,
+    /// leading layout version, for IDLs with `account_version_prefix`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<u8>,
+    /// the discriminator bytes the account data matched on
+    pub discriminator: Vec<u8>,
     pub value: TypedValue,
+    /// signs the IDL may have drifted from the program, e.g. a schema that
+    /// disagrees with the account's declared space
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
 }
 
 impl ParsedAccountResult {
     pub fn new(schema: SchemaNode, value: TypedValue) -> Self {
         Self {
-            name: schema.name,
+            name: schema.name.to_string(),
             schema: schema.typ,
+            version: None,
+            discriminator: Vec::new(),
             value,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+/// Why `OnChainIdl::build_instruction` couldn't build instruction data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildInstructionError {
+    UnknownInstruction(String),
+    MissingArg { instruction: String, arg: String },
+    UnexpectedArg { instruction: String, arg: String },
+}
+
+impl std::fmt::Display for BuildInstructionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownInstruction(name) => write!(f, "Unknown instruction {}", name),
+            Self::MissingArg { instruction, arg } => {
+                write!(f, "Missing arg {} for instruction {}", arg, instruction)
+            }
+            Self::UnexpectedArg { instruction, arg } => {
+                write!(f, "Unexpected arg {} for instruction {}", arg, instruction)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildInstructionError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        on_chain_idl::{
+            AccountMeta, BuildInstructionError, DecodeResult, DiscByteOrder, DiscPosition,
+            LegacyInstructionDecoder, OnChainIdl, ParsedInstructionResult, ResolvedAccount,
+        },
+        parse_idl::{parse_idl, parse_idl_with_options, ParseOptions},
+        schema::{DecodeOptions, IntWidth, SchemaNode, SchemaType},
+        test_fixtures::{counter_account, counter_idl, increment_instruction, COUNTER_IDL_JSON},
+        value::{TypedValue, ValueNode},
+    };
+    use borsh::{BorshDeserialize, BorshSerialize};
+    use solana_program::{hash::hash, pubkey::Pubkey};
+    use std::collections::HashMap;
+
+    #[test]
+    fn decode_any_prefers_the_account_table() {
+        // the account and the instruction deliberately share a discriminator
+        let json = r#"{
+          "version": "0.1.0",
+          "name": "any_prog",
+          "instructions": [
+            {
+              "name": "bump",
+              "accounts": [],
+              "args": [{ "name": "by", "type": "u64" }],
+              "discriminator": [1, 2, 3, 4, 5, 6, 7, 8]
+            }
+          ],
+          "accounts": [
+            {
+              "name": "Counter",
+              "discriminator": [1, 2, 3, 4, 5, 6, 7, 8],
+              "type": {
+                "kind": "struct",
+                "fields": [{ "name": "count", "type": "u64" }]
+              }
+            }
+          ],
+          "events": [
+            {
+              "name": "Bumped",
+              "fields": [{ "name": "count", "type": "u64", "index": false }]
+            }
+          ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+
+        let mut data = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        data.extend_from_slice(&5u64.to_le_bytes());
+        match idl.decode_any(&data, &[]) {
+            DecodeResult::Account(account) => {
+                assert_eq!(account.name, "Counter");
+                assert_eq!(
+                    account.value,
+                    TypedValue::new_struct(vec![("count", TypedValue::U64(5))])
+                );
+            }
+            other => panic!("expected an account, got {:?}", other),
+        }
+
+        let mut event = hash(b"event:Bumped").to_bytes()[..8].to_vec();
+        event.extend_from_slice(&6u64.to_le_bytes());
+        assert!(matches!(
+            idl.decode_any(&event, &[]),
+            DecodeResult::Event(e) if e.name == "Bumped"
+        ));
+
+        assert!(matches!(
+            idl.decode_any(&[0; 16], &[]),
+            DecodeResult::Unknown
+        ));
+    }
+
+    #[test]
+    fn decode_logs_decodes_program_data_lines() {
+        use base64::Engine;
+
+        let json = r#"{
+          "version": "0.1.0",
+          "name": "counter",
+          "instructions": [],
+          "events": [
+            {
+              "name": "Bumped",
+              "fields": [{ "name": "count", "type": "u64", "index": false }]
+            }
+          ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+
+        let mut event = hash(b"event:Bumped").to_bytes()[..8].to_vec();
+        event.extend_from_slice(&6u64.to_le_bytes());
+        let logs = vec![
+            "Program 11111111111111111111111111111111 invoke [1]".to_string(),
+            "Program log: Instruction: Bump".to_string(),
+            format!(
+                "Program data: {}",
+                base64::engine::general_purpose::STANDARD.encode(&event)
+            ),
+            "Program 11111111111111111111111111111111 success".to_string(),
+        ];
+
+        let events = idl.decode_logs(&logs);
+        assert_eq!(events.len(), 1);
+        let event = events[0].as_ref().unwrap();
+        assert_eq!(event.name, "Bumped");
+        assert_eq!(
+            event.value,
+            TypedValue::new_struct(vec![("count", TypedValue::U64(6))])
+        );
+
+        let events = idl.decode_logs(&["Program data: not base64!".to_string()]);
+        assert!(events[0].is_err());
+    }
+
+    #[test]
+    fn old_accounts_decode_without_newly_added_trailing_fields() {
+        let json = r#"{
+          "version": "0.2.0",
+          "name": "counter",
+          "instructions": [],
+          "accounts": [
+            {
+              "name": "Counter",
+              "type": {
+                "kind": "struct",
+                "fields": [
+                  { "name": "count", "type": "u64" },
+                  { "name": "lastBumper", "type": "publicKey" }
+                ]
+              }
+            }
+          ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+
+        // written by the previous version, before `lastBumper` existed
+        let mut data = counter_account(3);
+        assert!(idl.get_parsed_account(data.clone(), false).is_err());
+
+        let options = DecodeOptions {
+            tolerate_missing_trailing_fields: true,
+            ..Default::default()
+        };
+        let parsed = idl
+            .get_parsed_account_with_options(data.clone(), &options)
+            .unwrap();
+        assert_eq!(
+            parsed.value,
+            TypedValue::new_struct(vec![("count", TypedValue::U64(3))])
+        );
+
+        // running out mid-field is still an error
+        data.extend_from_slice(&[1; 16]);
+        assert!(idl.get_parsed_account_with_options(data, &options).is_err());
+    }
+
+    #[test]
+    fn suffix_discriminators_are_read_from_the_end() {
+        let json = r#"{
+          "version": "0.1.0",
+          "name": "tail_prog",
+          "accountDiscriminatorPosition": "suffix",
+          "instructions": [],
+          "accounts": [
+            {
+              "name": "Counter",
+              "type": {
+                "kind": "struct",
+                "fields": [{ "name": "count", "type": "u64" }, { "name": "bump", "type": "u8" }]
+              }
+            }
+          ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        assert_eq!(idl.account_disc_position, DiscPosition::Suffix);
+
+        let mut data = 9u64.to_le_bytes().to_vec();
+        data.push(254);
+        data.extend_from_slice(&hash(b"account:Counter").to_bytes()[..8]);
+        let parsed = idl.get_parsed_account(data, false).unwrap();
+        assert_eq!(parsed.name, "Counter");
+        assert_eq!(
+            parsed.value,
+            TypedValue::new_struct(vec![
+                ("count", TypedValue::U64(9)),
+                ("bump", TypedValue::U8(254)),
+            ])
+        );
+
+        let reparsed = parse_idl(idl.to_anchor_idl_json().to_string()).expect("reparse ok");
+        assert_eq!(reparsed.account_disc_position, DiscPosition::Suffix);
+    }
+
+    #[test]
+    fn declared_accounts_without_keys_are_missing() {
+        let json = r#"{
+          "version": "0.1.0",
+          "name": "accounts_prog",
+          "instructions": [
+            {
+              "name": "close",
+              "accounts": [
+                { "name": "owner", "isMut": false, "isSigner": true },
+                { "name": "rentReceiver", "isMut": true, "isSigner": false, "isOptional": true }
+              ],
+              "args": []
+            }
+          ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+
+        let data = hash(b"global:close").to_bytes()[..8].to_vec();
+        let owner = "CzwQ3dFHekGbHcGYNwUHAjShX9KmhFdWsfJBmYFMHoh7".to_string();
+        let parsed = idl
+            .get_parsed_instruction(data, std::slice::from_ref(&owner), false)
+            .unwrap();
+
+        assert_eq!(parsed.accounts, vec!["owner".to_string()]);
+        assert_eq!(parsed.accounts_map.get("owner"), Some(&owner));
+        assert!(!parsed.accounts_map.contains_key("rentReceiver"));
+        assert_eq!(parsed.missing_accounts, vec!["rentReceiver".to_string()]);
+        assert_eq!(
+            parsed.resolved_accounts,
+            vec![
+                ResolvedAccount {
+                    name: "owner".to_string(),
+                    address: Some(owner.clone()),
+                    is_mut: false,
+                    is_signer: true,
+                    is_optional: false,
+                },
+                ResolvedAccount {
+                    name: "rentReceiver".to_string(),
+                    address: None,
+                    is_mut: true,
+                    is_signer: false,
+                    is_optional: true,
+                },
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(&parsed).unwrap()["resolved_accounts"][1],
+            serde_json::json!({
+                "name": "rentReceiver",
+                "address": null,
+                "is_mut": true,
+                "is_signer": false,
+                "is_optional": true,
+            })
+        );
+    }
+
+    #[test]
+    fn min_data_len_covers_discriminator_and_args() {
+        let json = r#"{
+          "version": "0.1.0",
+          "name": "swap_prog",
+          "instructions": [
+            {
+              "name": "swap",
+              "accounts": [],
+              "args": [
+                { "name": "amountIn", "type": "u64" },
+                { "name": "minOut", "type": "u64" },
+                { "name": "deadline", "type": "u64" }
+              ]
+            },
+            {
+              "name": "memo",
+              "accounts": [],
+              "args": [
+                { "name": "text", "type": "string" },
+                { "name": "tip", "type": { "option": "u64" } }
+              ]
+            }
+          ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        let decoder = |name: &str| {
+            &idl.instruction_params
+                .iter()
+                .find(|(_, decoder)| &*decoder.instruction_args_parser.name == name)
+                .unwrap()
+                .1
+        };
+        let disc_len = idl.instruction_disc_len as usize;
+
+        assert_eq!(decoder("swap").min_data_len(), disc_len + 24);
+        // string length prefix plus option tag
+        assert_eq!(decoder("memo").min_data_len(), disc_len + 5);
+        assert_eq!(idl.min_instruction_data_len("swap"), Some(disc_len + 24));
+        assert_eq!(idl.min_instruction_data_len("unknown"), None);
+
+        // the discriminator length isn't stored per instruction, so a
+        // reloaded IDL fills it back in
+        let reloaded = OnChainIdl::try_from_slice(&idl.try_to_vec().unwrap()).unwrap();
+        assert_eq!(
+            reloaded.min_instruction_data_len("memo"),
+            Some(disc_len + 5)
+        );
+    }
+
+    #[test]
+    fn one_byte_opcodes_match_on_the_first_byte_only() {
+        let json = r#"{
+          "version": "0.1.0",
+          "name": "opcode_prog",
+          "instructions": [
+            {
+              "name": "deposit",
+              "discriminator": [1],
+              "accounts": [],
+              "args": [{ "name": "amount", "type": "u64" }]
+            },
+            {
+              "name": "withdraw",
+              "discriminator": [2],
+              "accounts": [],
+              "args": [{ "name": "amount", "type": "u64" }, { "name": "flag", "type": "u8" }]
+            }
+          ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        assert_eq!(idl.instruction_disc_len, 1);
+
+        // the first arg byte equals the other opcode
+        let mut data = vec![1u8];
+        data.extend_from_slice(&2u64.to_le_bytes());
+        let parsed = idl.get_parsed_instruction(data, &[], false).unwrap();
+        assert_eq!(parsed.name, "deposit");
+        assert_eq!(
+            parsed.value,
+            TypedValue::new_struct(vec![("amount", TypedValue::U64(2))])
+        );
+
+        let mut data = vec![2u8];
+        data.extend_from_slice(&1u64.to_le_bytes());
+        data.push(9);
+        let parsed = idl.get_parsed_instruction(data, &[], false).unwrap();
+        assert_eq!(parsed.name, "withdraw");
+        assert_eq!(
+            parsed.value,
+            TypedValue::new_struct(vec![
+                ("amount", TypedValue::U64(1)),
+                ("flag", TypedValue::U8(9)),
+            ])
+        );
+
+        assert!(idl.get_parsed_instruction(vec![3, 0], &[], false).is_err());
+
+        let too_wide = json.replace(
+            r#""discriminator": [2]"#,
+            r#""discriminant": { "type": "u8", "value": 258 }"#,
+        );
+        assert!(parse_idl(too_wide).is_err());
+    }
+
+    #[test]
+    fn brute_force_keeps_only_schemas_that_consume_the_buffer() {
+        let json = r#"{
+          "version": "0.1.0",
+          "name": "mystery",
+          "instructions": [
+            { "name": "setFee", "accounts": [], "args": [{ "name": "bps", "type": "u16" }] },
+            {
+              "name": "transfer",
+              "accounts": [{ "name": "to", "isMut": true, "isSigner": false }],
+              "args": [
+                { "name": "amount", "type": "u64" },
+                { "name": "recipient", "type": "publicKey" }
+              ]
+            },
+            { "name": "memo", "accounts": [], "args": [{ "name": "text", "type": "string" }] }
+          ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+
+        // unknown discriminator, followed by a u64 and a pubkey
+        let mut data = vec![0xff; 8];
+        data.extend_from_slice(&1_000u64.to_le_bytes());
+        data.extend_from_slice(&[7u8; 32]);
+
+        let to = "CzwQ3dFHekGbHcGYNwUHAjShX9KmhFdWsfJBmYFMHoh7".to_string();
+        assert!(idl
+            .get_parsed_instruction(data.clone(), std::slice::from_ref(&to), false)
+            .is_err());
+
+        let candidates = idl.brute_force_instruction(&data, std::slice::from_ref(&to));
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].name, "transfer");
+        assert_eq!(candidates[0].accounts_map.get("to"), Some(&to));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_get_parsed_accounts_preserves_order() {
+        fn assert_sync<T: Sync>() {}
+        assert_sync::<super::OnChainIdl>();
+
+        let idl = counter_idl();
+
+        let accounts: Vec<Vec<u8>> = (0..1000u64).map(counter_account).collect();
+
+        let results = idl.par_get_parsed_accounts(&accounts, false);
+        assert_eq!(results.len(), 1000);
+        for (count, result) in results.into_iter().enumerate() {
+            assert_eq!(
+                result.unwrap().value,
+                TypedValue::new_struct(vec![("count", TypedValue::U64(count as u64))])
+            );
+        }
+    }
+
+    #[test]
+    fn decode_accounts_jsonl_writes_one_line_per_account() {
+        let idl = counter_idl();
+
+        let accounts = vec![counter_account(1), vec![0xde, 0xad], counter_account(2)];
+
+        let mut out = Vec::new();
+        let (written, skipped) = idl
+            .decode_accounts_jsonl(accounts.into_iter(), &mut out)
+            .unwrap();
+        assert_eq!(written, 2);
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].0, 1);
+        assert_eq!(skipped[0].1.to_string(), "Account data is too short");
+
+        let lines: Vec<serde_json::Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("valid JSON line"))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                serde_json::json!({ "name": "Counter", "value": { "count": "1" } }),
+                serde_json::json!({ "name": "Counter", "value": { "count": "2" } }),
+            ]
+        );
+    }
+
+    #[test]
+    fn partial_decode_returns_fields_before_the_error() {
+        let json = r#"{
+          "version": "0.1.0",
+          "name": "pool",
+          "instructions": [],
+          "accounts": [
+            {
+              "name": "Pool",
+              "type": {
+                "kind": "struct",
+                "fields": [
+                  { "name": "fee", "type": "u16" },
+                  { "name": "config", "type": { "defined": "Config" } },
+                  { "name": "reserves", "type": "u64" }
+                ]
+              }
+            }
+          ],
+          "types": [
+            {
+              "name": "Config",
+              "type": {
+                "kind": "struct",
+                "fields": [
+                  { "name": "paused", "type": "bool" },
+                  { "name": "label", "type": "string" }
+                ]
+              }
+            }
+          ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+
+        let mut data = hash(b"account:Pool").to_bytes()[..8].to_vec();
+        data.extend_from_slice(&30u16.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(b"ok");
+        // only 3 of the 8 bytes of `reserves`
+        data.extend_from_slice(&[1, 2, 3]);
+
+        assert!(idl.get_parsed_account(data.clone(), false).is_err());
+        let (partial, err) = idl.get_parsed_account_partial(data, false);
+        assert!(err.is_some());
+        assert_eq!(
+            partial.unwrap(),
+            ValueNode::new_struct(
+                "Pool",
+                vec![
+                    ("fee", TypedValue::U16(30)),
+                    (
+                        "config",
+                        TypedValue::new_struct(vec![
+                            ("paused", TypedValue::Bool(true)),
+                            ("label", TypedValue::String("ok".to_string())),
+                        ])
+                    ),
+                ]
+            )
+        );
+
+        // a struct failing mid-way inside a nested struct keeps its own prefix
+        let mut data = hash(b"account:Pool").to_bytes()[..8].to_vec();
+        data.extend_from_slice(&30u16.to_le_bytes());
+        data.push(0);
+        data.extend_from_slice(&10u32.to_le_bytes());
+        let (partial, err) = idl.get_parsed_account_partial(data, false);
+        assert!(err.is_some());
+        assert_eq!(
+            partial.unwrap().value,
+            TypedValue::new_struct(vec![
+                ("fee", TypedValue::U16(30)),
+                (
+                    "config",
+                    TypedValue::new_struct(vec![("paused", TypedValue::Bool(false))])
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn versioned_accounts_surface_the_leading_version_byte() {
+        let json = COUNTER_IDL_JSON.replacen("{", r#"{ "accountVersionPrefix": true,"#, 1);
+        let idl = parse_idl(json).expect("parse_idl ok");
+        assert!(idl.account_version_prefix);
+
+        let mut data = vec![3u8];
+        data.extend(counter_account(9));
+
+        let parsed = idl.get_parsed_account(data, false).unwrap();
+        assert_eq!(parsed.version, Some(3));
+        assert_eq!(
+            parsed.value,
+            TypedValue::new_struct(vec![("count", TypedValue::U64(9))])
+        );
+        assert_eq!(serde_json::to_value(&parsed).unwrap()["version"], 3);
+
+        assert!(idl.get_parsed_account(vec![], false).is_err());
+    }
+
+    #[test]
+    fn accounts_map_serializes_in_stable_key_order() {
+        let names = ["systemProgram", "authority", "vault", "mint", "payer"];
+        // every HashMap gets its own random iteration order
+        let serialize = || {
+            let accounts_map: HashMap<String, String> = names
+                .iter()
+                .enumerate()
+                .map(|(i, name)| (name.to_string(), format!("key{}", i)))
+                .collect();
+            let result = ParsedInstructionResult::new(
+                SchemaNode::new("init", SchemaType::Empty),
+                names.iter().map(|name| name.to_string()).collect(),
+                accounts_map,
+                TypedValue::Empty,
+            );
+            serde_json::to_string(&result).unwrap()
+        };
+
+        let first = serialize();
+        for _ in 0..10 {
+            assert_eq!(serialize(), first);
+        }
+        assert!(
+            first.contains(
+                r#""accounts_map":{"authority":"key1","mint":"key3","payer":"key4","systemProgram":"key0","vault":"key2"}"#
+            ),
+            "{}",
+            first
+        );
+    }
+
+    #[test]
+    fn decode_slab_reads_node_count_from_header() {
+        let json = r#"{
+          "version": "0.1.0",
+          "name": "slab_prog",
+          "instructions": [],
+          "types": [
+            {
+              "name": "SlabHeader",
+              "type": {
+                "kind": "struct",
+                "fields": [
+                  { "name": "freeListHead", "type": "u32" },
+                  { "name": "nodeCount", "type": "u32" }
+                ]
+              }
+            },
+            {
+              "name": "SlabNode",
+              "type": {
+                "kind": "struct",
+                "fields": [
+                  { "name": "key", "type": "u64" },
+                  { "name": "next", "type": "u32" }
+                ]
+              }
+            }
+          ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&u32::MAX.to_le_bytes());
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&10u64.to_le_bytes());
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&20u64.to_le_bytes());
+        data.extend_from_slice(&u32::MAX.to_le_bytes());
+
+        let slab = idl
+            .decode_slab(&data, "SlabHeader", "SlabNode", "nodeCount")
+            .expect("decode_slab ok");
+
+        assert_eq!(&*slab.header.name, "SlabHeader");
+        assert_eq!(
+            slab.nodes,
+            vec![
+                TypedValue::new_struct(vec![
+                    ("key", TypedValue::U64(10)),
+                    ("next", TypedValue::U32(1)),
+                ]),
+                TypedValue::new_struct(vec![
+                    ("key", TypedValue::U64(20)),
+                    ("next", TypedValue::U32(u32::MAX)),
+                ]),
+            ]
+        );
+
+        // a count the remaining bytes can't hold is rejected up front
+        data[4..8].copy_from_slice(&3u32.to_le_bytes());
+        assert!(idl
+            .decode_slab(&data, "SlabHeader", "SlabNode", "nodeCount")
+            .is_err());
+    }
+
+    #[test]
+    fn decode_slab_rejects_zero_sized_nodes() {
+        let json = r#"{
+          "version": "0.1.0",
+          "name": "slab_prog",
+          "instructions": [],
+          "types": [
+            {
+              "name": "SlabHeader",
+              "type": { "kind": "struct", "fields": [{ "name": "nodeCount", "type": "u64" }] }
+            },
+            { "name": "Marker", "type": { "kind": "struct", "fields": [] } }
+          ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+
+        let data = u64::MAX.to_le_bytes();
+        let err = idl
+            .decode_slab(&data, "SlabHeader", "Marker", "nodeCount")
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Slab node type Marker can take no bytes");
+    }
+
+    #[test]
+    fn account_decodes_against_an_override_schema() {
+        let idl = counter_idl();
+
+        let mut data = idl.account_discriminator_bytes("Counter").unwrap();
+        data.extend_from_slice(&7u32.to_le_bytes());
+        data.extend_from_slice(&9u32.to_le_bytes());
+
+        let split = SchemaNode::new_struct(
+            "SplitCounter",
+            vec![("low", SchemaType::U32), ("high", SchemaType::U32)],
+        );
+        let parsed = idl
+            .get_parsed_account_with_schema(&data, &split, false)
+            .unwrap();
+        assert_eq!(parsed.name, "SplitCounter");
+        assert_eq!(
+            parsed.value,
+            TypedValue::new_struct(vec![
+                ("low", TypedValue::U32(7)),
+                ("high", TypedValue::U32(9)),
+            ])
+        );
+
+        // the table schema is untouched
+        let parsed = idl.get_parsed_account(data, false).unwrap();
+        assert_eq!(parsed.name, "Counter");
+    }
+
+    #[test]
+    fn discriminators_convert_back_to_bytes() {
+        let idl = counter_idl();
+
+        assert_eq!(
+            idl.instruction_discriminator_bytes("increment"),
+            Some(vec![11, 18, 104, 9, 104, 174, 59, 33])
+        );
+        assert_eq!(
+            idl.account_discriminator_bytes("Counter"),
+            Some(hash(b"account:Counter").to_bytes()[..8].to_vec())
+        );
+        assert_eq!(idl.instruction_discriminator_bytes("missing"), None);
+    }
+
+    #[test]
+    fn builds_instruction_data_from_named_args() {
+        let json = r#"{
+          "version": "0.1.0",
+          "name": "swap_prog",
+          "instructions": [
+            {
+              "name": "swap",
+              "accounts": [],
+              "args": [
+                { "name": "amountIn", "type": "u64" },
+                { "name": "slippageBps", "type": "u16" }
+              ]
+            }
+          ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        let args = |fields: Vec<(&str, TypedValue)>| {
+            TypedValue::Struct(
+                fields
+                    .into_iter()
+                    .map(|(name, value)| ValueNode::new(name, value))
+                    .collect(),
+            )
+        };
+
+        let data = idl
+            .build_instruction(
+                "swap",
+                args(vec![
+                    ("amountIn", TypedValue::U64(1_000)),
+                    ("slippageBps", TypedValue::U16(50)),
+                ]),
+            )
+            .unwrap();
+        let mut expected = hash(b"global:swap").to_bytes()[..8].to_vec();
+        expected.extend_from_slice(&1_000u64.to_le_bytes());
+        expected.extend_from_slice(&50u16.to_le_bytes());
+        assert_eq!(data, expected);
+        let parsed = idl.get_parsed_instruction(data, &[], false).unwrap();
+        assert_eq!(parsed.name, "swap");
+
+        let error = |name: &str, fields| {
+            idl.build_instruction(name, args(fields))
+                .unwrap_err()
+                .downcast::<BuildInstructionError>()
+                .unwrap()
+        };
+        assert_eq!(
+            error("swop", vec![]),
+            BuildInstructionError::UnknownInstruction("swop".to_string())
+        );
+        assert_eq!(
+            error("swap", vec![("amountIn", TypedValue::U64(1))]),
+            BuildInstructionError::MissingArg {
+                instruction: "swap".to_string(),
+                arg: "slippageBps".to_string()
+            }
+        );
+        assert_eq!(
+            error(
+                "swap",
+                vec![
+                    ("amountIn", TypedValue::U64(1)),
+                    ("slippageBps", TypedValue::U16(1)),
+                    ("memo", TypedValue::String("hi".to_string())),
+                ]
+            ),
+            BuildInstructionError::UnexpectedArg {
+                instruction: "swap".to_string(),
+                arg: "memo".to_string()
+            }
+        );
+        // wrongly typed args are rejected by the encoder
+        assert!(idl
+            .build_instruction(
+                "swap",
+                args(vec![
+                    ("amountIn", TypedValue::U32(1)),
+                    ("slippageBps", TypedValue::U16(1)),
+                ])
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn anchor_030_events_take_their_layout_from_types() {
+        let json = r#"{
+          "address": "11111111111111111111111111111111",
+          "metadata": { "name": "dex", "version": "0.1.0", "spec": "0.1.0" },
+          "instructions": [],
+          "events": [
+            { "name": "TradeEvent", "discriminator": [189, 219, 127, 211, 78, 230, 97, 238] }
+          ],
+          "types": [
+            {
+              "name": "TradeEvent",
+              "type": {
+                "kind": "struct",
+                "fields": [
+                  { "name": "mint", "type": "pubkey" },
+                  { "name": "solAmount", "type": "u64" },
+                  { "name": "isBuy", "type": "bool" },
+                  { "name": "timestamp", "type": "i64" }
+                ]
+              }
+            }
+          ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        assert_eq!(idl.event_disc_len, 8);
+
+        let mint = solana_program::pubkey::Pubkey::new_unique();
+        let mut data = vec![189, 219, 127, 211, 78, 230, 97, 238];
+        data.extend_from_slice(&mint.to_bytes());
+        data.extend_from_slice(&1_500_000u64.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(&1_700_000_000i64.to_le_bytes());
+
+        let event = idl.get_parsed_event(data, false).unwrap();
+        assert_eq!(event.name, "TradeEvent");
+        assert_eq!(
+            event.value,
+            TypedValue::new_struct(vec![
+                ("mint", TypedValue::Pubkey(mint.to_string())),
+                ("solAmount", TypedValue::U64(1_500_000)),
+                ("isBuy", TypedValue::Bool(true)),
+                ("timestamp", TypedValue::I64(1_700_000_000)),
+            ])
+        );
+    }
+
+    /// Checks an `OnChainIdl` read from a blob in `tests/layouts`, which an
+    /// earlier layout serialized from `tests/layouts/counter.json`
+    fn check_counter_layout(idl: &OnChainIdl) {
+        let authority = Pubkey::new_from_array([5; 32]);
+        let mut data = hash(b"account:Counter").to_bytes()[..8].to_vec();
+        data.extend_from_slice(authority.as_ref());
+        data.extend_from_slice(&7u64.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(&100u64.to_le_bytes());
+        data.extend_from_slice(&(-2i64).to_le_bytes());
+        let parsed = idl.get_parsed_account(data, false).unwrap();
+        assert_eq!(parsed.name, "Counter");
+        assert_eq!(
+            serde_json::to_value(&parsed.value).unwrap(),
+            serde_json::json!({
+                "authority": authority.to_string(),
+                "count": "7",
+                "last": { "slot": "100", "delta": "-2" },
+            })
+        );
+
+        let data = [
+            hash(b"global:increment").to_bytes()[..8].to_vec(),
+            3u64.to_le_bytes().to_vec(),
+        ];
+        let keys = ["A".to_string(), "B".to_string()];
+        let parsed = idl
+            .get_parsed_instruction(data.concat(), &keys, false)
+            .unwrap();
+        assert_eq!(parsed.name, "increment");
+        assert_eq!(parsed.accounts, vec!["counter", "authority"]);
+
+        // re-serializing writes the current layout, which reads back as is
+        let serialized = idl.try_to_vec().unwrap();
+        assert_eq!(serialized[..4], u32::MAX.to_le_bytes());
+        assert_eq!(&OnChainIdl::try_from_slice(&serialized).unwrap(), idl);
+    }
+
+    #[test]
+    fn baseline_layout_migrates_to_byte_discriminators() {
+        let idl =
+            OnChainIdl::try_from_slice(include_bytes!("../tests/layouts/baseline.bin")).unwrap();
+        assert_eq!(idl.program_name, "counter");
+        assert!(idl.types.is_empty());
+        assert!(idl.events.is_empty());
+        check_counter_layout(&idl);
+    }
+
+    #[test]
+    fn types_and_events_layouts_migrate() {
+        let types = |idl: &OnChainIdl| -> Vec<String> {
+            idl.types.iter().map(|typ| typ.name.to_string()).collect()
+        };
+        let idl = OnChainIdl::try_from_slice(include_bytes!("../tests/layouts/types.bin")).unwrap();
+        assert_eq!(types(&idl), ["Counter", "Entry"]);
+        assert!(idl.events.is_empty());
+        check_counter_layout(&idl);
+
+        let idl =
+            OnChainIdl::try_from_slice(include_bytes!("../tests/layouts/events.bin")).unwrap();
+        assert_eq!(types(&idl), ["Counter", "Entry"]);
+        let mut data = hash(b"event:Incremented").to_bytes()[..8].to_vec();
+        data.extend_from_slice(&8u64.to_le_bytes());
+        assert_eq!(
+            idl.get_parsed_event(data, false).unwrap().name,
+            "Incremented"
+        );
+        check_counter_layout(&idl);
+    }
+
+    #[test]
+    fn serialization_order_layout_migrates() {
+        let idl =
+            OnChainIdl::try_from_slice(include_bytes!("../tests/layouts/serialization_order.bin"))
+                .unwrap();
+        assert_eq!(idl.events.len(), 1);
+        check_counter_layout(&idl);
+    }
+
+    #[test]
+    fn version_prefix_layout_migrates() {
+        let idl = OnChainIdl::try_from_slice(include_bytes!("../tests/layouts/version_prefix.bin"))
+            .unwrap();
+        assert!(!idl.account_version_prefix);
+        check_counter_layout(&idl);
+    }
+
+    #[test]
+    fn docs_layout_migrates() {
+        let idl = OnChainIdl::try_from_slice(include_bytes!("../tests/layouts/docs.bin")).unwrap();
+        assert_eq!(idl.docs, ["Counts things"]);
+        assert_eq!(
+            idl.account_docs("Counter"),
+            Some(&["A counter".to_string()][..])
+        );
+        assert_eq!(idl.instruction_params[0].1.docs, ["Adds to the count"]);
+        check_counter_layout(&idl);
+    }
+
+    #[test]
+    fn disc_position_layout_migrates() {
+        let idl = OnChainIdl::try_from_slice(include_bytes!("../tests/layouts/disc_position.bin"))
+            .unwrap();
+        assert_eq!(idl.account_disc_position, DiscPosition::Prefix);
+        assert_eq!(idl.docs, ["Counts things"]);
+        check_counter_layout(&idl);
+    }
+
+    #[test]
+    fn deprecated_layout_migrates() {
+        let idl =
+            OnChainIdl::try_from_slice(include_bytes!("../tests/layouts/deprecated.bin")).unwrap();
+        assert!(idl.deprecated_accounts.is_empty());
+        assert!(!idl.instruction_params[0].1.deprecated);
+        assert_eq!(idl.instruction_params[0].1.docs, ["Adds to the count"]);
+        check_counter_layout(&idl);
+    }
+
+    #[test]
+    fn byte_order_layout_migrates() {
+        let idl =
+            OnChainIdl::try_from_slice(include_bytes!("../tests/layouts/byte_order.bin")).unwrap();
+        assert_eq!(idl.disc_byte_order, DiscByteOrder::LittleEndian);
+        assert_eq!(idl.docs, ["Counts things"]);
+        check_counter_layout(&idl);
+    }
+
+    #[test]
+    fn unversioned_program_names_must_be_in_the_input() {
+        // claims a 2 GiB program name, with nothing behind it
+        assert!(OnChainIdl::try_from_slice(&[0xff, 0xff, 0xff, 0x7f]).is_err());
+        assert!(OnChainIdl::try_from_slice(b"\x03\0\0\0abc").is_err());
+    }
+
+    #[test]
+    fn declared_space_mismatches_warn() {
+        let json = r#"{
+          "version": "0.1.0",
+          "name": "counter",
+          "instructions": [],
+          "accounts": [
+            {
+              "name": "Counter",
+              "space": 24,
+              "type": { "kind": "struct", "fields": [{ "name": "count", "type": "u64" }] }
+            },
+            {
+              "name": "Profile",
+              "space": 20,
+              "type": { "kind": "struct", "fields": [{ "name": "handle", "type": "string" }] }
+            }
+          ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        assert_eq!(idl.account_spaces.get("Counter"), Some(&24));
+
+        // 8 + 8 bytes, but the account was allocated 24: a field is missing
+        let mut counter = counter_account(7);
+        counter.extend_from_slice(&[0; 8]);
+        let parsed = idl.get_parsed_account(counter, false).unwrap();
+        assert_eq!(
+            parsed.warnings,
+            vec!["Counter declares 24 bytes of space, but its schema takes 16".to_string()]
+        );
+        assert_eq!(
+            serde_json::to_value(&parsed).unwrap()["warnings"][0],
+            "Counter declares 24 bytes of space, but its schema takes 16"
+        );
+
+        let profile = |handle: &str| {
+            let mut data = hash(b"account:Profile").to_bytes()[..8].to_vec();
+            data.extend_from_slice(&(handle.len() as u32).to_le_bytes());
+            data.extend_from_slice(handle.as_bytes());
+            data
+        };
+        let parsed = idl.get_parsed_account(profile("gm"), false).unwrap();
+        assert!(parsed.warnings.is_empty());
+        assert!(serde_json::to_value(&parsed)
+            .unwrap()
+            .get("warnings")
+            .is_none());
+        let parsed = idl
+            .get_parsed_account(profile("a long handle"), false)
+            .unwrap();
+        assert_eq!(
+            parsed.warnings,
+            vec!["Profile decoded 25 bytes, more than its declared space of 20".to_string()]
+        );
+
+        // layouts from before account spaces were stored read back without them
+        let mut serialized = idl.try_to_vec().unwrap();
+        serialized[4] = 2;
+        let spaces_len = idl.account_spaces.try_to_vec().unwrap().len();
+        let width_len = idl.enum_disc_width.try_to_vec().unwrap().len();
+        serialized.truncate(serialized.len() - spaces_len - width_len);
+        let old = OnChainIdl::try_from_slice(&serialized).unwrap();
+        assert!(old.account_spaces.is_empty());
+        assert_eq!(old.accounts, idl.accounts);
+    }
+
+    #[test]
+    fn strict_decoding_rejects_trailing_bytes() {
+        let idl = counter_idl();
+        let strict = DecodeOptions {
+            reject_trailing_bytes: true,
+            ..Default::default()
+        };
+
+        let mut counter = counter_account(7);
+        let mut increment = increment_instruction(2);
+
+        // the schema covers every byte
+        idl.get_parsed_account_with_options(counter.clone(), &strict)
+            .unwrap();
+        idl.get_parsed_instruction_with_options(increment.clone(), &[], &strict)
+            .unwrap();
+
+        // a field the schema doesn't know about
+        counter.extend_from_slice(&[1, 2, 3]);
+        increment.push(1);
+        let err = idl
+            .get_parsed_account_with_options(counter.clone(), &strict)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "3 bytes left over after decoding Counter");
+        let err = idl
+            .get_parsed_instruction_with_options(increment.clone(), &[], &strict)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "1 bytes left over after decoding increment"
+        );
+
+        // lenient by default
+        idl.get_parsed_account(counter, false).unwrap();
+        idl.get_parsed_instruction(increment, &[], false).unwrap();
+    }
+
+    #[test]
+    fn results_carry_the_matched_discriminator() {
+        let idl = counter_idl();
+
+        let increment_disc = hash(b"global:increment").to_bytes()[..8].to_vec();
+        let parsed = idl
+            .get_parsed_instruction(increment_instruction(2), &[], false)
+            .unwrap();
+        assert_eq!(parsed.discriminator, increment_disc);
+        assert_eq!(
+            serde_json::to_value(&parsed).unwrap()["discriminator"],
+            serde_json::json!(increment_disc)
+        );
+
+        let counter_disc = hash(b"account:Counter").to_bytes()[..8].to_vec();
+        let parsed = idl.get_parsed_account(counter_account(7), false).unwrap();
+        assert_eq!(parsed.discriminator, counter_disc);
+        assert_eq!(
+            serde_json::to_value(&parsed).unwrap()["discriminator"],
+            serde_json::json!(counter_disc)
+        );
+    }
+
+    #[test]
+    fn instruction_account_flags_round_trip() {
+        let json = r#"{
+          "version": "0.1.0",
+          "name": "vault",
+          "instructions": [
+            {
+              "name": "withdraw",
+              "accounts": [
+                { "name": "authority", "isMut": false, "isSigner": true },
+                { "name": "vault", "writable": true },
+                { "name": "referrer", "isMut": true, "isSigner": false, "isOptional": true }
+              ],
+              "args": []
+            }
+          ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        let flags = vec![
+            AccountMeta {
+                name: "authority".to_string(),
+                is_mut: false,
+                is_signer: true,
+                is_optional: false,
+            },
+            AccountMeta {
+                name: "vault".to_string(),
+                is_mut: true,
+                is_signer: false,
+                is_optional: false,
+            },
+            AccountMeta {
+                name: "referrer".to_string(),
+                is_mut: true,
+                is_signer: false,
+                is_optional: true,
+            },
+        ];
+        assert_eq!(idl.instruction_params[0].1.accounts, flags);
+
+        let reparsed = OnChainIdl::try_from_slice(&idl.try_to_vec().unwrap()).unwrap();
+        assert_eq!(reparsed.instruction_params[0].1.accounts, flags);
+
+        let exported = parse_idl(idl.to_anchor_idl_json().to_string()).unwrap();
+        assert_eq!(exported.instruction_params[0].1.accounts, flags);
+
+        let data = hash(b"global:withdraw").to_bytes()[..8].to_vec();
+        let keys = vec!["A".to_string(), "B".to_string()];
+        let parsed = idl.get_parsed_instruction(data, &keys, false).unwrap();
+        let resolved: Vec<_> = parsed
+            .resolved_accounts
+            .iter()
+            .map(|a| (a.name.as_str(), a.is_mut, a.is_signer, a.is_optional))
+            .collect();
+        assert_eq!(
+            resolved,
+            vec![
+                ("authority", false, true, false),
+                ("vault", true, false, false),
+                ("referrer", true, false, true),
+            ]
+        );
+
+        // layouts from before the flags were stored read back without them
+        let legacy: Vec<(Vec<u8>, LegacyInstructionDecoder)> = idl
+            .instruction_params
+            .iter()
+            .map(|(disc, decoder)| {
+                let decoder = LegacyInstructionDecoder {
+                    accounts: decoder.accounts.iter().map(|a| a.name.clone()).collect(),
+                    instruction_args_parser: decoder.instruction_args_parser.clone(),
+                    docs: decoder.docs.clone(),
+                    deprecated: decoder.deprecated,
+                };
+                (disc.clone(), decoder)
+            })
+            .collect();
+        let mut serialized = idl.try_to_vec().unwrap();
+        serialized[4] = 3;
+        serialized.pop(); // enum_disc_width
+        let current_len = idl.instruction_params.try_to_vec().unwrap().len();
+        let start = 5
+            + idl.program_name.try_to_vec().unwrap().len()
+            + 2
+            + idl.accounts.try_to_vec().unwrap().len();
+        serialized.splice(start..start + current_len, legacy.try_to_vec().unwrap());
+        let old = OnChainIdl::try_from_slice(&serialized).unwrap();
+        assert_eq!(
+            old.instruction_params[0].1.accounts,
+            ["authority", "vault", "referrer"].map(AccountMeta::named)
+        );
+    }
+
+    #[test]
+    fn idl_level_enum_width_applies_to_untagged_enums() {
+        let json = r#"{
+          "version": "0.1.0",
+          "name": "orders",
+          "instructions": [],
+          "accounts": [
+            {
+              "name": "Order",
+              "type": {
+                "kind": "struct",
+                "fields": [
+                  { "name": "side", "type": { "defined": "Side" } },
+                  { "name": "status", "type": { "defined": "Status" } }
+                ]
+              }
+            }
+          ],
+          "types": [
+            {
+              "name": "Side",
+              "type": { "kind": "enum", "variants": [{ "name": "Bid" }, { "name": "Ask" }] }
+            },
+            {
+              "name": "Status",
+              "type": {
+                "kind": "enum",
+                "discriminant": "u8",
+                "variants": [{ "name": "Open" }, { "name": "Filled" }]
+              }
+            }
+          ]
+        }"#;
+        let options = ParseOptions {
+            enum_disc_width: Some(IntWidth::U16),
+            ..Default::default()
+        };
+        let idl = parse_idl_with_options(json.to_string(), &options).expect("parse_idl ok");
+
+        let mut data = hash(b"account:Order").to_bytes()[..8].to_vec();
+        data.extend_from_slice(&[1, 0]); // Side::Ask, 2-byte tag
+        data.push(1); // Status::Filled keeps its declared u8 tag
+        let parsed = idl.get_parsed_account(data.clone(), false).unwrap();
+        assert_eq!(
+            serde_json::to_value(&parsed.value).unwrap(),
+            serde_json::json!({ "side": "Ask", "status": "Filled" })
+        );
+        let order = &idl.accounts[0].1;
+        assert_eq!(order.typ.fixed_size_with(idl.enum_disc_width), Some(3));
+        assert_eq!(
+            order
+                .typ
+                .serialize_value_with(&parsed.value, idl.enum_disc_width)
+                .unwrap(),
+            data[8..]
+        );
+
+        // options that set a width win over the IDL's
+        let options = DecodeOptions {
+            enum_disc_width: Some(IntWidth::U8),
+            ..Default::default()
+        };
+        let parsed = idl
+            .get_parsed_account_with_options(data.clone(), &options)
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&parsed.value).unwrap(),
+            serde_json::json!({ "side": "Ask", "status": "Open" })
+        );
+
+        // with Borsh's u8 tags the tag's second byte is read as the status,
+        // and an explicit u8 discriminant is a plain enum
+        let borsh = parse_idl(json.to_string()).unwrap();
+        assert_eq!(borsh.enum_disc_width, IntWidth::U8);
+        assert!(matches!(borsh.types[1].typ, SchemaType::Enum(_)));
+        let parsed = borsh.get_parsed_account(data, false).unwrap();
+        assert_eq!(
+            serde_json::to_value(&parsed.value).unwrap(),
+            serde_json::json!({ "side": "Ask", "status": "Open" })
+        );
+    }
+
+    #[test]
+    fn enum_disc_width_round_trips() {
+        let mut idl = parse_idl(include_str!("../tests/layouts/counter.json").to_string()).unwrap();
+        idl.enum_disc_width = IntWidth::U32;
+        idl.size_instruction_decoders();
+        let restored = OnChainIdl::try_from_slice(&borsh::to_vec(&idl).unwrap()).unwrap();
+        assert_eq!(restored, idl);
+    }
+
+    #[test]
+    fn versioned_layouts_migrate() {
+        let v2 = OnChainIdl::try_from_slice(include_bytes!("../tests/layouts/v2.bin")).unwrap();
+        let v3 = OnChainIdl::try_from_slice(include_bytes!("../tests/layouts/v3.bin")).unwrap();
+        let v4 = OnChainIdl::try_from_slice(include_bytes!("../tests/layouts/v4.bin")).unwrap();
+        for idl in [&v2, &v3, &v4] {
+            assert_eq!(idl.enum_disc_width, IntWidth::U8);
+            check_counter_layout(idl);
         }
+        // only layout 4 kept the flags of instruction accounts
+        let counter = |idl: &OnChainIdl| idl.instruction_params[0].1.accounts[0].clone();
+        assert_eq!(counter(&v3), AccountMeta::named("counter"));
+        assert!(counter(&v4).is_mut);
     }
 }
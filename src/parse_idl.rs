@@ -1,6 +1,6 @@
 use std::{
     cell::RefCell,
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
 };
 
 use borsh::{BorshDeserialize, BorshSerialize};
@@ -8,52 +8,311 @@ use serde_json::{Map, Value};
 use solana_program::hash::hash;
 
 use crate::{
-    on_chain_idl::{InstructionDecoder, OnChainIdl},
-    schema::{SchemaNode, SchemaType, SmallVecLen},
+    on_chain_idl::{AccountMeta, DiscByteOrder, DiscPosition, InstructionDecoder, OnChainIdl},
+    schema::{IntWidth, SchemaNode, SchemaType, SmallVecLen},
 };
 
-pub fn parse_idl_file(file_path: &str) -> Result<OnChainIdl, Box<dyn std::error::Error>> {
+pub fn parse_idl_file(file_path: &str) -> Result<OnChainIdl, ParseIdlError> {
     parse_idl(std::fs::read_to_string(file_path)?)
 }
 
-pub fn parse_idl(json_str: String) -> Result<OnChainIdl, Box<dyn std::error::Error>> {
+/// Opt-in transformations applied while parsing an IDL
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Instructions taking a single struct arg (`fn foo(ctx, args: FooArgs)`)
+    /// get that struct's fields as their args, so they decode to
+    /// `{ "amount": .. }` rather than `{ "args": { "amount": .. } }`
+    pub flatten_single_struct_arg: bool,
+    /// Fail when any `{ "defined": .. }` reference names a type or constant
+    /// the IDL doesn't define, instead of skipping the types using it
+    pub strict: bool,
+    /// How integer discriminators (`{ "type": "u64", "value": 1 }`) are laid
+    /// out in data; byte-array discriminators are matched as written
+    pub disc_byte_order: DiscByteOrder,
+    /// Width of the discriminant of every enum that doesn't declare one
+    /// (`"discriminant": "u32"`), for programs that don't use Borsh's `u8`.
+    /// Overrides the IDL's own `"enumDiscriminant"`. See
+    /// `OnChainIdl::enum_disc_width`.
+    pub enum_disc_width: Option<IntWidth>,
+}
+
+pub fn parse_idl(json_str: String) -> Result<OnChainIdl, ParseIdlError> {
+    parse_idl_with_options(json_str, &ParseOptions::default())
+}
+
+/// Parses a compressed IDL, such as the zlib blob Anchor stores in a
+/// program's IDL account. Gzip is recognized by its magic bytes; anything
+/// else is inflated as zlib, then as raw deflate if that fails.
+pub fn parse_idl_compressed(bytes: &[u8]) -> Result<OnChainIdl, ParseIdlError> {
+    parse_idl(inflate(bytes)?)
+}
+
+fn inflate(bytes: &[u8]) -> Result<String, ParseIdlError> {
+    use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+    use std::io::Read;
+
+    let mut json = String::new();
+    if bytes.starts_with(&[0x1f, 0x8b]) {
+        GzDecoder::new(bytes).read_to_string(&mut json)?;
+        return Ok(json);
+    }
+    if ZlibDecoder::new(bytes).read_to_string(&mut json).is_ok() {
+        return Ok(json);
+    }
+    json.clear();
+    DeflateDecoder::new(bytes).read_to_string(&mut json)?;
+    Ok(json)
+}
+
+pub fn parse_idl_with_options(
+    json_str: String,
+    options: &ParseOptions,
+) -> Result<OnChainIdl, ParseIdlError> {
     let json: Value = serde_json::from_str(&json_str)?;
-    let root = json.as_object().ok_or("Root is not an object")?;
+    let root = json
+        .as_object()
+        .ok_or_else(|| missing("Root is not an object"))?;
 
     let mut idl_type_map = parse_types(root)?;
     parse_accounts(root, &mut idl_type_map)?;
 
-    let mut idl_parser = IdlParser::new(idl_type_map);
+    let constants = parse_constants(root)?;
+    if options.strict {
+        let unresolved = unresolved_defined_names(root, &idl_type_map, &constants);
+        if !unresolved.is_empty() {
+            let names: Vec<_> = unresolved.into_iter().collect();
+            return Err(ParseIdlError::UnresolvedTypes(names));
+        }
+    }
+
+    let enum_disc_width = match options.enum_disc_width {
+        Some(width) => width,
+        None => parse_enum_disc_width(root)?,
+    };
+    let mut idl_parser = IdlParser::new(idl_type_map)
+        .with_constants(constants)
+        .with_enum_disc_width(enum_disc_width);
     let schema_map = idl_parser.parse()?;
 
-    let (accounts, account_disc_len) = parse_account_schemas(root, &schema_map)?;
-    let (instruction_params, instruction_disc_len) = parse_instructions(root, &mut idl_parser)?;
+    let order = options.disc_byte_order;
+    let (accounts, account_disc_len) = parse_account_schemas(root, &schema_map, order)?;
+    let mut types: Vec<SchemaNode> = schema_map.values().cloned().collect();
+    types.sort_by(|a, b| a.name.cmp(&b.name));
+    let (instruction_params, instruction_disc_len) =
+        parse_instructions(root, &mut idl_parser, options)?;
+    let (events, event_disc_len) = parse_events(root, &idl_parser, order)?;
 
-    let on_chain_idl = OnChainIdl {
-        program_name: root
-            .get("name")
-            .and_then(|v| v.as_str())
-            .or_else(|| {
-                root.get("metadata")
-                    .and_then(|m| m.get("name"))
-                    .and_then(|v| v.as_str())
-            })
-            .unwrap_or_default()
-            .to_string(),
+    let mut on_chain_idl = OnChainIdl {
+        program_name: parse_program_name(root),
         account_disc_len,
         instruction_disc_len,
-        accounts: accounts.into_iter().collect(),
-        instruction_params: instruction_params.into_iter().collect(),
+        accounts: sorted_by_discriminator(accounts),
+        instruction_params: sorted_by_discriminator(instruction_params),
+        types,
+        event_disc_len,
+        events: sorted_by_discriminator(events),
+        account_version_prefix: root
+            .get("accountVersionPrefix")
+            .and_then(|v| v.as_bool())
+            .unwrap_or_default(),
+        account_disc_position: match root
+            .get("accountDiscriminatorPosition")
+            .and_then(|v| v.as_str())
+        {
+            None | Some("prefix") => DiscPosition::Prefix,
+            Some("suffix") => DiscPosition::Suffix,
+            Some(other) => {
+                return Err(ParseIdlError::Invalid(format!(
+                    "Unknown account discriminator position: {}",
+                    other
+                )))
+            }
+        },
+        disc_byte_order: order,
+        docs: parse_docs(root),
+        account_docs: parse_account_docs(root),
+        deprecated_accounts: parse_deprecated_accounts(root),
+        account_spaces: parse_account_spaces(root),
+        enum_disc_width: idl_parser.enum_disc_width,
+        #[cfg(feature = "decode-cache")]
+        decode_cache: None,
     };
+    on_chain_idl.size_instruction_decoders();
 
     validate_on_chain_idl(&on_chain_idl)?;
 
     Ok(on_chain_idl)
 }
 
+/// Parsed entries keyed by their discriminator bytes
+type DiscTable<T> = HashMap<Vec<u8>, T>;
+
+/// Keeps the output independent of `HashMap` iteration order, so parsing the
+/// same IDL twice yields equal `OnChainIdl`s
+fn sorted_by_discriminator<T>(map: DiscTable<T>) -> Vec<(Vec<u8>, T)> {
+    let mut entries: Vec<_> = map.into_iter().collect();
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+    entries
+}
+
+/// The IDL-wide enum width written by `to_anchor_idl_json`, e.g.
+/// `"enumDiscriminant": "u16"`; Borsh's `u8` without one
+fn parse_enum_disc_width(root: &Map<String, Value>) -> Result<IntWidth, ParseIdlError> {
+    match root.get("enumDiscriminant").and_then(|v| v.as_str()) {
+        None => Ok(IntWidth::U8),
+        Some(name) => IntWidth::from_name(name).ok_or_else(|| {
+            ParseIdlError::Invalid(format!("Unsupported enum discriminant: {}", name))
+        }),
+    }
+}
+
+/// Legacy Anchor IDLs carry a top-level "name", Anchor 0.30+ and Codama IDLs
+/// put it under "metadata"; anything else gets an empty name.
+fn parse_program_name(root: &Map<String, Value>) -> String {
+    root.get("name")
+        .and_then(|v| v.as_str())
+        .or_else(|| {
+            root.get("metadata")
+                .and_then(|m| m.get("name"))
+                .and_then(|v| v.as_str())
+        })
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// The `"docs"` string list of an IDL item, if any
+fn parse_docs(item: &Map<String, Value>) -> Vec<String> {
+    item.get("docs")
+        .and_then(|v| v.as_array())
+        .map(|docs| {
+            docs.iter()
+                .filter_map(|doc| doc.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Legacy IDLs document accounts on the account entry, Anchor 0.30+ on the
+/// type of the same name
+fn parse_account_docs(root: &Map<String, Value>) -> BTreeMap<String, Vec<String>> {
+    let items = |key: &str| {
+        root.get(key)
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|item| item.as_object())
+    };
+    let mut account_docs = BTreeMap::new();
+    for account in items("accounts") {
+        let Some(name) = account.get("name").and_then(|v| v.as_str()) else {
+            continue;
+        };
+        let mut docs = parse_docs(account);
+        if docs.is_empty() {
+            if let Some(typ) = items("types").find(|typ| typ.get("name") == account.get("name")) {
+                docs = parse_docs(typ);
+            }
+        }
+        if !docs.is_empty() {
+            account_docs.insert(name.to_string(), docs);
+        }
+    }
+    account_docs
+}
+
+/// Whether an IDL item carries `"deprecated": true`
+fn is_deprecated(item: &Map<String, Value>) -> bool {
+    item.get("deprecated")
+        .and_then(|v| v.as_bool())
+        .unwrap_or_default()
+}
+
+fn parse_deprecated_accounts(root: &Map<String, Value>) -> BTreeSet<String> {
+    root.get("accounts")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|account| account.as_object())
+        .filter(|account| is_deprecated(account))
+        .filter_map(|account| account.get("name").and_then(|v| v.as_str()))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Declared `"space"` of each account, for IDLs that record Anchor's
+/// `InitSpace`-computed sizes
+fn parse_account_spaces(root: &Map<String, Value>) -> BTreeMap<String, u64> {
+    root.get("accounts")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|account| account.as_object())
+        .filter_map(|account| {
+            let name = account.get("name")?.as_str()?;
+            let space = account.get("space")?.as_u64()?;
+            Some((name.to_string(), space))
+        })
+        .collect()
+}
+
+/// Names referenced with `{ "defined": .. }` anywhere in the IDL that are
+/// neither a type, a constant nor a built-in
+fn unresolved_defined_names(
+    root: &Map<String, Value>,
+    idl_type_map: &HashMap<String, Map<String, Value>>,
+    constants: &HashMap<String, Value>,
+) -> BTreeSet<String> {
+    fn collect(value: &Value, names: &mut BTreeSet<String>) {
+        match value {
+            Value::Object(object) => {
+                if let Some(defined) = object.get("defined") {
+                    let name = defined
+                        .as_str()
+                        .or_else(|| defined.get("name").and_then(|v| v.as_str()));
+                    names.extend(name.map(str::to_string));
+                }
+                object.values().for_each(|v| collect(v, names));
+            }
+            Value::Array(items) => items.iter().for_each(|v| collect(v, names)),
+            _ => (),
+        }
+    }
+
+    let mut names = BTreeSet::new();
+    for key in ["types", "accounts", "instructions", "events"] {
+        if let Some(value) = root.get(key) {
+            collect(value, &mut names);
+        }
+    }
+
+    let is_known = |name: &str| {
+        idl_type_map.contains_key(name)
+            || constants.contains_key(name)
+            || time_schema_type(name).is_some()
+    };
+    names
+        .into_iter()
+        .filter(|name| {
+            match name
+                .strip_prefix("SmallVec<")
+                .and_then(|s| s.strip_suffix('>'))
+                .and_then(|s| s.split(',').nth(1))
+                .map(str::trim)
+            {
+                Some(elem) => {
+                    primitive_schema_type(elem).is_none()
+                        && !elem.eq_ignore_ascii_case("pubkey")
+                        && !is_known(elem)
+                }
+                None => !is_known(name),
+            }
+        })
+        .collect()
+}
+
 fn parse_types(
     root: &Map<String, Value>,
-) -> Result<HashMap<String, Map<String, Value>>, Box<dyn std::error::Error>> {
+) -> Result<HashMap<String, Map<String, Value>>, ParseIdlError> {
     let mut idl_type_map = HashMap::new();
     // Handle missing or null types field gracefully - some IDLs don't have custom types
     let type_map_list = root
@@ -64,20 +323,42 @@ fn parse_types(
     for raw_type_map in type_map_list {
         let type_map = raw_type_map
             .as_object()
-            .ok_or("Type map is not an object")?;
+            .ok_or_else(|| missing("Type map is not an object"))?;
         let type_name = type_map
             .get("name")
             .and_then(|v| v.as_str())
-            .ok_or("Type name is not a string")?;
+            .ok_or_else(|| missing("Type name is not a string"))?;
         idl_type_map.insert(type_name.to_string(), type_map.clone());
     }
     Ok(idl_type_map)
 }
 
+fn parse_constants(root: &Map<String, Value>) -> Result<HashMap<String, Value>, ParseIdlError> {
+    let mut constants = HashMap::new();
+    let constant_list = root
+        .get("constants")
+        .and_then(|v| v.as_array())
+        .cloned()
+        .unwrap_or_default();
+    for raw_constant in constant_list {
+        let constant = raw_constant
+            .as_object()
+            .ok_or_else(|| missing("Constant is not an object"))?;
+        let name = constant
+            .get("name")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| missing("Constant name is not a string"))?;
+        if let Some(value) = constant.get("value") {
+            constants.insert(name.to_string(), value.clone());
+        }
+    }
+    Ok(constants)
+}
+
 fn parse_accounts(
     root: &Map<String, Value>,
     idl_type_map: &mut HashMap<String, Map<String, Value>>,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<(), ParseIdlError> {
     let account_map_list = root
         .get("accounts")
         .and_then(|s| s.as_array().cloned())
@@ -86,14 +367,14 @@ fn parse_accounts(
     for raw_account_map in account_map_list {
         let account_map = raw_account_map
             .as_object()
-            .ok_or("Account map is not an object")?;
+            .ok_or_else(|| missing("Account map is not an object"))?;
         let account_name = account_map
             .get("name")
             .and_then(|v| v.as_str())
-            .ok_or("Account name is not a string")?;
+            .ok_or_else(|| missing("Account name is not a string"))?;
 
         // Only merge if this account carries an inline layout (legacy style).
-        if account_map.get("type").is_some() {
+        if account_map.get("type").is_some() && referenced_type_name(account_map).is_none() {
             // Don’t overwrite an existing proper type definition
             idl_type_map
                 .entry(account_name.to_string())
@@ -106,7 +387,8 @@ fn parse_accounts(
 fn parse_account_schemas(
     root: &Map<String, Value>,
     schema_map: &HashMap<String, SchemaNode>,
-) -> Result<(HashMap<u64, SchemaNode>, u8), Box<dyn std::error::Error>> {
+    order: DiscByteOrder,
+) -> Result<(DiscTable<SchemaNode>, u8), ParseIdlError> {
     let mut account_disc_types = HashSet::new();
     let mut accounts = HashMap::new();
     let account_map_list = root
@@ -117,11 +399,11 @@ fn parse_account_schemas(
     for raw_account_map in account_map_list {
         let account_map = raw_account_map
             .as_object()
-            .ok_or("Account map is not an object")?;
+            .ok_or_else(|| missing("Account map is not an object"))?;
         let account_name = account_map
             .get("name")
             .and_then(|v| v.as_str())
-            .ok_or("Account name is not a string")?;
+            .ok_or_else(|| missing("Account name is not a string"))?;
 
         // let (key, disc_type) = if let Some(disc) = account_map.get("discriminant") {
         //     parse_explicit_discriminant(disc)?
@@ -134,27 +416,34 @@ fn parse_account_schemas(
             .get("discriminant")
             .or_else(|| account_map.get("discriminator"))
         {
-            parse_any_discriminator(disc)?
+            parse_any_discriminator(disc, order)?
         } else {
             let (key, _type_len) = parse_implicit_discriminant(account_name)?;
             (key, 8u8)
         };
         account_disc_types.insert(disc_len as u64);
-        accounts.insert(
-            key,
-            schema_map
+        let schema = match referenced_type_name(account_map) {
+            // keep the account's own name, which may differ from its type's
+            Some(type_name) => SchemaNode {
+                name: account_name.into(),
+                ..schema_map
+                    .get(type_name)
+                    .ok_or_else(|| ParseIdlError::UnknownType(type_name.to_string()))?
+                    .clone()
+            },
+            None => schema_map
                 .get(account_name)
-                .ok_or("Account not found in schema map")?
+                .ok_or_else(|| ParseIdlError::UnknownType(account_name.to_string()))?
                 .clone(),
-        );
+        };
+        accounts.insert(key, schema);
     }
 
     if account_disc_types.len() > 1 {
-        return Err(format!(
+        return Err(ParseIdlError::InvalidDiscriminator(format!(
             "Multiple discriminant types found: {:?}",
             account_disc_types
-        )
-        .into());
+        )));
     }
 
     let account_disc_len = *account_disc_types.iter().next().unwrap_or(&8) as u8;
@@ -162,96 +451,204 @@ fn parse_account_schemas(
     Ok((accounts, account_disc_len))
 }
 
+/// The type an account entry points at with `"type": { "defined": ... }`
+/// instead of carrying an inline layout
+fn referenced_type_name(account_map: &Map<String, Value>) -> Option<&str> {
+    let defined = account_map.get("type")?.get("defined")?;
+    defined
+        .as_str()
+        .or_else(|| defined.get("name").and_then(|v| v.as_str()))
+}
+
 // helper: accepts either object {"type": "...", "value": ...} or byte array [u8;N]
-// Returns (u64_value, disc_len_bytes)
-fn parse_any_discriminator(v: &serde_json::Value) -> Result<(u64, u8), Box<dyn std::error::Error>> {
+// Returns (discriminator bytes as they appear in data, disc_len_bytes)
+fn parse_any_discriminator(
+    v: &serde_json::Value,
+    order: DiscByteOrder,
+) -> Result<(Vec<u8>, u8), ParseIdlError> {
     if let Some(obj) = v.as_object() {
         // legacy/new object form: { type: "u64"|"u8", value: <u64> }
         let typ = obj
             .get("type")
             .and_then(|v| v.as_str())
-            .ok_or("Discriminant type is not a string")?;
+            .ok_or_else(|| missing("Discriminant type is not a string"))?;
         let disc_type_len = match typ {
             "u8" => 1u8,
             "u64" => 8u8,
-            other => return Err(format!("Unknown discriminant type: {}", other).into()),
+            other => {
+                return Err(ParseIdlError::InvalidDiscriminator(format!(
+                    "Unknown discriminant type: {}",
+                    other
+                )))
+            }
         };
         let val = obj
             .get("value")
             .and_then(|v| v.as_u64())
-            .ok_or("Discriminant value is not a u64")?;
-        return Ok((val, disc_type_len));
+            .ok_or_else(|| missing("Discriminant value is not a u64"))?;
+        // a wider value could never match the single byte read at decode time
+        if disc_type_len == 1 && val > u8::MAX as u64 {
+            return Err(ParseIdlError::InvalidDiscriminator(format!(
+                "Discriminant value {} does not fit in a u8",
+                val
+            )));
+        }
+        return Ok((order.bytes(val, disc_type_len), disc_type_len));
     }
 
     if let Some(arr) = v.as_array() {
-        // byte array form: [149, 0, 150, 1, ...]
-        // we interpret as little-endian; pad/truncate to 8 bytes to fit u64 (like your current reader).
-        let mut padded = [0u8; 8];
-        let n = arr.len().min(8);
-        for i in 0..n {
-            padded[i] = arr[i]
+        // byte array form: [149, 0, 150, 1, ...], matched as written
+        let mut bytes = Vec::with_capacity(arr.len());
+        for byte in arr {
+            let byte = byte
                 .as_u64()
-                .ok_or("Discriminator byte is not a number")? as u8;
+                .ok_or_else(|| missing("Discriminator byte is not a number"))?;
+            bytes.push(u8::try_from(byte).map_err(|_| {
+                ParseIdlError::InvalidDiscriminator(format!(
+                    "Discriminator byte {} is not a u8",
+                    byte
+                ))
+            })?);
         }
-        let val = u64::from_le_bytes(padded);
-        return Ok((val, arr.len() as u8));
+        return Ok((bytes, arr.len() as u8));
     }
 
-    Err("Unsupported discriminator/discriminant value; expected object or byte array".into())
+    Err(ParseIdlError::InvalidDiscriminator(
+        "Unsupported discriminator/discriminant value; expected object or byte array".to_string(),
+    ))
 }
 
-fn parse_implicit_discriminant(
-    account_name: &str,
-) -> Result<(u64, u64), Box<dyn std::error::Error>> {
+fn parse_implicit_discriminant(account_name: &str) -> Result<(Vec<u8>, u64), ParseIdlError> {
     let seeds = format!("account:{}", account_name).into_bytes();
-    let key = u64::from_le_bytes(
-        hash(&seeds).to_bytes()[..8]
-            .try_into()
-            .map_err(|_| "Failed to convert hash to u64")?,
-    );
-    Ok((key, 8))
+    Ok((hash(&seeds).to_bytes()[..8].to_vec(), 8))
+}
+
+fn parse_events(
+    root: &Map<String, Value>,
+    idl_parser: &IdlParser,
+    order: DiscByteOrder,
+) -> Result<(DiscTable<SchemaNode>, u8), ParseIdlError> {
+    let mut event_disc_types = HashSet::new();
+    let mut events = HashMap::new();
+    let event_map_list = root
+        .get("events")
+        .and_then(|s| s.as_array().cloned())
+        .unwrap_or_default();
+
+    for raw_event_map in event_map_list {
+        let event_map = raw_event_map
+            .as_object()
+            .ok_or_else(|| missing("Event map is not an object"))?;
+        let event_name = event_map
+            .get("name")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| missing("Event name is not a string"))?;
+
+        let (key, disc_len) = if let Some(disc) = event_map
+            .get("discriminant")
+            .or_else(|| event_map.get("discriminator"))
+        {
+            parse_any_discriminator(disc, order)?
+        } else {
+            let (key, _type_len) = parse_implicit_event_discriminant(event_name)?;
+            (key, 8u8)
+        };
+        event_disc_types.insert(disc_len as u64);
+        // Anchor 0.30+ keeps the event's layout in `types`, under its name
+        let schema = match event_map.get("fields") {
+            Some(fields) => {
+                let fields = fields.as_array().ok_or_else(|| {
+                    ParseIdlError::MissingField(format!(
+                        "Fields for event {} is not an array",
+                        event_name
+                    ))
+                })?;
+                idl_parser.parse_fields(event_name, fields)?
+            }
+            None => idl_parser.parse_type(event_name).map_err(|e| {
+                ParseIdlError::MissingField(format!(
+                    "Event {} has no fields and no type: {}",
+                    event_name, e
+                ))
+            })?,
+        };
+        events.insert(key, schema);
+    }
+
+    if event_disc_types.len() > 1 {
+        return Err(ParseIdlError::InvalidDiscriminator(format!(
+            "Multiple discriminant types found: {:?}",
+            event_disc_types
+        )));
+    }
+
+    let event_disc_len = *event_disc_types.iter().next().unwrap_or(&8) as u8;
+
+    Ok((events, event_disc_len))
+}
+
+fn parse_implicit_event_discriminant(event_name: &str) -> Result<(Vec<u8>, u64), ParseIdlError> {
+    let seeds = format!("event:{}", event_name).into_bytes();
+    Ok((hash(&seeds).to_bytes()[..8].to_vec(), 8))
 }
 
 fn parse_instructions(
     root: &Map<String, Value>,
     idl_parser: &mut IdlParser,
-) -> Result<(HashMap<u64, InstructionDecoder>, u8), Box<dyn std::error::Error>> {
+    options: &ParseOptions,
+) -> Result<(DiscTable<InstructionDecoder>, u8), ParseIdlError> {
     let mut instruction_disc_types = HashSet::new();
     let mut instruction_params = HashMap::new();
 
+    // Some IDL dialects name the instruction list "methods" or "transactions"
     let instruction_map_list = root
         .get("instructions")
+        .or_else(|| root.get("methods"))
+        .or_else(|| root.get("transactions"))
         .and_then(|v| v.as_array().cloned())
         .unwrap_or_default();
 
     for raw_instruction_map in instruction_map_list {
         let instruction_map = raw_instruction_map
             .as_object()
-            .ok_or("Instruction map is not an object")?;
+            .ok_or_else(|| missing("Instruction map is not an object"))?;
         let instruction_name = instruction_map
             .get("name")
             .and_then(|v| v.as_str())
-            .ok_or("Instruction name is not a string")?;
+            .ok_or_else(|| missing("Instruction name is not a string"))?;
 
         let accounts = parse_instruction_accounts(instruction_map)?;
         let instruction_args = parse_instruction_args(instruction_map)?;
 
-        let instruction_args_parser = if instruction_args.is_empty() {
-            SchemaNode::new(instruction_name, SchemaType::Empty)
-        } else {
-            idl_parser.parse_fields(instruction_name, &instruction_args)?
-        };
+        // argless instructions get an empty struct, so they decode to `{}`
+        let mut instruction_args_parser =
+            idl_parser.parse_fields(instruction_name, &instruction_args)?;
+        if options.flatten_single_struct_arg {
+            if let SchemaType::Struct(args) = &mut instruction_args_parser.typ {
+                if let [SchemaNode {
+                    typ: SchemaType::Struct(fields),
+                    ..
+                }] = args.as_mut_slice()
+                {
+                    *args = std::mem::take(fields);
+                }
+            }
+        }
 
         let instruction_decoder = InstructionDecoder {
             accounts,
             instruction_args_parser,
+            docs: parse_docs(instruction_map),
+            deprecated: is_deprecated(instruction_map),
+            disc_len: 0,
+            enum_disc_width: IntWidth::U8,
         };
 
         let (key, disc_len) = if let Some(disc) = instruction_map
             .get("discriminant")
             .or_else(|| instruction_map.get("discriminator"))
         {
-            parse_any_discriminator(disc)?
+            parse_any_discriminator(disc, options.disc_byte_order)?
         } else {
             let (key, _type_len) = parse_implicit_instruction_discriminant(instruction_name)?;
             (key, 8u8)
@@ -261,11 +658,10 @@ fn parse_instructions(
     }
 
     if instruction_disc_types.len() > 1 {
-        return Err(format!(
+        return Err(ParseIdlError::InvalidDiscriminator(format!(
             "Multiple discriminant types found: {:?}",
             instruction_disc_types
-        )
-        .into());
+        )));
     }
 
     let instruction_disc_len = *instruction_disc_types.iter().next().unwrap_or(&8) as u8;
@@ -275,26 +671,63 @@ fn parse_instructions(
 
 fn parse_instruction_accounts(
     instruction_map: &Map<String, Value>,
-) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+) -> Result<Vec<AccountMeta>, ParseIdlError> {
     let mut accounts = Vec::new();
     let accounts_list = instruction_map
         .get("accounts")
         .and_then(|v| v.as_array())
-        .ok_or("Accounts is not an array")?;
+        .ok_or_else(|| missing("Accounts is not an array"))?;
+    flatten_instruction_accounts(accounts_list, "", &mut accounts)?;
+    Ok(accounts)
+}
+
+/// Composite account groups (`{ "name": "group", "accounts": [...] }`) are
+/// flattened into dotted names such as `group.authority`
+fn flatten_instruction_accounts(
+    accounts_list: &[Value],
+    prefix: &str,
+    accounts: &mut Vec<AccountMeta>,
+) -> Result<(), ParseIdlError> {
     for raw_account in accounts_list {
-        let account = raw_account.as_object().ok_or("Account is not an object")?;
+        let account = raw_account
+            .as_object()
+            .ok_or_else(|| missing("Account is not an object"))?;
         let account_name = account
             .get("name")
             .and_then(|v| v.as_str())
-            .ok_or("Account name is not a string")?;
-        accounts.push(account_name.to_string());
+            .ok_or_else(|| missing("Account name is not a string"))?;
+        let full_name = format!("{}{}", prefix, account_name);
+        match account.get("accounts") {
+            Some(group) => {
+                let group = group
+                    .as_array()
+                    .ok_or_else(|| missing("Account group is not an array"))?;
+                flatten_instruction_accounts(group, &format!("{}.", full_name), accounts)?;
+            }
+            None => {
+                // legacy Anchor flags, or their names since Anchor 0.30
+                let flag = |legacy: &str, current: &str| {
+                    account
+                        .get(legacy)
+                        .or_else(|| account.get(current))
+                        .and_then(|v| v.as_bool())
+                        .unwrap_or(false)
+                };
+                accounts.push(AccountMeta {
+                    name: full_name,
+                    is_mut: flag("isMut", "writable"),
+                    is_signer: flag("isSigner", "signer"),
+                    is_optional: flag("isOptional", "optional"),
+                })
+            }
+        }
     }
-    Ok(accounts)
+    Ok(())
 }
 
 fn parse_instruction_args(
     instruction_map: &Map<String, Value>,
-) -> Result<Vec<Value>, Box<dyn std::error::Error>> {
+) -> Result<Vec<Value>, ParseIdlError> {
     Ok(instruction_map
         .get("args")
         .and_then(|v| v.as_array().cloned())
@@ -303,28 +736,26 @@ fn parse_instruction_args(
 
 fn parse_implicit_instruction_discriminant(
     instruction_name: &str,
-) -> Result<(u64, u64), Box<dyn std::error::Error>> {
+) -> Result<(Vec<u8>, u64), ParseIdlError> {
     let seeds = format!("global:{}", camel_to_snake_case(instruction_name)).into_bytes();
-    let key = u64::from_le_bytes(
-        hash(&seeds).to_bytes()[..8]
-            .try_into()
-            .map_err(|_| "Failed to convert hash to u64")?,
-    );
-    Ok((key, 8))
+    Ok((hash(&seeds).to_bytes()[..8].to_vec(), 8))
 }
 
-fn validate_on_chain_idl(on_chain_idl: &OnChainIdl) -> Result<(), Box<dyn std::error::Error>> {
+fn validate_on_chain_idl(on_chain_idl: &OnChainIdl) -> Result<(), ParseIdlError> {
     let serialized = on_chain_idl.try_to_vec()?;
     let deserialized = OnChainIdl::try_from_slice(&serialized)?;
     assert_eq!(deserialized, *on_chain_idl);
     Ok(())
 }
 
-fn _get_disc_from_str(s: &str) -> u64 {
+fn _get_disc_from_str(s: &str) -> Result<u64, ParseIdlError> {
     match s {
-        "u8" => 1,
-        "u64" => 8,
-        _ => panic!("Unknown discriminant type: {}", s),
+        "u8" => Ok(1),
+        "u64" => Ok(8),
+        _ => Err(ParseIdlError::InvalidDiscriminator(format!(
+            "Unknown discriminant type: {}",
+            s
+        ))),
     }
 }
 
@@ -352,20 +783,39 @@ fn camel_to_snake_case(s: &str) -> String {
 
 pub struct IdlParser {
     type_map: HashMap<String, Map<String, Value>>,
+    /// raw `value`s of the IDL's constants, by name
+    constants: HashMap<String, Value>,
     parsed_cache: RefCell<HashMap<String, SchemaNode>>,
+    /// width of enums without a `"discriminant"`, see `ParseOptions`
+    enum_disc_width: IntWidth,
 }
 
 impl IdlParser {
     pub fn new(type_map: HashMap<String, Map<String, Value>>) -> Self {
         Self {
             type_map,
+            constants: HashMap::new(),
             parsed_cache: RefCell::new(HashMap::new()),
+            enum_disc_width: IntWidth::U8,
         }
     }
+
+    /// Constants that array sizes may reference via `{ "defined": "NAME" }`
+    pub fn with_constants(mut self, constants: HashMap<String, Value>) -> Self {
+        self.constants = constants;
+        self
+    }
+
+    /// Width the IDL reads enums without a `"discriminant"` with, so that an
+    /// explicit `"discriminant": "u8"` can keep Borsh's width under a wider one
+    pub fn with_enum_disc_width(mut self, width: IntWidth) -> Self {
+        self.enum_disc_width = width;
+        self
+    }
 }
 
 impl IdlParser {
-    pub fn parse(&mut self) -> Result<HashMap<String, SchemaNode>, Box<dyn std::error::Error>> {
+    pub fn parse(&mut self) -> Result<HashMap<String, SchemaNode>, ParseIdlError> {
         let keys: Vec<_> = self.type_map.keys().cloned().collect();
         let mut types = HashMap::new();
         for type_name in keys {
@@ -382,45 +832,83 @@ impl IdlParser {
         Ok(types)
     }
 
-    fn parse_type(&self, type_name: &str) -> Result<SchemaNode, Box<dyn std::error::Error>> {
+    fn parse_type(&self, type_name: &str) -> Result<SchemaNode, ParseIdlError> {
         if let Some(schema) = self.parsed_cache.borrow().get(type_name) {
             return Ok(schema.clone());
         }
         let type_map = self
             .type_map
             .get(type_name)
-            .ok_or_else(|| format!("Type {} not found in type map", type_name))?;
+            .ok_or_else(|| ParseIdlError::UnknownType(type_name.to_string()))?;
+        // very old IDLs describe some accounts with a bare type, e.g. "publicKey"
+        if let Some(bare) = type_map.get("type").and_then(|v| v.as_str()) {
+            let schema = SchemaNode::new(type_name, parse_raw_schema_type(bare)?);
+            self.parsed_cache
+                .borrow_mut()
+                .insert(type_name.to_string(), schema.clone());
+            return Ok(schema);
+        }
         let typ = type_map
             .get("type")
             .and_then(|v| v.as_object())
-            .ok_or_else(|| format!("Type for {} is not an object", type_name))?;
+            .ok_or_else(|| {
+                ParseIdlError::MissingField(format!("Type for {} is not an object", type_name))
+            })?;
         let kind = typ
             .get("kind")
             .and_then(|v| v.as_str())
-            .ok_or("Kind is not a string")?;
+            .ok_or_else(|| missing("Kind is not a string"))?;
         let schema = match kind {
             "struct" => {
                 let fields = typ
                     .get("fields")
                     .and_then(|v| v.as_array())
-                    .ok_or_else(|| format!("Fields for {} is not an array", type_name))?;
+                    .ok_or_else(|| {
+                        ParseIdlError::MissingField(format!(
+                            "Fields for {} is not an array",
+                            type_name
+                        ))
+                    })?;
                 self.parse_fields(type_name, fields)
             }
-            "enum" => {
+            // Codama splits enums into C-style "scalarEnum"s and "dataEnum"s
+            // with payloads
+            "enum" | "scalarEnum" | "dataEnum" => {
                 let variants = typ
                     .get("variants")
                     .and_then(|v| v.as_array())
-                    .ok_or_else(|| format!("Variants for {} is not an array", type_name))?;
+                    .ok_or_else(|| {
+                        ParseIdlError::MissingField(format!(
+                            "Variants for {} is not an array",
+                            type_name
+                        ))
+                    })?;
                 let mut nodes = vec![];
                 for raw_variant in variants {
-                    let variant = raw_variant.as_object().ok_or("Variant is not an object")?;
+                    // scalar variants may be listed by name alone
+                    if let Some(variant_name) = raw_variant.as_str() {
+                        nodes.push(SchemaNode::new(variant_name, SchemaType::Empty));
+                        continue;
+                    }
+                    let variant = raw_variant
+                        .as_object()
+                        .ok_or_else(|| missing("Variant is not an object"))?;
                     let variant_name = variant
                         .get("name")
                         .and_then(|v| v.as_str())
-                        .ok_or("Variant name is not a string")?;
+                        .ok_or_else(|| missing("Variant name is not a string"))?;
                     if let Some(fields) = variant.get("fields") {
+                        if kind == "scalarEnum" {
+                            return Err(ParseIdlError::Invalid(format!(
+                                "Scalar enum {} has fields on variant {}",
+                                type_name, variant_name
+                            )));
+                        }
                         let fields = fields.as_array().ok_or_else(|| {
-                            format!("Fields for variant {} is not an array", variant_name)
+                            ParseIdlError::MissingField(format!(
+                                "Fields for variant {} is not an array",
+                                variant_name
+                            ))
                         })?;
                         let inner_schema = self.parse_fields(variant_name, fields)?.typ;
                         nodes.push(SchemaNode::new(variant_name, inner_schema));
@@ -428,17 +916,36 @@ impl IdlParser {
                         nodes.push(SchemaNode::new(variant_name, SchemaType::Empty));
                     }
                 }
-                Ok(SchemaNode::new(type_name, SchemaType::Enum(nodes)))
+                // non-Borsh programs may use a wider discriminant, e.g. "discriminant": "u32";
+                // an explicit "u8" only needs tagging when the IDL-wide width differs
+                match typ.get("discriminant").and_then(|v| v.as_str()) {
+                    None => Ok(SchemaNode::new(type_name, SchemaType::Enum(nodes))),
+                    Some("u8") if self.enum_disc_width == IntWidth::U8 => {
+                        Ok(SchemaNode::new(type_name, SchemaType::Enum(nodes)))
+                    }
+                    Some(width) => {
+                        let width = IntWidth::from_name(width).ok_or_else(|| {
+                            ParseIdlError::Invalid(format!(
+                                "Unsupported enum discriminant for {}: {}",
+                                type_name, width
+                            ))
+                        })?;
+                        Ok(SchemaNode::new(
+                            type_name,
+                            SchemaType::TaggedEnum(width, nodes),
+                        ))
+                    }
+                }
             }
             "alias" => {
                 // Type alias: parse the value field directly
-                let value = typ
-                    .get("value")
-                    .ok_or_else(|| format!("Alias {} missing value field", type_name))?;
+                let value = typ.get("value").ok_or_else(|| {
+                    ParseIdlError::MissingField(format!("Alias {} missing value field", type_name))
+                })?;
                 let inner_type = self.parse_field_inner(value)?;
                 Ok(SchemaNode::new(type_name, inner_type))
             }
-            _ => Err("Unknown type kind".into()),
+            other => Err(ParseIdlError::UnknownKind(other.to_string())),
         }?;
         self.parsed_cache
             .borrow_mut()
@@ -446,15 +953,25 @@ impl IdlParser {
         Ok(schema)
     }
 
-    fn parse_fields(
-        &self,
-        type_name: &str,
-        fields: &[Value],
-    ) -> Result<SchemaNode, Box<dyn std::error::Error>> {
+    fn parse_fields(&self, type_name: &str, fields: &[Value]) -> Result<SchemaNode, ParseIdlError> {
         let mut parsed_fields = Vec::new();
         for raw_field in fields {
             let field = self.parse_field(raw_field)?;
-            parsed_fields.push(field);
+            // `"flatten": true` inlines a struct's fields into the parent
+            let flatten = raw_field
+                .get("flatten")
+                .and_then(|v| v.as_bool())
+                .unwrap_or_default();
+            match field.typ {
+                SchemaType::Struct(inner) if flatten => parsed_fields.extend(inner),
+                _ if flatten => {
+                    return Err(ParseIdlError::Invalid(format!(
+                        "Flattened field {} is not a struct",
+                        field.name
+                    )))
+                }
+                _ => parsed_fields.push(field),
+            }
         }
         Ok(SchemaNode::new(
             type_name,
@@ -462,12 +979,14 @@ impl IdlParser {
         ))
     }
 
-    fn parse_field(&self, raw_field: &Value) -> Result<SchemaNode, Box<dyn std::error::Error>> {
+    fn parse_field(&self, raw_field: &Value) -> Result<SchemaNode, ParseIdlError> {
         if !raw_field.is_object() {
             return Ok(SchemaNode::new("", self.parse_field_inner(raw_field)?));
         }
 
-        let field = raw_field.as_object().ok_or("Field is not an object")?;
+        let field = raw_field
+            .as_object()
+            .ok_or_else(|| missing("Field is not an object"))?;
 
         if !field.contains_key("type") {
             return Ok(SchemaNode::new("", self.parse_field_inner(raw_field)?));
@@ -477,70 +996,126 @@ impl IdlParser {
             .get("name")
             .and_then(|v| v.as_str())
             .unwrap_or_default();
-        let field_type = field.get("type").ok_or("Field type not found")?.clone();
+        let field_type = field
+            .get("type")
+            .ok_or_else(|| missing("Field type not found"))?
+            .clone();
         let schema_type = self.parse_field_inner(&field_type)?;
-        Ok(SchemaNode::new(field_name, schema_type))
+        let mut node = SchemaNode::new(field_name, schema_type);
+        // fields serialized out of declaration order carry their position
+        if let Some(order) = field.get("serializationOrder") {
+            node.serialization_order = Some(
+                order
+                    .as_u64()
+                    .and_then(|order| u32::try_from(order).ok())
+                    .ok_or_else(|| {
+                        ParseIdlError::Invalid(format!(
+                            "Invalid serializationOrder for {}",
+                            field_name
+                        ))
+                    })?,
+            );
+        }
+        Ok(node)
     }
 
-    fn parse_field_inner(
-        &self,
-        field_type: &Value,
-    ) -> Result<SchemaType, Box<dyn std::error::Error>> {
+    fn parse_field_inner(&self, field_type: &Value) -> Result<SchemaType, ParseIdlError> {
         let schema_type = if field_type.is_object() {
             let field_type_object = field_type
                 .as_object()
-                .ok_or("Field type is not an object")?;
+                .ok_or_else(|| missing("Field type is not an object"))?;
             let (key, value) = field_type_object
                 .iter()
                 .next()
-                .ok_or("Field type object is empty")?;
+                .ok_or_else(|| missing("Field type object is empty"))?;
             match key.as_str() {
                 "vec" => SchemaType::vec(if value.is_object() {
                     self.parse_field_inner(value)?
                 } else {
-                    parse_raw_schema_type(value.as_str().ok_or("Vec type is not a string")?)?
+                    parse_raw_schema_type(
+                        value
+                            .as_str()
+                            .ok_or_else(|| missing("Vec type is not a string"))?,
+                    )?
                 }),
                 "option" => SchemaType::option(if value.is_object() {
                     self.parse_field_inner(value)?
                 } else {
-                    parse_raw_schema_type(value.as_str().ok_or("Option type is not a string")?)?
+                    parse_raw_schema_type(
+                        value
+                            .as_str()
+                            .ok_or_else(|| missing("Option type is not a string"))?,
+                    )?
                 }),
                 "array" => {
-                    let inner_array = value.as_array().ok_or("Array is not an array")?;
-                    let size = inner_array
-                        .get(1)
-                        .and_then(|v| v.as_u64())
-                        .ok_or("Array size is not a u64")? as usize;
-
-                    let value = inner_array.first().ok_or("Array value not found")?;
+                    let inner_array = value
+                        .as_array()
+                        .ok_or_else(|| missing("Array is not an array"))?;
+                    let size = self.parse_array_size(
+                        inner_array
+                            .get(1)
+                            .ok_or_else(|| missing("Array size not found"))?,
+                    )?;
+
+                    let value = inner_array
+                        .first()
+                        .ok_or_else(|| missing("Array value not found"))?;
                     let elem_type = if value.is_object() {
                         self.parse_field_inner(value)?
                     } else {
                         parse_raw_schema_type(
-                            value.as_str().ok_or("Array type is not a string")?,
+                            value
+                                .as_str()
+                                .ok_or_else(|| missing("Array type is not a string"))?,
                         )?
                     };
                     SchemaType::array(size, elem_type)
                 }
+                // { "tuple": [<type>, ...] }; elements may be labeled with
+                // { "name": .., "type": .. }
+                "tuple" => {
+                    let elements = value
+                        .as_array()
+                        .ok_or_else(|| missing("Tuple is not an array"))?
+                        .iter()
+                        .map(|element| self.parse_field(element))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    // same layout as a struct, so labeled tuples decode to an object
+                    if elements.iter().any(|element| !element.name.is_empty()) {
+                        SchemaType::Struct(
+                            elements
+                                .into_iter()
+                                .enumerate()
+                                .map(|(index, mut element)| {
+                                    if element.name.is_empty() {
+                                        element.name = index.to_string().into();
+                                    }
+                                    element
+                                })
+                                .collect(),
+                        )
+                    } else {
+                        SchemaType::Tuple(elements.into_iter().map(|element| element.typ).collect())
+                    }
+                }
                 "defined" => {
                     // Support both old format (string) and new format (object with name field)
                     let inner_type = if value.is_string() {
                         value
                             .as_str()
-                            .ok_or("Defined type is not a string")?
+                            .ok_or_else(|| missing("Defined type is not a string"))?
                             .to_string()
                     } else if value.is_object() {
                         value
                             .as_object()
                             .and_then(|obj| obj.get("name"))
                             .and_then(|name| name.as_str())
-                            .ok_or("Defined object missing 'name' field")?
+                            .ok_or_else(|| missing("Defined object missing 'name' field"))?
                             .to_string()
                     } else {
-                        return Err(
-                            "Defined type must be either a string or object with 'name' field"
-                                .into(),
-                        );
+                        return Err(missing(
+                            "Defined type must be either a string or object with 'name' field",
+                        ));
                     };
 
                     // NEW: handle inline parametrized types like SmallVec<u8,Pubkey>
@@ -550,21 +1125,19 @@ impl IdlParser {
                     {
                         // inner looks like: "u8,Pubkey"  or "u16,u8"  etc.
                         let mut parts = inner.split(',').map(|s| s.trim());
-                        let len_s = parts.next().ok_or("SmallVec missing len type")?;
-                        let elem_s = parts.next().ok_or("SmallVec missing elem type")?;
+                        let len_s = parts
+                            .next()
+                            .ok_or_else(|| missing("SmallVec missing len type"))?;
+                        let elem_s = parts
+                            .next()
+                            .ok_or_else(|| missing("SmallVec missing elem type"))?;
                         if parts.next().is_some() {
-                            return Err("SmallVec has more than two generic params".into());
+                            return Err(ParseIdlError::Invalid(
+                                "SmallVec has more than two generic params".to_string(),
+                            ));
                         }
 
-                        let len_ty = match len_s {
-                            "u8" => SmallVecLen::U8,
-                            "u16" => SmallVecLen::U16,
-                            other => {
-                                return Err(
-                                    format!("Unsupported SmallVec len type: {}", other).into()
-                                )
-                            }
-                        };
+                        let len_ty = parse_smallvec_len(len_s)?;
 
                         // Try built-ins first (handle Pubkey/publicKey/case too)
                         let elem_ty = match elem_s {
@@ -594,36 +1167,297 @@ impl IdlParser {
                         return Ok(SchemaType::SmallVec(len_ty, Box::new(elem_ty)));
                     }
 
+                    if let Some(time_type) = time_schema_type(&inner_type) {
+                        // unless the IDL defines it as something other than an i64
+                        if !self.type_map.contains_key(&inner_type)
+                            || self.parse_type(&inner_type)?.typ == SchemaType::I64
+                        {
+                            return Ok(time_type);
+                        }
+                    }
+
                     self.parse_type(&inner_type)?.typ
                 }
+                "lenientOption" => SchemaType::LenientOption(Box::new(if value.is_object() {
+                    self.parse_field_inner(value)?
+                } else {
+                    parse_raw_schema_type(
+                        value
+                            .as_str()
+                            .ok_or_else(|| missing("LenientOption type is not a string"))?,
+                    )?
+                })),
+                // { "bitmap": <bit count> }
+                "bitmap" => SchemaType::Bitmap(self.parse_array_size(value)?),
+                // { "countedArray": { "countFrom": "numEntries", "type": <field type> } }
+                "countedArray" => {
+                    let counted = value
+                        .as_object()
+                        .ok_or_else(|| missing("CountedArray is not an object"))?;
+                    let count_from = counted
+                        .get("countFrom")
+                        .and_then(|v| v.as_str())
+                        .ok_or_else(|| missing("CountedArray countFrom is not a string"))?;
+                    let element = self.parse_field_inner(
+                        counted
+                            .get("type")
+                            .ok_or_else(|| missing("CountedArray missing type"))?,
+                    )?;
+                    SchemaType::CountedArray {
+                        count_from: count_from.to_string(),
+                        element: Box::new(element),
+                    }
+                }
+                // { "tlv": [{ "tag": 1, "name": "transferFeeConfig", "type": <field type> }] }
+                "tlv" => {
+                    let mut entries = std::collections::BTreeMap::new();
+                    for entry in value
+                        .as_array()
+                        .ok_or_else(|| missing("Tlv is not an array"))?
+                    {
+                        let tag = entry
+                            .get("tag")
+                            .and_then(|v| v.as_u64())
+                            .and_then(|v| u16::try_from(v).ok())
+                            .ok_or_else(|| missing("Tlv tag is not a u16"))?;
+                        let name = entry
+                            .get("name")
+                            .and_then(|v| v.as_str())
+                            .ok_or_else(|| missing("Tlv entry name is not a string"))?;
+                        let typ = self.parse_field_inner(
+                            entry
+                                .get("type")
+                                .ok_or_else(|| missing("Tlv entry missing type"))?,
+                        )?;
+                        entries.insert(tag, SchemaNode::new(name, typ));
+                    }
+                    SchemaType::Tlv(entries)
+                }
+                // { "flaggedOption": { "flag": "u32", "type": <field type> } }
+                "flaggedOption" => {
+                    let flagged = value
+                        .as_object()
+                        .ok_or_else(|| missing("FlaggedOption is not an object"))?;
+                    let flag = flagged
+                        .get("flag")
+                        .and_then(|v| v.as_str())
+                        .ok_or_else(|| missing("FlaggedOption flag is not a string"))?;
+                    let flag_width = IntWidth::from_name(flag).ok_or_else(|| {
+                        ParseIdlError::Invalid(format!(
+                            "Unsupported FlaggedOption flag type: {}",
+                            flag
+                        ))
+                    })?;
+                    let inner = self.parse_field_inner(
+                        flagged
+                            .get("type")
+                            .ok_or_else(|| missing("FlaggedOption missing type"))?,
+                    )?;
+                    SchemaType::FlaggedOption {
+                        flag_width,
+                        inner: Box::new(inner),
+                    }
+                }
+                // { "hashMap": [<key type>, <value type>] }, same for "bTreeMap"
+                "hashMap" | "bTreeMap" => match value.as_array().map(|v| v.as_slice()) {
+                    Some([key, value]) => SchemaType::Map(
+                        Box::new(self.parse_field_inner(key)?),
+                        Box::new(self.parse_field_inner(value)?),
+                    ),
+                    _ => {
+                        return Err(ParseIdlError::Invalid(format!(
+                            "{} is not a [key, value] pair",
+                            key
+                        )))
+                    }
+                },
+                // Object form: { "smallVec": { "len": "u8", "elem": <field type> } }
+                "smallVec" => {
+                    let small_vec = value
+                        .as_object()
+                        .ok_or_else(|| missing("SmallVec is not an object"))?;
+                    let len_ty = parse_smallvec_len(
+                        small_vec
+                            .get("len")
+                            .and_then(|v| v.as_str())
+                            .ok_or_else(|| missing("SmallVec len is not a string"))?,
+                    )?;
+                    let elem_ty = self.parse_field_inner(
+                        small_vec
+                            .get("elem")
+                            .ok_or_else(|| missing("SmallVec missing elem"))?,
+                    )?;
+                    SchemaType::SmallVec(len_ty, Box::new(elem_ty))
+                }
                 _ => {
-                    return Err("Unknown field type".into());
+                    return Err(ParseIdlError::UnknownType(key.to_string()));
                 }
             }
         } else {
-            let field_type_name = field_type.as_str().ok_or("Field type is not a string")?;
+            let field_type_name = field_type
+                .as_str()
+                .ok_or_else(|| missing("Field type is not a string"))?;
             parse_raw_schema_type(field_type_name)?
         };
         Ok(schema_type)
     }
 }
 
-fn parse_raw_schema_type(name: &str) -> Result<SchemaType, Box<dyn std::error::Error>> {
-    // Support bracket-array shorthand like "[u8; 3]" or "[publicKey; 2]"
-    if let Some(inner) = name.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
-        let mut parts = inner.split(';');
-        let ty_s = parts.next().ok_or("Array missing element type")?.trim();
-        let len_s = parts.next().ok_or("Array missing length")?.trim();
-        if parts.next().is_some() {
-            return Err("Array syntax has extra parts".into());
+/// Semantic wrappers for well-known `i64` aliases
+fn time_schema_type(name: &str) -> Option<SchemaType> {
+    match name {
+        "UnixTimestamp" => Some(SchemaType::UnixTimestamp),
+        "Duration" => Some(SchemaType::Duration),
+        _ => None,
+    }
+}
+
+impl IdlParser {
+    /// Either a literal or a reference to a constant: `{ "defined": "MAX_LEN" }`
+    fn parse_array_size(&self, size: &Value) -> Result<usize, ParseIdlError> {
+        if let Some(size) = size.as_u64() {
+            return Ok(size as usize);
         }
-        let len: usize = len_s.parse::<usize>()?;
+        let defined = size
+            .get("defined")
+            .ok_or_else(|| missing("Array size is neither a u64 nor a defined constant"))?;
+        let name = defined
+            .as_str()
+            .or_else(|| defined.get("name").and_then(|v| v.as_str()))
+            .ok_or_else(|| missing("Array size constant name is not a string"))?;
+        let value = self.constants.get(name).ok_or_else(|| {
+            ParseIdlError::MissingField(format!("Array size constant {} not found", name))
+        })?;
+        // Anchor stores constant values as strings, e.g. "32"
+        value
+            .as_u64()
+            .map(|v| v as usize)
+            .or_else(|| value.as_str()?.trim().replace('_', "").parse().ok())
+            .ok_or_else(|| {
+                ParseIdlError::Invalid(format!(
+                    "Array size constant {} is not an integer: {}",
+                    name, value
+                ))
+            })
+    }
+}
+
+fn parse_smallvec_len(len_s: &str) -> Result<SmallVecLen, ParseIdlError> {
+    match len_s {
+        "u8" => Ok(SmallVecLen::U8),
+        "u16" => Ok(SmallVecLen::U16),
+        "shortU16" => Ok(SmallVecLen::ShortU16),
+        other => Err(ParseIdlError::Invalid(format!(
+            "Unsupported SmallVec len type: {}",
+            other
+        ))),
+    }
+}
+
+/// Why an IDL couldn't be parsed
+#[derive(Debug, thiserror::Error)]
+pub enum ParseIdlError {
+    /// a required key is missing or holds the wrong kind of JSON value
+    #[error("{0}")]
+    MissingField(String),
+    /// neither a primitive nor a type the IDL defines
+    #[error("Unknown type: {0}")]
+    UnknownType(String),
+    /// `{ "defined": .. }` references to names the IDL doesn't define, in
+    /// strict mode
+    #[error("Unresolved defined types: {}", .0.join(", "))]
+    UnresolvedTypes(Vec<String>),
+    /// a type definition's `kind` isn't one we decode
+    #[error("Unknown type kind: {0}")]
+    UnknownKind(String),
+    /// a discriminator that is malformed or inconsistent with the others
+    #[error("Invalid discriminator: {0}")]
+    InvalidDiscriminator(String),
+    /// malformed bracket-array shorthand, with the reason
+    #[error("Invalid array {name}: {reason}")]
+    InvalidArray { name: String, reason: String },
+    /// any other inconsistency in the IDL
+    #[error("{0}")]
+    Invalid(String),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+fn missing(what: &str) -> ParseIdlError {
+    ParseIdlError::MissingField(what.to_string())
+}
+
+impl SchemaType {
+    /// Parses an IDL primitive name (`"u64"`, `"pubkey"`, `"bytes"`, ...) or
+    /// bracket-array shorthand over one (`"[u8; 32]"`, `"[[u16; 2]; 4]"`)
+    pub fn from_idl_name(name: &str) -> Result<SchemaType, ParseIdlError> {
+        parse_raw_schema_type(name)
+    }
+}
+
+fn parse_raw_schema_type(name: &str) -> Result<SchemaType, ParseIdlError> {
+    // Support bracket-array shorthand like "[u8; 3]" or "[publicKey; 2]"
+    if let Some((ty_s, len_s)) = split_bracket_array(name)? {
+        let len: usize = len_s
+            .parse::<usize>()
+            .map_err(|e| ParseIdlError::InvalidArray {
+                name: name.to_string(),
+                reason: format!("length {}: {}", len_s, e),
+            })?;
         let elem = parse_raw_schema_type(ty_s)?;
         return Ok(SchemaType::array(len, elem));
     }
 
-    Ok(match name {
-        "pubkey" | "publicKey" => SchemaType::Pubkey,
+    primitive_schema_type(name).ok_or_else(|| ParseIdlError::UnknownType(name.to_string()))
+}
+
+/// Splits bracket-array shorthand like "[u8; 3]" or "[[u8; 4]; 3]" into its
+/// element type and length strings, splitting on the last top-level `;`.
+/// Returns `None` if `name` isn't bracketed.
+pub(crate) fn split_bracket_array(name: &str) -> Result<Option<(&str, &str)>, ParseIdlError> {
+    let Some(inner) = name.strip_prefix('[').and_then(|s| s.strip_suffix(']')) else {
+        return Ok(None);
+    };
+    let invalid_array = |reason: &str| ParseIdlError::InvalidArray {
+        name: name.to_string(),
+        reason: reason.to_string(),
+    };
+    let mut depth = 0i32;
+    let mut split_at = None;
+    for (i, c) in inner.char_indices() {
+        match c {
+            '[' | '<' | '(' => depth += 1,
+            ']' | '>' | ')' => depth -= 1,
+            ';' if depth == 0 => split_at = Some(i),
+            _ => (),
+        }
+        if depth < 0 {
+            return Err(invalid_array("unbalanced brackets"));
+        }
+    }
+    if depth != 0 {
+        return Err(invalid_array("unbalanced brackets"));
+    }
+    let split_at = split_at.ok_or_else(|| invalid_array("missing length"))?;
+    let ty_s = inner[..split_at].trim();
+    let len_s = inner[split_at + 1..].trim();
+    if ty_s.is_empty() {
+        return Err(invalid_array("missing element type"));
+    }
+    Ok(Some((ty_s, len_s)))
+}
+
+/// Maps an IDL primitive name to its `SchemaType`.
+///
+/// `"bytes"` is Anchor's Borsh `Vec<u8>`, i.e. a u32 length prefix followed
+/// by that many bytes. `"bytes_remaining"`/`"rest"`/`"remaining"` are raw, unprefixed bytes
+/// running to the end of the buffer and must be the last field.
+/// `"raw_string"` is length-prefixed like `"string"` but needn't be UTF-8.
+pub(crate) fn primitive_schema_type(name: &str) -> Option<SchemaType> {
+    Some(match name {
+        "pubkey" | "publicKey" | "Pubkey" => SchemaType::Pubkey,
         "string" => SchemaType::String,
         "i8" => SchemaType::I8,
         "u8" => SchemaType::U8,
@@ -639,17 +1473,25 @@ fn parse_raw_schema_type(name: &str) -> Result<SchemaType, Box<dyn std::error::E
         "f64" => SchemaType::F64,
         "bool" => SchemaType::Bool,
         "bytes" => SchemaType::Vec(Box::new(SchemaType::U8)),
-        "bytes_remaining" | "rest" => SchemaType::RemainingBytes,
-        _ => panic!("Unknown type: {}", name),
+        "bytes_remaining" | "rest" | "remaining" => SchemaType::RemainingBytes,
+        "utf8_remaining" => SchemaType::Utf8Remaining,
+        "raw_string" => SchemaType::RawString,
+        _ => return None,
     })
 }
 
 #[cfg(test)]
 mod test {
-    use super::camel_to_snake_case;
+    use super::{
+        camel_to_snake_case, parse_idl_compressed, parse_idl_with_options, parse_raw_schema_type,
+        IdlParser, ParseIdlError, ParseOptions,
+    };
     use crate::{
+        on_chain_idl::DiscByteOrder,
         parse_idl::parse_idl,
-        schema::{SchemaType, SmallVecLen},
+        schema::{IntWidth, SchemaNode, SchemaType, SmallVecLen},
+        test_fixtures::COUNTER_IDL_JSON,
+        value::{RenderOptions, TypedValue, ValueNode},
     };
     use solana_program::hash::hash;
 
@@ -720,7 +1562,7 @@ mod test {
                 // msg: SmallVec<u8, Pubkey>
                 let msg = fields
                     .iter()
-                    .find(|f| f.name == "msg")
+                    .find(|f| &*f.name == "msg")
                     .expect("msg present");
                 match &msg.typ {
                     SchemaType::SmallVec(len_ty, elem) => {
@@ -733,7 +1575,7 @@ mod test {
                 // ixs: SmallVec<u8, CompiledInstruction>
                 let ixs = fields
                     .iter()
-                    .find(|f| f.name == "ixs")
+                    .find(|f| &*f.name == "ixs")
                     .expect("ixs present");
                 match &ixs.typ {
                     SchemaType::SmallVec(len_ty, elem) => {
@@ -743,7 +1585,7 @@ mod test {
                                 // inside CompiledInstruction, check its SmallVec fields too
                                 let acc_idx = ci_fields
                                     .iter()
-                                    .find(|f| f.name == "accountIndexes")
+                                    .find(|f| &*f.name == "accountIndexes")
                                     .unwrap();
                                 match &acc_idx.typ {
                                     SchemaType::SmallVec(len_ty, elem) => {
@@ -752,7 +1594,7 @@ mod test {
                                     }
                                     other => panic!("accountIndexes wrong schema: {:?}", other),
                                 }
-                                let data = ci_fields.iter().find(|f| f.name == "data").unwrap();
+                                let data = ci_fields.iter().find(|f| &*f.name == "data").unwrap();
                                 match &data.typ {
                                     SchemaType::SmallVec(len_ty, elem) => {
                                         assert!(matches!(len_ty, SmallVecLen::U16));
@@ -798,13 +1640,13 @@ mod test {
         let (_disc, dec) = &idl.instruction_params[0];
         match &dec.instruction_args_parser.typ {
             SchemaType::Struct(fields) => {
-                let f_three = fields.iter().find(|f| f.name == "three").unwrap();
+                let f_three = fields.iter().find(|f| &*f.name == "three").unwrap();
                 match &f_three.typ {
                     SchemaType::Array(3, inner) => assert!(matches!(**inner, SchemaType::U8)),
                     other => panic!("three wrong schema: {:?}", other),
                 }
 
-                let f_keys = fields.iter().find(|f| f.name == "twoKeys").unwrap();
+                let f_keys = fields.iter().find(|f| &*f.name == "twoKeys").unwrap();
                 match &f_keys.typ {
                     SchemaType::Array(2, inner) => assert!(matches!(**inner, SchemaType::Pubkey)),
                     other => panic!("twoKeys wrong schema: {:?}", other),
@@ -834,4 +1676,1398 @@ mod test {
         assert_eq!(idl.instruction_params.len(), 1);
         assert_eq!(idl.program_name, "TestIDL");
     }
+
+    #[test]
+    fn parses_instructions_under_methods_key() {
+        let json = r#"{
+            "version": "1.0.0",
+            "name": "methods_prog",
+            "methods": [
+                {
+                    "name": "deposit",
+                    "accounts": [{"name": "vault", "isMut": true, "isSigner": false}],
+                    "args": [{"name": "amount", "type": "u64"}]
+                }
+            ]
+        }"#;
+
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        assert_eq!(idl.instruction_params.len(), 1);
+        let (_disc, dec) = &idl.instruction_params[0];
+        assert_eq!(&*dec.instruction_args_parser.name, "deposit");
+        assert_eq!(dec.accounts.len(), 1);
+        assert_eq!(dec.accounts[0].name, "vault");
+    }
+
+    #[test]
+    fn argless_instruction_decodes_to_empty_object() {
+        let json = r#"{
+            "version": "1.0.0",
+            "name": "pause_prog",
+            "instructions": [
+                {"name": "pause", "accounts": [{"name": "admin"}], "args": []}
+            ]
+        }"#;
+
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        let data = hash(b"global:pause").to_bytes()[..8].to_vec();
+        let parsed = idl
+            .get_parsed_instruction(data, &["admin".to_string()], false)
+            .unwrap();
+        assert_eq!(parsed.value, TypedValue::Struct(vec![]));
+        assert_eq!(
+            serde_json::to_value(&parsed).unwrap()["value"],
+            serde_json::json!({})
+        );
+    }
+
+    #[test]
+    fn empty_instruction_args_survive_borsh_round_trip() {
+        use crate::on_chain_idl::OnChainIdl;
+        use borsh::{BorshDeserialize, BorshSerialize};
+
+        let json = r#"{
+            "version": "1.0.0",
+            "name": "pause_prog",
+            "instructions": [
+                {"name": "pause", "accounts": [{"name": "admin"}], "args": []}
+            ]
+        }"#;
+        let mut idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        // hand-built IDLs may still use a bare `Empty` schema for no args
+        idl.instruction_params[0].1.instruction_args_parser =
+            SchemaNode::new("pause", SchemaType::Empty);
+
+        let serialized = idl.try_to_vec().unwrap();
+        let idl = OnChainIdl::try_from_slice(&serialized).unwrap();
+        assert_eq!(
+            idl.instruction_params[0].1.instruction_args_parser.typ,
+            SchemaType::Empty
+        );
+
+        let data = hash(b"global:pause").to_bytes()[..8].to_vec();
+        let parsed = idl
+            .get_parsed_instruction(data, &["admin".to_string()], false)
+            .unwrap();
+        assert_eq!(parsed.value, TypedValue::Empty);
+        assert_eq!(
+            serde_json::to_value(&parsed).unwrap()["value"],
+            serde_json::json!("")
+        );
+    }
+
+    #[test]
+    fn parses_program_instruction_and_account_docs() {
+        let json = r#"{
+            "address": "C73nDAFn23RYwiFa6vtHshSbcg8x6BLYjw3bERJ3vHxf",
+            "metadata": {"name": "docs_prog", "spec": "0.1.0", "version": "1.0.0"},
+            "docs": ["A program with docs"],
+            "instructions": [
+                {
+                    "name": "initialize",
+                    "docs": ["Creates the config.", "Can only be called once."],
+                    "discriminator": [175, 175, 109, 31, 13, 152, 155, 237],
+                    "accounts": [{"name": "config"}],
+                    "args": []
+                },
+                {
+                    "name": "noop",
+                    "discriminator": [1, 2, 3, 4, 5, 6, 7, 8],
+                    "accounts": [],
+                    "args": []
+                }
+            ],
+            "accounts": [
+                {"name": "Config", "discriminator": [155, 12, 170, 224, 30, 250, 204, 130]}
+            ],
+            "types": [
+                {
+                    "name": "Config",
+                    "docs": ["Global settings"],
+                    "type": {"kind": "struct", "fields": [{"name": "admin", "type": "pubkey"}]}
+                }
+            ]
+        }"#;
+
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        assert_eq!(idl.docs, vec!["A program with docs".to_string()]);
+        assert_eq!(
+            idl.instruction_docs("initialize"),
+            Some(
+                &[
+                    "Creates the config.".to_string(),
+                    "Can only be called once.".to_string()
+                ][..]
+            )
+        );
+        assert_eq!(idl.instruction_docs("noop"), Some(&[][..]));
+        assert_eq!(idl.instruction_docs("missing"), None);
+        assert_eq!(
+            idl.account_docs("Config"),
+            Some(&["Global settings".to_string()][..])
+        );
+    }
+
+    #[test]
+    fn flattens_a_single_struct_arg_when_asked() {
+        let json = r#"{
+            "version": "1.0.0",
+            "name": "args_prog",
+            "instructions": [
+                {
+                    "name": "deposit",
+                    "accounts": [],
+                    "args": [{"name": "args", "type": {"defined": "DepositArgs"}}]
+                }
+            ],
+            "types": [
+                {
+                    "name": "DepositArgs",
+                    "type": {
+                        "kind": "struct",
+                        "fields": [
+                            {"name": "amount", "type": "u64"},
+                            {"name": "memo", "type": "string"}
+                        ]
+                    }
+                }
+            ]
+        }"#;
+        let mut data = hash(b"global:deposit").to_bytes()[..8].to_vec();
+        data.extend_from_slice(&10u64.to_le_bytes());
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(b"gm");
+        let args = TypedValue::new_struct(vec![
+            ("amount", TypedValue::U64(10)),
+            ("memo", TypedValue::String("gm".to_string())),
+        ]);
+
+        let nested = parse_idl(json.to_string()).expect("parse_idl ok");
+        let parsed = nested
+            .get_parsed_instruction(data.clone(), &[], false)
+            .unwrap();
+        assert_eq!(
+            parsed.value,
+            TypedValue::new_struct(vec![("args", args.clone())])
+        );
+
+        let options = ParseOptions {
+            flatten_single_struct_arg: true,
+            ..Default::default()
+        };
+        let flat = parse_idl_with_options(json.to_string(), &options).expect("parse_idl ok");
+        let parsed = flat.get_parsed_instruction(data, &[], false).unwrap();
+        assert_eq!(parsed.value, args);
+    }
+
+    #[test]
+    fn accounts_may_reference_a_differently_named_type() {
+        let json = r#"{
+            "version": "1.0.0",
+            "name": "pool_prog",
+            "instructions": [],
+            "accounts": [
+                {"name": "Pool", "type": {"defined": "PoolState"}},
+                {"name": "Vault", "type": {"defined": {"name": "VaultState"}}}
+            ],
+            "types": [
+                {
+                    "name": "PoolState",
+                    "type": {"kind": "struct", "fields": [{"name": "liquidity", "type": "u64"}]}
+                },
+                {
+                    "name": "VaultState",
+                    "type": {"kind": "struct", "fields": [{"name": "owner", "type": "publicKey"}]}
+                }
+            ]
+        }"#;
+
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        let mut data = hash(b"account:Pool").to_bytes()[..8].to_vec();
+        data.extend_from_slice(&42u64.to_le_bytes());
+        let parsed = idl.get_parsed_account(data, false).unwrap();
+        assert_eq!(parsed.name, "Pool");
+        assert_eq!(
+            parsed.value,
+            TypedValue::new_struct(vec![("liquidity", TypedValue::U64(42))])
+        );
+        assert_eq!(
+            idl.get_type("PoolState").unwrap().typ,
+            SchemaType::Struct(vec![SchemaNode::new("liquidity", SchemaType::U64)])
+        );
+
+        let vault = idl
+            .accounts
+            .iter()
+            .find(|(_, schema)| &*schema.name == "Vault")
+            .map(|(_, schema)| schema)
+            .unwrap();
+        assert_eq!(vault.typ, idl.get_type("VaultState").unwrap().typ);
+    }
+
+    #[test]
+    fn parses_bare_typed_accounts() {
+        let json = r#"{
+            "version": "0.0.0",
+            "name": "legacy_prog",
+            "instructions": [],
+            "accounts": [
+                {"name": "Delegate", "type": "publicKey"}
+            ]
+        }"#;
+
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        let delegate = solana_program::pubkey::Pubkey::new_unique();
+        let mut data = hash(b"account:Delegate").to_bytes()[..8].to_vec();
+        data.extend_from_slice(delegate.as_ref());
+        let parsed = idl.get_parsed_account(data, false).unwrap();
+        assert_eq!(parsed.name, "Delegate");
+        assert_eq!(parsed.value, TypedValue::Pubkey(delegate.to_string()));
+    }
+
+    #[test]
+    fn flattened_struct_fields_become_siblings() {
+        let json = r#"{
+            "version": "1.0.0",
+            "name": "flatten_prog",
+            "instructions": [],
+            "accounts": [
+                {
+                    "name": "Position",
+                    "type": {
+                        "kind": "struct",
+                        "fields": [
+                            {"name": "owner", "type": "publicKey"},
+                            {"name": "common", "type": {"defined": "Common"}, "flatten": true},
+                            {"name": "size", "type": "u64"}
+                        ]
+                    }
+                }
+            ],
+            "types": [
+                {
+                    "name": "Common",
+                    "type": {
+                        "kind": "struct",
+                        "fields": [{"name": "bump", "type": "u8"}, {"name": "version", "type": "u16"}]
+                    }
+                }
+            ]
+        }"#;
+
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        let position = idl.get_type("Position").unwrap();
+        assert_eq!(
+            position.typ,
+            SchemaType::Struct(vec![
+                SchemaNode::new("owner", SchemaType::Pubkey),
+                SchemaNode::new("bump", SchemaType::U8),
+                SchemaNode::new("version", SchemaType::U16),
+                SchemaNode::new("size", SchemaType::U64),
+            ])
+        );
+
+        let owner = solana_program::pubkey::Pubkey::new_unique();
+        let mut data = hash(b"account:Position").to_bytes()[..8].to_vec();
+        data.extend_from_slice(owner.as_ref());
+        data.push(255);
+        data.extend_from_slice(&2u16.to_le_bytes());
+        data.extend_from_slice(&1000u64.to_le_bytes());
+        let parsed = idl.get_parsed_account(data, false).unwrap();
+        assert_eq!(
+            parsed.value,
+            TypedValue::new_struct(vec![
+                ("owner", TypedValue::Pubkey(owner.to_string())),
+                ("bump", TypedValue::U8(255)),
+                ("version", TypedValue::U16(2)),
+                ("size", TypedValue::U64(1000)),
+            ])
+        );
+    }
+
+    #[test]
+    fn flattens_nested_account_groups() {
+        let json = r#"{
+            "version": "1.0.0",
+            "name": "composite_prog",
+            "instructions": [
+                {
+                    "name": "swap",
+                    "accounts": [
+                        {"name": "user", "isMut": false, "isSigner": true},
+                        {
+                            "name": "pool",
+                            "accounts": [
+                                {"name": "authority", "isMut": false, "isSigner": false},
+                                {"name": "vault", "isMut": true, "isSigner": false}
+                            ]
+                        },
+                        {"name": "tokenProgram", "isMut": false, "isSigner": false}
+                    ],
+                    "args": []
+                }
+            ]
+        }"#;
+
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        let (_disc, dec) = &idl.instruction_params[0];
+        let names: Vec<&str> = dec.accounts.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["user", "pool.authority", "pool.vault", "tokenProgram"]
+        );
+    }
+
+    #[test]
+    fn flattens_two_level_account_groups_in_order() {
+        let json = r#"{
+            "version": "1.0.0",
+            "name": "router",
+            "instructions": [
+                {
+                    "name": "route",
+                    "accounts": [
+                        {"name": "payer", "isMut": true, "isSigner": true},
+                        {
+                            "name": "hop",
+                            "accounts": [
+                                {
+                                    "name": "market",
+                                    "accounts": [
+                                        {"name": "bids", "isMut": true, "isSigner": false},
+                                        {"name": "asks", "isMut": true, "isSigner": false}
+                                    ]
+                                },
+                                {"name": "program", "isMut": false, "isSigner": false}
+                            ]
+                        },
+                        {"name": "systemProgram", "isMut": false, "isSigner": false}
+                    ],
+                    "args": []
+                }
+            ]
+        }"#;
+
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        let (_disc, dec) = &idl.instruction_params[0];
+        let accounts: Vec<(&str, bool)> = dec
+            .accounts
+            .iter()
+            .map(|a| (a.name.as_str(), a.is_mut))
+            .collect();
+        assert_eq!(
+            accounts,
+            vec![
+                ("payer", true),
+                ("hop.market.bids", true),
+                ("hop.market.asks", true),
+                ("hop.program", false),
+                ("systemProgram", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn bytes_is_length_prefixed_and_rest_is_not() {
+        let json = r#"{
+            "version": "1.0.0",
+            "name": "bytes_prog",
+            "instructions": [
+                {
+                    "name": "write",
+                    "accounts": [],
+                    "args": [
+                        {"name": "prefixed", "type": "bytes"},
+                        {"name": "trailing", "type": "rest"}
+                    ]
+                }
+            ]
+        }"#;
+
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        let (_disc, dec) = &idl.instruction_params[0];
+        match &dec.instruction_args_parser.typ {
+            SchemaType::Struct(fields) => {
+                assert_eq!(fields[0].typ, SchemaType::vec(SchemaType::U8));
+                assert_eq!(fields[1].typ, SchemaType::RemainingBytes);
+            }
+            other => panic!("args not a struct: {:?}", other),
+        }
+
+        // u32 length 2, then [7, 8]; everything after that is "rest"
+        let data: Vec<u8> = vec![2, 0, 0, 0, 7, 8, 9, 10, 11];
+        let value = dec
+            .instruction_args_parser
+            .deserialize_bytes(&mut data.as_slice(), false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            value.value,
+            TypedValue::new_struct(vec![
+                ("prefixed", TypedValue::Bytes(vec![7, 8])),
+                ("trailing", TypedValue::Bytes(vec![9, 10, 11])),
+            ])
+        );
+    }
+
+    #[test]
+    fn parses_nested_generics_around_defined_types() {
+        let json = r#"{
+            "version": "1.0.0",
+            "name": "nested_prog",
+            "instructions": [
+                {
+                    "name": "nested",
+                    "accounts": [],
+                    "args": [
+                        {"name": "optVec", "type": {"option": {"vec": {"defined": "Foo"}}}},
+                        {"name": "vecOpt", "type": {"vec": {"option": {"defined": "Foo"}}}},
+                        {"name": "arr", "type": {"array": [{"defined": "Foo"}, 3]}}
+                    ]
+                }
+            ],
+            "types": [
+                {
+                    "name": "Foo",
+                    "type": {"kind": "struct", "fields": [{"name": "x", "type": "u16"}]}
+                }
+            ]
+        }"#;
+
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        let (_disc, dec) = &idl.instruction_params[0];
+        let foo = SchemaType::Struct(vec![SchemaNode::new("x", SchemaType::U16)]);
+        assert_eq!(
+            dec.instruction_args_parser.typ,
+            SchemaType::Struct(vec![
+                SchemaNode::new("optVec", SchemaType::option(SchemaType::vec(foo.clone()))),
+                SchemaNode::new("vecOpt", SchemaType::vec(SchemaType::option(foo.clone()))),
+                SchemaNode::new("arr", SchemaType::array(3, foo)),
+            ])
+        );
+
+        let mut data = vec![1u8];
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&7u16.to_le_bytes());
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.push(0);
+        data.push(1);
+        data.extend_from_slice(&8u16.to_le_bytes());
+        for x in [1u16, 2, 3] {
+            data.extend_from_slice(&x.to_le_bytes());
+        }
+
+        let mut bytes = data.as_slice();
+        let value = dec
+            .instruction_args_parser
+            .deserialize_bytes(&mut bytes, false)
+            .unwrap()
+            .unwrap();
+        assert!(bytes.is_empty(), "buffer fully consumed");
+
+        let foo = |x: u16| TypedValue::new_struct(vec![("x", TypedValue::U16(x))]);
+        assert_eq!(
+            value.value,
+            TypedValue::new_struct(vec![
+                (
+                    "optVec",
+                    TypedValue::Option(Box::new(Some(TypedValue::Vec(vec![foo(7)])))),
+                ),
+                (
+                    "vecOpt",
+                    TypedValue::Vec(vec![
+                        TypedValue::Option(Box::new(None)),
+                        TypedValue::Option(Box::new(Some(foo(8)))),
+                    ]),
+                ),
+                ("arr", TypedValue::Array(vec![foo(1), foo(2), foo(3)])),
+            ])
+        );
+    }
+
+    #[test]
+    fn parses_smallvec_object_form() {
+        let json = r#"{
+            "version": "1.0.0",
+            "name": "smallvec_obj",
+            "instructions": [
+                {
+                    "name": "push",
+                    "accounts": [],
+                    "args": [
+                        {"name": "batches", "type": {"smallVec": {"len": "u8", "elem": {"vec": "u64"}}}}
+                    ]
+                }
+            ]
+        }"#;
+
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        let (_disc, dec) = &idl.instruction_params[0];
+        assert_eq!(
+            dec.instruction_args_parser.typ,
+            SchemaType::Struct(vec![SchemaNode::new(
+                "batches",
+                SchemaType::SmallVec(SmallVecLen::U8, Box::new(SchemaType::vec(SchemaType::U64))),
+            )])
+        );
+
+        // two batches: [1] and [2, 3]
+        let mut data = vec![2u8];
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&1u64.to_le_bytes());
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&2u64.to_le_bytes());
+        data.extend_from_slice(&3u64.to_le_bytes());
+
+        let value = dec
+            .instruction_args_parser
+            .deserialize_bytes(&mut data.as_slice(), false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            value.value,
+            TypedValue::new_struct(vec![(
+                "batches",
+                TypedValue::Vec(vec![
+                    TypedValue::Vec(vec![TypedValue::U64(1)]),
+                    TypedValue::Vec(vec![TypedValue::U64(2), TypedValue::U64(3)]),
+                ]),
+            )])
+        );
+    }
+
+    #[test]
+    fn program_name_falls_back_to_metadata() {
+        let metadata_only = r#"{
+            "address": "C73nDAFn23RYwiFa6vtHshSbcg8x6BLYjw3bERJ3vHxf",
+            "metadata": {"name": "codama_prog", "version": "1.0.0", "spec": "0.1.0"},
+            "instructions": []
+        }"#;
+        let idl = parse_idl(metadata_only.to_string()).expect("parse_idl ok");
+        assert_eq!(idl.program_name, "codama_prog");
+
+        let both = r#"{
+            "name": "top_level",
+            "metadata": {"name": "nested"},
+            "instructions": []
+        }"#;
+        let idl = parse_idl(both.to_string()).expect("parse_idl ok");
+        assert_eq!(idl.program_name, "top_level");
+
+        let idl = parse_idl(r#"{"instructions": []}"#.to_string()).expect("parse_idl ok");
+        assert_eq!(idl.program_name, "");
+    }
+
+    #[test]
+    fn parses_nested_bracket_arrays() {
+        assert_eq!(
+            parse_raw_schema_type("[[u8; 4]; 3]").unwrap(),
+            SchemaType::array(3, SchemaType::array(4, SchemaType::U8))
+        );
+        assert_eq!(
+            parse_raw_schema_type("[[[u16; 2]; 2]; 5]").unwrap(),
+            SchemaType::array(
+                5,
+                SchemaType::array(2, SchemaType::array(2, SchemaType::U16))
+            )
+        );
+        assert_eq!(
+            parse_raw_schema_type("[Pubkey; 2]").unwrap(),
+            SchemaType::array(2, SchemaType::Pubkey)
+        );
+        assert!(parse_raw_schema_type("[u8 4]").is_err());
+        assert!(parse_raw_schema_type("[[u8; 4; 3]").is_err());
+    }
+
+    #[test]
+    fn idl_names_parse_without_panicking() {
+        assert_eq!(
+            SchemaType::from_idl_name("[u8; 4]").unwrap(),
+            SchemaType::array(4, SchemaType::U8)
+        );
+        assert_eq!(
+            SchemaType::from_idl_name("pubkey").unwrap(),
+            SchemaType::Pubkey
+        );
+        assert!(matches!(
+            SchemaType::from_idl_name("OrderBook"),
+            Err(ParseIdlError::UnknownType(name)) if name == "OrderBook"
+        ));
+        assert!(matches!(
+            SchemaType::from_idl_name("[u8; many]"),
+            Err(ParseIdlError::InvalidArray { .. })
+        ));
+    }
+
+    #[test]
+    fn bogus_field_types_fail_the_parse_instead_of_panicking() {
+        let json = r#"{
+            "name": "bogus",
+            "instructions": [
+                {
+                    "name": "set",
+                    "accounts": [],
+                    "args": [{"name": "value", "type": "u7"}]
+                }
+            ]
+        }"#;
+        let err = parse_idl(json.to_string()).expect_err("bogus arg type must not parse");
+        assert!(err.to_string().contains("u7"), "{}", err);
+
+        assert!(super::_get_disc_from_str("u16").is_err());
+        assert_eq!(super::_get_disc_from_str("u64").unwrap(), 8);
+    }
+
+    #[test]
+    fn parse_errors_can_be_matched_by_failure_class() {
+        assert!(matches!(
+            parse_idl("{ not json".to_string()),
+            Err(ParseIdlError::Json(_))
+        ));
+        assert!(matches!(
+            parse_idl("[]".to_string()),
+            Err(ParseIdlError::MissingField(_))
+        ));
+
+        let json = r#"{
+            "name": "bad_disc",
+            "instructions": [
+                { "name": "set", "accounts": [], "args": [], "discriminator": [1, 256] }
+            ]
+        }"#;
+        assert!(matches!(
+            parse_idl(json.to_string()),
+            Err(ParseIdlError::InvalidDiscriminator(_))
+        ));
+
+        let json = r#"{
+            "name": "bad_kind",
+            "instructions": [
+                { "name": "set", "accounts": [], "args": [{ "name": "v", "type": { "defined": "Weird" } }] }
+            ],
+            "types": [{ "name": "Weird", "type": { "kind": "union", "fields": [] } }]
+        }"#;
+        let idl = IdlParser::new(
+            serde_json::from_str::<serde_json::Value>(json).unwrap()["types"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|t| {
+                    (
+                        t["name"].as_str().unwrap().to_string(),
+                        t.as_object().unwrap().clone(),
+                    )
+                })
+                .collect(),
+        );
+        assert!(matches!(
+            idl.parse_type("Weird"),
+            Err(ParseIdlError::UnknownKind(kind)) if kind == "union"
+        ));
+        // the instruction using the type fails the same way
+        assert!(matches!(
+            parse_idl(json.to_string()),
+            Err(ParseIdlError::UnknownKind(kind)) if kind == "union"
+        ));
+    }
+
+    #[test]
+    fn compressed_idls_inflate_before_parsing() {
+        use flate2::{
+            write::{DeflateEncoder, GzEncoder, ZlibEncoder},
+            Compression,
+        };
+        use std::io::Write;
+
+        let json = COUNTER_IDL_JSON;
+        let expected = parse_idl(json.to_string()).unwrap();
+
+        let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+        zlib.write_all(json.as_bytes()).unwrap();
+        let mut deflate = DeflateEncoder::new(Vec::new(), Compression::default());
+        deflate.write_all(json.as_bytes()).unwrap();
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(json.as_bytes()).unwrap();
+
+        for compressed in [
+            zlib.finish().unwrap(),
+            deflate.finish().unwrap(),
+            gzip.finish().unwrap(),
+        ] {
+            assert_eq!(parse_idl_compressed(&compressed).unwrap(), expected);
+        }
+        assert!(matches!(
+            parse_idl_compressed(b"not compressed"),
+            Err(ParseIdlError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn remaining_keyword_parses_to_remaining_bytes() {
+        let typ = parse_raw_schema_type("remaining").unwrap();
+        assert_eq!(typ, SchemaType::RemainingBytes);
+
+        let mut data: &[u8] = &[4, 5, 6];
+        assert_eq!(
+            typ.deserialize_bytes(&mut data, false).unwrap(),
+            TypedValue::Bytes(vec![4, 5, 6])
+        );
+        assert!(data.is_empty());
+    }
+
+    #[test]
+    fn time_aliases_parse_to_semantic_types() {
+        let json = r#"{
+            "name": "vesting",
+            "instructions": [],
+            "types": [
+                {
+                    "name": "Schedule",
+                    "type": {
+                        "kind": "struct",
+                        "fields": [
+                            {"name": "start", "type": {"defined": "UnixTimestamp"}},
+                            {"name": "cliff", "type": {"defined": {"name": "Duration"}}}
+                        ]
+                    }
+                },
+                {
+                    "name": "UnixTimestamp",
+                    "type": {"kind": "alias", "value": "i64"}
+                }
+            ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        let schedule = idl.get_type("Schedule").expect("Schedule type");
+        assert_eq!(
+            schedule.typ,
+            SchemaType::Struct(vec![
+                SchemaNode::new("start", SchemaType::UnixTimestamp),
+                SchemaNode::new("cliff", SchemaType::Duration),
+            ])
+        );
+
+        let mut data = 1_700_000_000i64.to_le_bytes().to_vec();
+        data.extend_from_slice(&3_600i64.to_le_bytes());
+        let value = schedule
+            .deserialize_bytes(&mut data.as_slice(), false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&value.value).unwrap(),
+            serde_json::json!({"start": "1700000000", "cliff": "3600"})
+        );
+        let options = RenderOptions {
+            humanize_time: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(value.value.render(&options)).unwrap(),
+            serde_json::json!({"start": "2023-11-14T22:13:20Z", "cliff": "PT1H"})
+        );
+    }
+
+    #[test]
+    fn parses_and_decodes_u32_tagged_enum() {
+        let json = r#"{
+            "name": "tagged",
+            "instructions": [],
+            "types": [
+                {
+                    "name": "Side",
+                    "type": {
+                        "kind": "enum",
+                        "discriminant": "u32",
+                        "variants": [
+                            {"name": "Bid"},
+                            {"name": "Ask", "fields": [{"name": "price", "type": "u16"}]}
+                        ]
+                    }
+                }
+            ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        let side = idl.get_type("Side").expect("Side type");
+        assert!(matches!(
+            side.typ,
+            SchemaType::TaggedEnum(IntWidth::U32, ref variants) if variants.len() == 2
+        ));
+
+        let mut data: &[u8] = &[0x01, 0x00, 0x00, 0x00, 0x2a, 0x00];
+        let value = side.typ.deserialize_bytes(&mut data, false).unwrap();
+        assert_eq!(
+            value,
+            TypedValue::Enum(Box::new(ValueNode::new_struct(
+                "Ask",
+                vec![("price", TypedValue::U16(42))]
+            )))
+        );
+        assert!(data.is_empty());
+
+        let mut out_of_bounds: &[u8] = &[0x02, 0x00, 0x00, 0x00];
+        assert!(side
+            .typ
+            .deserialize_bytes(&mut out_of_bounds, false)
+            .is_err());
+    }
+
+    #[test]
+    fn parses_scalar_and_data_enums() {
+        let json = r#"{
+            "name": "codama_enums",
+            "instructions": [],
+            "types": [
+                {
+                    "name": "Side",
+                    "type": {"kind": "scalarEnum", "variants": ["Bid", "Ask"]}
+                },
+                {
+                    "name": "Status",
+                    "type": {"kind": "enum", "variants": ["Open", {"name": "Closed"}]}
+                },
+                {
+                    "name": "Fill",
+                    "type": {
+                        "kind": "dataEnum",
+                        "variants": [
+                            "None",
+                            {"name": "Partial", "fields": [{"name": "size", "type": "u16"}]}
+                        ]
+                    }
+                }
+            ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+
+        let side = idl.get_type("Side").expect("Side type");
+        let value = side.typ.deserialize_bytes(&mut &[1u8][..], false).unwrap();
+        assert_eq!(
+            value,
+            TypedValue::Enum(Box::new(ValueNode::new("Ask", TypedValue::Empty)))
+        );
+        let status = idl.get_type("Status").expect("Status type");
+        let value = status
+            .typ
+            .deserialize_bytes(&mut &[0u8][..], false)
+            .unwrap();
+        assert_eq!(
+            value,
+            TypedValue::Enum(Box::new(ValueNode::new("Open", TypedValue::Empty)))
+        );
+        let fill = idl.get_type("Fill").expect("Fill type");
+        let value = fill
+            .typ
+            .deserialize_bytes(&mut &[1u8, 7, 0][..], false)
+            .unwrap();
+        assert_eq!(
+            value,
+            TypedValue::Enum(Box::new(ValueNode::new_struct(
+                "Partial",
+                vec![("size", TypedValue::U16(7))]
+            )))
+        );
+
+        let payload_in_scalar = json.replace(r#""kind": "dataEnum""#, r#""kind": "scalarEnum""#);
+        // a type that fails to parse is left out
+        let idl = parse_idl(payload_in_scalar).expect("parse_idl ok");
+        assert!(idl.get_type("Fill").is_none());
+    }
+
+    #[test]
+    fn parses_and_decodes_flagged_option() {
+        let json = r#"{
+            "name": "flagged",
+            "instructions": [],
+            "types": [
+                {
+                    "name": "Config",
+                    "type": {
+                        "kind": "struct",
+                        "fields": [
+                            {"name": "limit", "type": {"flaggedOption": {"flag": "u32", "type": "u64"}}},
+                            {"name": "bump", "type": "u8"}
+                        ]
+                    }
+                }
+            ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        let config = idl.get_type("Config").expect("Config type");
+
+        let mut present = 1u32.to_le_bytes().to_vec();
+        present.extend_from_slice(&500u64.to_le_bytes());
+        present.push(254);
+        let value = config
+            .deserialize_bytes(&mut present.as_slice(), false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            value.value,
+            TypedValue::new_struct(vec![
+                ("limit", TypedValue::from(Some(500u64))),
+                ("bump", TypedValue::U8(254)),
+            ])
+        );
+
+        let mut absent = 0u32.to_le_bytes().to_vec();
+        absent.push(254);
+        let value = config
+            .deserialize_bytes(&mut absent.as_slice(), false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            value.value,
+            TypedValue::new_struct(vec![
+                ("limit", TypedValue::from(None::<u64>)),
+                ("bump", TypedValue::U8(254)),
+            ])
+        );
+    }
+
+    #[test]
+    fn lenient_option_tolerates_bad_tags_while_option_stays_strict() {
+        let json = r#"{
+            "name": "options",
+            "instructions": [],
+            "types": [
+                {
+                    "name": "Slot",
+                    "type": {
+                        "kind": "struct",
+                        "fields": [
+                            {"name": "legacy", "type": {"lenientOption": "u8"}},
+                            {"name": "current", "type": {"option": "u8"}}
+                        ]
+                    }
+                }
+            ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        let slot = idl.get_type("Slot").expect("Slot type");
+
+        // a garbage tag on the lenient field reads as None
+        let mut data: &[u8] = &[2, 1, 9];
+        let value = slot.deserialize_bytes(&mut data, false).unwrap().unwrap();
+        assert_eq!(
+            value.value,
+            TypedValue::new_struct(vec![
+                ("legacy", TypedValue::from(None::<u8>)),
+                ("current", TypedValue::from(Some(9u8))),
+            ])
+        );
+
+        // while the same tag on the strict field is an error
+        let mut data: &[u8] = &[0, 2];
+        let err = slot.deserialize_bytes(&mut data, false).unwrap_err();
+        assert!(err.to_string().contains("Invalid Option tag: 2"), "{}", err);
+    }
+
+    #[test]
+    fn decodes_fields_in_serialization_order() {
+        let json = r#"{
+            "name": "ported",
+            "instructions": [],
+            "types": [
+                {
+                    "name": "Header",
+                    "type": {
+                        "kind": "struct",
+                        "fields": [
+                            {"name": "version", "type": "u8", "serializationOrder": 2},
+                            {"name": "length", "type": "u32", "serializationOrder": 1},
+                            {"name": "flags", "type": "u16", "serializationOrder": 0}
+                        ]
+                    }
+                }
+            ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        let header = idl.get_type("Header").expect("Header type");
+
+        // flags, then length, then version
+        let mut data = 0x0102u16.to_le_bytes().to_vec();
+        data.extend_from_slice(&300u32.to_le_bytes());
+        data.push(7);
+
+        let value = header
+            .deserialize_bytes(&mut data.as_slice(), false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            value.value,
+            TypedValue::new_struct(vec![
+                ("version", TypedValue::U8(7)),
+                ("length", TypedValue::U32(300)),
+                ("flags", TypedValue::U16(0x0102)),
+            ])
+        );
+        assert_eq!(
+            header.deserialize_field(&data, "version").unwrap(),
+            TypedValue::U8(7)
+        );
+    }
+
+    #[test]
+    fn resolves_array_size_from_constant() {
+        let idl_with_size = |size: &str| {
+            format!(
+                r#"{{
+                    "name": "consts",
+                    "instructions": [],
+                    "constants": [
+                        {{"name": "MAX_LEN", "type": "usize", "value": "32"}},
+                        {{"name": "SEED", "type": "bytes", "value": "[118, 97, 117, 108, 116]"}}
+                    ],
+                    "types": [
+                        {{
+                            "name": "Name",
+                            "type": {{
+                                "kind": "struct",
+                                "fields": [{{"name": "bytes", "type": {{"array": ["u8", {}]}}}}]
+                            }}
+                        }}
+                    ]
+                }}"#,
+                size
+            )
+        };
+
+        let idl = parse_idl(idl_with_size(r#"{"defined": "MAX_LEN"}"#)).expect("parse_idl ok");
+        assert_eq!(
+            idl.get_type("Name").unwrap().typ,
+            SchemaType::Struct(vec![SchemaNode::new(
+                "bytes",
+                SchemaType::array(32, SchemaType::U8)
+            )])
+        );
+
+        let parser = |size: &str| {
+            let json: serde_json::Value = serde_json::from_str(&idl_with_size(size)).unwrap();
+            let root = json.as_object().unwrap();
+            IdlParser::new(super::parse_types(root).unwrap())
+                .with_constants(super::parse_constants(root).unwrap())
+        };
+        let err = parser(r#"{"defined": "MISSING"}"#)
+            .parse_type("Name")
+            .unwrap_err();
+        assert!(err.to_string().contains("MISSING not found"), "{}", err);
+        let err = parser(r#"{"defined": "SEED"}"#)
+            .parse_type("Name")
+            .unwrap_err();
+        assert!(err.to_string().contains("not an integer"), "{}", err);
+    }
+
+    #[test]
+    fn deprecated_items_are_not_active_but_still_decode() {
+        let json = r#"{
+            "version": "1.0.0",
+            "name": "legacy_prog",
+            "instructions": [
+                {"name": "deposit", "accounts": [], "args": [{"name": "amount", "type": "u64"}]},
+                {
+                    "name": "depositV0",
+                    "deprecated": true,
+                    "accounts": [],
+                    "args": [{"name": "amount", "type": "u32"}]
+                }
+            ],
+            "accounts": [
+                {
+                    "name": "Vault",
+                    "type": {"kind": "struct", "fields": [{"name": "owner", "type": "publicKey"}]}
+                },
+                {
+                    "name": "VaultV0",
+                    "deprecated": true,
+                    "type": {"kind": "struct", "fields": [{"name": "owner", "type": "publicKey"}]}
+                }
+            ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+
+        assert_eq!(idl.active_instruction_names(), vec!["deposit"]);
+        assert_eq!(idl.active_account_names(), vec!["Vault"]);
+
+        let mut data = hash(b"global:deposit_v0").to_bytes()[..8].to_vec();
+        data.extend_from_slice(&5u32.to_le_bytes());
+        let parsed = idl.get_parsed_instruction(data, &[], false).unwrap();
+        assert_eq!(parsed.name, "depositV0");
+        assert_eq!(
+            parsed.value,
+            TypedValue::new_struct(vec![("amount", TypedValue::U32(5))])
+        );
+    }
+
+    #[test]
+    fn strict_mode_rejects_dangling_defined_references() {
+        let json = r#"{
+            "version": "1.0.0",
+            "name": "dangling_prog",
+            "instructions": [
+                {
+                    "name": "swap",
+                    "accounts": [],
+                    "args": [
+                        {"name": "route", "type": {"vec": {"defined": "RouteStep"}}},
+                        {"name": "deadline", "type": {"defined": "UnixTimestamp"}}
+                    ]
+                }
+            ],
+            "accounts": [
+                {
+                    "name": "Pool",
+                    "type": {
+                        "kind": "struct",
+                        "fields": [
+                            {"name": "fees", "type": {"defined": {"name": "FeeTier"}}},
+                            {"name": "config", "type": {"defined": "Config"}}
+                        ]
+                    }
+                }
+            ],
+            "types": [
+                {"name": "Config", "type": {"kind": "struct", "fields": []}}
+            ]
+        }"#;
+        let strict = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+
+        let err = parse_idl_with_options(json.to_string(), &strict).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unresolved defined types: FeeTier, RouteStep"
+        );
+
+        let resolved = json.replace(
+            r#""types": ["#,
+            r#""types": [
+                {"name": "FeeTier", "type": {"kind": "struct", "fields": []}},
+                {"name": "RouteStep", "type": {"kind": "struct", "fields": []}},"#,
+        );
+        assert!(parse_idl_with_options(resolved, &strict).is_ok());
+    }
+
+    #[test]
+    fn labeled_tuples_decode_to_named_objects() {
+        let json = r#"{
+            "version": "1.0.0",
+            "name": "tuple_prog",
+            "instructions": [
+                {
+                    "name": "setRange",
+                    "accounts": [],
+                    "args": [
+                        {
+                            "name": "range",
+                            "type": {"tuple": [{"name": "low", "type": "u16"}, {"name": "high", "type": "u16"}]}
+                        },
+                        {"name": "pair", "type": {"tuple": ["u8", {"option": "bool"}]}}
+                    ]
+                }
+            ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+
+        let mut data = hash(b"global:set_range").to_bytes()[..8].to_vec();
+        data.extend_from_slice(&10u16.to_le_bytes());
+        data.extend_from_slice(&20u16.to_le_bytes());
+        data.extend_from_slice(&[3, 1, 1]);
+        let parsed = idl.get_parsed_instruction(data, &[], false).unwrap();
+        assert_eq!(
+            serde_json::to_value(&parsed.value).unwrap(),
+            serde_json::json!({
+                "range": { "low": 10, "high": 20 },
+                "pair": [3, true]
+            })
+        );
+    }
+
+    #[test]
+    fn bitmaps_decode_into_one_bool_per_bit() {
+        let json = r#"{
+            "name": "slots",
+            "instructions": [],
+            "accounts": [
+                {
+                    "name": "SlotMap",
+                    "type": {"kind": "struct", "fields": [{"name": "used", "type": {"bitmap": 12}}]}
+                }
+            ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        let slot_map = idl.get_type("SlotMap").expect("SlotMap type");
+        assert_eq!(slot_map.typ.fixed_size(), Some(2));
+
+        // bits 0, 2, 8 and 11 set; the top 4 bits of the second byte are padding
+        let data = [0b0000_0101u8, 0b1111_1001];
+        let value = slot_map
+            .deserialize_bytes(&mut data.as_slice(), false)
+            .unwrap()
+            .unwrap();
+        let expected = (0..12).map(|bit| TypedValue::Bool([0, 2, 8, 11].contains(&bit)));
+        assert_eq!(
+            value.value,
+            TypedValue::new_struct(vec![("used", TypedValue::Vec(expected.collect()))])
+        );
+    }
+
+    #[test]
+    fn big_endian_integer_discriminators_are_written_big_endian() {
+        let json = r#"{
+            "name": "be_tags",
+            "instructions": [
+                {
+                    "name": "ping",
+                    "accounts": [],
+                    "args": [],
+                    "discriminant": {"type": "u64", "value": 2}
+                },
+                {
+                    "name": "set",
+                    "accounts": [],
+                    "args": [{"name": "value", "type": "u16"}],
+                    "discriminant": {"type": "u64", "value": 1}
+                }
+            ]
+        }"#;
+        let options = ParseOptions {
+            disc_byte_order: DiscByteOrder::BigEndian,
+            ..Default::default()
+        };
+        let idl = parse_idl_with_options(json.to_string(), &options).expect("parse_idl ok");
+        assert_eq!(
+            idl.instruction_discriminator_bytes("set"),
+            Some(vec![0, 0, 0, 0, 0, 0, 0, 1])
+        );
+
+        let parsed = idl
+            .get_parsed_instruction(vec![0, 0, 0, 0, 0, 0, 0, 1, 0x34, 0x12], &[], false)
+            .unwrap();
+        assert_eq!(parsed.name, "set");
+        assert_eq!(
+            parsed.value,
+            TypedValue::new_struct(vec![("value", TypedValue::U16(0x1234))])
+        );
+
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        assert_eq!(
+            idl.instruction_discriminator_bytes("set"),
+            Some(vec![1, 0, 0, 0, 0, 0, 0, 0])
+        );
+
+        // byte arrays are matched as written, whatever the order
+        let json = json
+            .replace(r#"{"type": "u64", "value": 2}"#, "[0, 0, 0, 2]")
+            .replace(r#"{"type": "u64", "value": 1}"#, "[0, 0, 0, 1]");
+        let idl = parse_idl_with_options(json, &options).expect("parse_idl ok");
+        let parsed = idl
+            .get_parsed_instruction(vec![0, 0, 0, 1, 0x34, 0x12], &[], false)
+            .unwrap();
+        assert_eq!(parsed.name, "set");
+    }
+
+    #[test]
+    fn sixteen_byte_discriminators_match_in_full() {
+        // the first 8 bytes are shared, so only the tail tells them apart
+        let json = r#"{
+            "name": "long_tags",
+            "instructions": [
+                {
+                    "name": "open",
+                    "accounts": [],
+                    "args": [],
+                    "discriminator": [1, 2, 3, 4, 5, 6, 7, 8, 9, 9, 9, 9, 9, 9, 9, 1]
+                },
+                {
+                    "name": "close",
+                    "accounts": [],
+                    "args": [{"name": "value", "type": "u8"}],
+                    "discriminator": [1, 2, 3, 4, 5, 6, 7, 8, 9, 9, 9, 9, 9, 9, 9, 2]
+                }
+            ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        assert_eq!(idl.instruction_disc_len, 16);
+
+        let mut data = vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 9, 9, 9, 9, 9, 9, 2];
+        data.push(42);
+        let parsed = idl.get_parsed_instruction(data, &[], false).unwrap();
+        assert_eq!(parsed.name, "close");
+        assert_eq!(
+            parsed.value,
+            TypedValue::new_struct(vec![("value", TypedValue::U8(42))])
+        );
+        assert_eq!(
+            idl.instruction_discriminator_bytes("open").unwrap().len(),
+            16
+        );
+    }
+
+    #[test]
+    fn parses_trimmed_anchor_030_idl() {
+        let json = r#"{
+            "address": "11111111111111111111111111111111",
+            "metadata": {"name": "amm", "version": "0.1.0", "spec": "0.1.0"},
+            "instructions": [
+                {
+                    "name": "set_fees",
+                    "discriminator": [9, 8, 7, 6, 5, 4, 3, 2],
+                    "accounts": [{"name": "pool", "writable": true}],
+                    "args": [{"name": "fees", "type": {"defined": {"name": "Fees"}}}]
+                }
+            ],
+            "accounts": [
+                {"name": "Pool", "discriminator": [1, 2, 3, 4, 5, 6, 7, 8]}
+            ],
+            "types": [
+                {
+                    "name": "Fees",
+                    "type": {
+                        "kind": "struct",
+                        "fields": [
+                            {"name": "trade_bps", "type": "u16"},
+                            {"name": "protocol_bps", "type": "u16"}
+                        ]
+                    }
+                },
+                {
+                    "name": "Pool",
+                    "type": {
+                        "kind": "struct",
+                        "fields": [
+                            {"name": "authority", "type": "pubkey"},
+                            {"name": "fees", "type": {"defined": {"name": "Fees"}}}
+                        ]
+                    }
+                }
+            ]
+        }"#;
+        let idl = parse_idl(json.to_string()).expect("parse_idl ok");
+        let fees = TypedValue::new_struct(vec![
+            ("trade_bps", TypedValue::U16(30)),
+            ("protocol_bps", TypedValue::U16(5)),
+        ]);
+
+        let authority = solana_program::pubkey::Pubkey::new_unique();
+        let mut account = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        account.extend_from_slice(&authority.to_bytes());
+        account.extend_from_slice(&[30, 0, 5, 0]);
+        let parsed = idl.get_parsed_account(account, false).unwrap();
+        assert_eq!(parsed.name, "Pool");
+        assert_eq!(
+            parsed.value,
+            TypedValue::new_struct(vec![
+                ("authority", TypedValue::Pubkey(authority.to_string())),
+                ("fees", fees.clone()),
+            ])
+        );
+
+        let parsed = idl
+            .get_parsed_instruction(vec![9, 8, 7, 6, 5, 4, 3, 2, 30, 0, 5, 0], &[], false)
+            .unwrap();
+        assert_eq!(parsed.name, "set_fees");
+        assert_eq!(parsed.value, TypedValue::new_struct(vec![("fees", fees)]));
+    }
 }
@@ -0,0 +1,521 @@
+//! Decoding into a bump arena, for indexers decoding at very high rates: the
+//! collections, strings and byte blobs of a decode all live in one `Bump`
+//! and are released together when it's reset, instead of one heap
+//! allocation per `Vec` and string.
+
+use borsh::BorshDeserialize;
+use bumpalo::{collections::Vec as BumpVec, Bump};
+use serde::{ser::SerializeMap, Serialize, Serializer};
+use solana_program::pubkey::Pubkey;
+
+use crate::{
+    schema::{
+        bytes_deserialize::{serialization_order, DecodeContext},
+        DecodeOptions, IntWidth, SchemaNode, SchemaType,
+    },
+    value::{TypedValue, ValueNode},
+};
+
+/// A decoded value allocated in a `Bump`. It renders to the same JSON as
+/// the `TypedValue` the heap decoder produces for the same bytes.
+#[derive(Debug, PartialEq)]
+pub enum ArenaValue<'b> {
+    /// a fixed-size scalar; never a variant owning heap memory, since the
+    /// arena doesn't run destructors
+    Scalar(TypedValue),
+    Pubkey(Pubkey),
+    String(&'b str),
+    Bytes(&'b [u8]),
+    Option(Option<&'b ArenaValue<'b>>),
+    Array(&'b [ArenaValue<'b>]),
+    Tuple(&'b [ArenaValue<'b>]),
+    Vec(&'b [ArenaValue<'b>]),
+    Struct(&'b [ArenaNode<'b>]),
+    Enum(&'b ArenaNode<'b>),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ArenaNode<'b> {
+    pub name: &'b str,
+    pub value: ArenaValue<'b>,
+}
+
+impl SchemaNode {
+    /// Like `deserialize_bytes_with_options`, but allocating the result in
+    /// `bump`. See `SchemaType::deserialize_bytes_in` for the supported types.
+    pub fn deserialize_bytes_in<'b>(
+        &self,
+        bytes: &mut &[u8],
+        bump: &'b Bump,
+        options: &DecodeOptions,
+    ) -> anyhow::Result<Option<ArenaNode<'b>>> {
+        let value = self.typ.deserialize_bytes_in(bytes, bump, options)?;
+        if self.is_hidden && !options.show_hidden {
+            Ok(None)
+        } else {
+            Ok(Some(ArenaNode {
+                name: bump.alloc_str(&self.name),
+                value,
+            }))
+        }
+    }
+}
+
+impl SchemaType {
+    /// Like `deserialize_bytes_with_options`, but allocating the result in
+    /// `bump`. `Tlv`, `Map`, `CountedArray`, `Utf8Remaining` and `RawString`
+    /// aren't supported and fail the decode.
+    pub fn deserialize_bytes_in<'b>(
+        &self,
+        bytes: &mut &[u8],
+        bump: &'b Bump,
+        options: &DecodeOptions,
+    ) -> anyhow::Result<ArenaValue<'b>> {
+        let mut ctx = DecodeContext::new(options, bytes);
+        let result = decode_in(self, bytes, bump, &mut ctx);
+        ctx.finish(result)
+    }
+}
+
+fn decode_in<'b>(
+    typ: &SchemaType,
+    bytes: &mut &[u8],
+    bump: &'b Bump,
+    ctx: &mut DecodeContext,
+) -> anyhow::Result<ArenaValue<'b>> {
+    let remaining = bytes.len();
+    let tolerate_eof = ctx.take_trailing_tolerance();
+    let result = decode_value_in(typ, bytes, bump, ctx, tolerate_eof);
+    if result.is_err() {
+        ctx.fail_at(remaining);
+    }
+    result
+}
+
+fn decode_value_in<'b>(
+    typ: &SchemaType,
+    bytes: &mut &[u8],
+    bump: &'b Bump,
+    ctx: &mut DecodeContext,
+    tolerate_eof: bool,
+) -> anyhow::Result<ArenaValue<'b>> {
+    Ok(match typ {
+        SchemaType::Empty
+        | SchemaType::I8
+        | SchemaType::U8
+        | SchemaType::I16
+        | SchemaType::U16
+        | SchemaType::I32
+        | SchemaType::U32
+        | SchemaType::I64
+        | SchemaType::U64
+        | SchemaType::I128
+        | SchemaType::U128
+        | SchemaType::F32
+        | SchemaType::F64
+        | SchemaType::Bool
+        | SchemaType::UnixTimestamp
+        | SchemaType::Duration => ArenaValue::Scalar(typ.decode(bytes, ctx)?),
+        SchemaType::Pubkey => ArenaValue::Pubkey(Pubkey::new_from_array(
+            <[u8; 32]>::deserialize_reader(&mut *bytes)?,
+        )),
+        SchemaType::String => {
+            let len = u32::deserialize_reader(&mut *bytes)? as usize;
+            let text = std::str::from_utf8(take(bytes, len, "String")?)?;
+            ArenaValue::String(bump.alloc_str(text))
+        }
+        SchemaType::Option(t) => match u8::deserialize_reader(&mut *bytes)? {
+            0 => ArenaValue::Option(None),
+            1 => ArenaValue::Option(Some(bump.alloc(decode_in(t, bytes, bump, ctx)?))),
+            tag => return Err(anyhow::anyhow!("Invalid Option tag: {}", tag)),
+        },
+        SchemaType::LenientOption(t) => match u8::deserialize_reader(&mut *bytes)? {
+            1 => ArenaValue::Option(Some(bump.alloc(decode_in(t, bytes, bump, ctx)?))),
+            _ => ArenaValue::Option(None),
+        },
+        SchemaType::FlaggedOption { flag_width, inner } => match flag_width.read(bytes)? {
+            0 => ArenaValue::Option(None),
+            _ => ArenaValue::Option(Some(bump.alloc(decode_in(inner, bytes, bump, ctx)?))),
+        },
+        SchemaType::Array(len, t) if matches!(**t, SchemaType::U8) => {
+            ArenaValue::Bytes(bump.alloc_slice_copy(take(bytes, *len, "[u8; N]")?))
+        }
+        SchemaType::Array(len, t) => {
+            ArenaValue::Array(decode_elements_in(t, *len, bytes, bump, ctx)?)
+        }
+        SchemaType::Bitmap(bits) => {
+            let raw = take(bytes, bits.div_ceil(8), "bitmap")?;
+            ArenaValue::Vec(bump.alloc_slice_fill_iter((0..*bits).map(|bit| {
+                ArenaValue::Scalar(TypedValue::Bool((raw[bit / 8] >> (bit % 8)) & 1 == 1))
+            })))
+        }
+        SchemaType::Vec(t) => {
+            let len = u32::deserialize_reader(&mut *bytes)? as usize;
+            decode_list_in(t, len, bytes, bump, ctx)?
+        }
+        SchemaType::SmallVec(len_ty, t) => {
+            let len = len_ty.read(bytes)?;
+            decode_list_in(t, len, bytes, bump, ctx)?
+        }
+        SchemaType::RemainingBytes => {
+            let rest = std::mem::take(bytes);
+            ArenaValue::Bytes(bump.alloc_slice_copy(rest))
+        }
+        SchemaType::RemainingVec(t) => {
+            let mut values = BumpVec::new_in(bump);
+            while !bytes.is_empty() {
+                ctx.count_elements(1)?;
+                let remaining = bytes.len();
+                values.push(decode_in(t, bytes, bump, ctx)?);
+                if bytes.len() == remaining {
+                    return Err(anyhow::anyhow!(
+                        "Zero-sized element can't fill the remaining {} bytes",
+                        remaining
+                    ));
+                }
+            }
+            ArenaValue::Vec(values.into_bump_slice())
+        }
+        SchemaType::Tuple(types) => {
+            let mut values = BumpVec::with_capacity_in(types.len(), bump);
+            for t in types {
+                values.push(decode_in(t, bytes, bump, ctx)?);
+            }
+            ArenaValue::Tuple(values.into_bump_slice())
+        }
+        SchemaType::Struct(fields) => {
+            ArenaValue::Struct(decode_fields_in(fields, bytes, bump, ctx, tolerate_eof)?)
+        }
+        SchemaType::Enum(variants) => decode_enum_in(variants, ctx.enum_width(), bytes, bump, ctx)?,
+        SchemaType::TaggedEnum(width, variants) => {
+            decode_enum_in(variants, *width, bytes, bump, ctx)?
+        }
+        SchemaType::CountedArray { .. }
+        | SchemaType::Tlv(_)
+        | SchemaType::Map(_, _)
+        | SchemaType::Utf8Remaining
+        | SchemaType::RawString => {
+            return Err(anyhow::anyhow!(
+                "{} isn't supported by arena decoding",
+                typ.typename()
+            ))
+        }
+    })
+}
+
+/// The next `len` bytes
+fn take<'a>(bytes: &mut &'a [u8], len: usize, what: &str) -> anyhow::Result<&'a [u8]> {
+    if bytes.len() < len {
+        return Err(anyhow::anyhow!(
+            "Not enough bytes for {}: need {}, have {}",
+            what,
+            len,
+            bytes.len()
+        ));
+    }
+    let (raw, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Ok(raw)
+}
+
+/// A length-prefixed list, read as bytes when its elements are `u8`
+fn decode_list_in<'b>(
+    t: &SchemaType,
+    len: usize,
+    bytes: &mut &[u8],
+    bump: &'b Bump,
+    ctx: &mut DecodeContext,
+) -> anyhow::Result<ArenaValue<'b>> {
+    Ok(if matches!(t, SchemaType::U8) {
+        ArenaValue::Bytes(bump.alloc_slice_copy(take(bytes, len, "Vec<u8>")?))
+    } else {
+        ArenaValue::Vec(decode_elements_in(t, len, bytes, bump, ctx)?)
+    })
+}
+
+fn decode_elements_in<'b>(
+    t: &SchemaType,
+    len: usize,
+    bytes: &mut &[u8],
+    bump: &'b Bump,
+    ctx: &mut DecodeContext,
+) -> anyhow::Result<&'b [ArenaValue<'b>]> {
+    ctx.count_elements(len)?;
+    let mut values = BumpVec::with_capacity_in(len.min(bytes.len()), bump);
+    for _ in 0..len {
+        values.push(decode_in(t, bytes, bump, ctx)?);
+    }
+    Ok(values.into_bump_slice())
+}
+
+/// Reads fields in serialization order, returning them in declaration order.
+/// With `tolerate_eof`, running out of bytes between fields ends the struct.
+fn decode_fields_in<'b>(
+    fields: &[SchemaNode],
+    bytes: &mut &[u8],
+    bump: &'b Bump,
+    ctx: &mut DecodeContext,
+    tolerate_eof: bool,
+) -> anyhow::Result<&'b [ArenaNode<'b>]> {
+    let mut slots = BumpVec::with_capacity_in(fields.len(), bump);
+    slots.extend(fields.iter().map(|_| None));
+    for index in serialization_order(fields) {
+        if bytes.is_empty() && tolerate_eof {
+            break;
+        }
+        let field = &fields[index];
+        let value = decode_in(&field.typ, bytes, bump, ctx)?;
+        if !field.is_hidden || ctx.options.show_hidden {
+            slots[index] = Some(ArenaNode {
+                name: bump.alloc_str(&field.name),
+                value,
+            });
+        }
+    }
+    let mut values = BumpVec::with_capacity_in(fields.len(), bump);
+    values.extend(slots.into_iter().flatten());
+    Ok(values.into_bump_slice())
+}
+
+fn decode_enum_in<'b>(
+    variants: &[SchemaNode],
+    width: IntWidth,
+    bytes: &mut &[u8],
+    bump: &'b Bump,
+    ctx: &mut DecodeContext,
+) -> anyhow::Result<ArenaValue<'b>> {
+    let discriminant = width.read(bytes)?;
+    let variant = usize::try_from(discriminant)
+        .ok()
+        .and_then(|index| variants.get(index))
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "enum discriminant {} out of bounds (variants: {})",
+                discriminant,
+                variants.len()
+            )
+        })?;
+    let value = decode_in(&variant.typ, bytes, bump, ctx)?;
+    if variant.is_hidden && !ctx.options.show_hidden {
+        return Err(anyhow::anyhow!("is_hidden shouldn't appear in Enum types"));
+    }
+    Ok(ArenaValue::Enum(bump.alloc(ArenaNode {
+        name: bump.alloc_str(&variant.name),
+        value,
+    })))
+}
+
+impl ArenaValue<'_> {
+    /// The heap-allocated equivalent, as `deserialize_bytes` would return it
+    pub fn to_typed_value(&self) -> TypedValue {
+        let all = |values: &[ArenaValue]| values.iter().map(|v| v.to_typed_value()).collect();
+        match self {
+            ArenaValue::Scalar(v) => v.clone(),
+            ArenaValue::Pubkey(v) => TypedValue::Pubkey(v.to_string()),
+            ArenaValue::String(v) => TypedValue::String(v.to_string()),
+            ArenaValue::Bytes(v) => TypedValue::Bytes(v.to_vec()),
+            ArenaValue::Option(v) => TypedValue::Option(Box::new(v.map(|v| v.to_typed_value()))),
+            ArenaValue::Array(v) => TypedValue::Array(all(v)),
+            ArenaValue::Tuple(v) => TypedValue::Tuple(all(v)),
+            ArenaValue::Vec(v) => TypedValue::Vec(all(v)),
+            ArenaValue::Struct(v) => {
+                TypedValue::Struct(v.iter().map(|node| node.to_value_node()).collect())
+            }
+            ArenaValue::Enum(v) => TypedValue::Enum(Box::new(v.to_value_node())),
+        }
+    }
+}
+
+impl ArenaNode<'_> {
+    pub fn to_value_node(&self) -> ValueNode {
+        ValueNode::new(self.name, self.value.to_typed_value())
+    }
+}
+
+impl Serialize for ArenaValue<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            ArenaValue::Scalar(v) => v.serialize(serializer),
+            ArenaValue::Pubkey(v) => serializer.collect_str(v),
+            ArenaValue::String(v) => v.serialize(serializer),
+            ArenaValue::Bytes(v) => v.serialize(serializer),
+            ArenaValue::Option(Some(ArenaValue::Scalar(TypedValue::Empty))) => {
+                serializer.serialize_bool(true)
+            }
+            ArenaValue::Option(v) => v.serialize(serializer),
+            ArenaValue::Array(v) | ArenaValue::Tuple(v) | ArenaValue::Vec(v) => {
+                v.serialize(serializer)
+            }
+            ArenaValue::Struct(fields) => {
+                let mut state = serializer.serialize_map(Some(fields.len()))?;
+                for field in fields.iter() {
+                    state.serialize_entry(field.name, &field.value)?;
+                }
+                state.end()
+            }
+            ArenaValue::Enum(v) if matches!(v.value, ArenaValue::Scalar(TypedValue::Empty)) => {
+                v.name.serialize(serializer)
+            }
+            ArenaValue::Enum(v) => v.serialize(serializer),
+        }
+    }
+}
+
+impl Serialize for ArenaNode<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_map(Some(2))?;
+        state.serialize_entry("name", self.name)?;
+        state.serialize_entry("value", &self.value)?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bumpalo::Bump;
+    use solana_program::pubkey::Pubkey;
+
+    use crate::schema::{DecodeOptions, IntWidth, SchemaNode, SchemaType, SmallVecLen};
+
+    #[test]
+    fn arena_decode_matches_heap_decode() {
+        let order = SchemaNode::new_struct(
+            "Order",
+            vec![
+                ("owner", SchemaType::Pubkey),
+                ("price", SchemaType::U64),
+                ("size", SchemaType::I32),
+                (
+                    "side",
+                    SchemaType::Enum(vec![
+                        SchemaNode::new("Bid", SchemaType::Empty),
+                        SchemaNode::new_struct("Ask", vec![("limit", SchemaType::F64)]),
+                    ]),
+                ),
+            ],
+        );
+        let mut padding = SchemaNode::new("padding", SchemaType::array(3, SchemaType::U8));
+        padding.is_hidden = true;
+        let SchemaType::Struct(mut fields) = SchemaNode::new_struct(
+            "Book",
+            vec![
+                ("label", SchemaType::String),
+                ("orders", SchemaType::vec(order.typ)),
+                ("memo", SchemaType::option(SchemaType::vec(SchemaType::U8))),
+                (
+                    "ticks",
+                    SchemaType::SmallVec(SmallVecLen::U8, Box::new(SchemaType::U16)),
+                ),
+                (
+                    "state",
+                    SchemaType::TaggedEnum(
+                        IntWidth::U32,
+                        vec![
+                            SchemaNode::new("Open", SchemaType::Empty),
+                            SchemaNode::new("Closed", SchemaType::UnixTimestamp),
+                        ],
+                    ),
+                ),
+                (
+                    "pair",
+                    SchemaType::Tuple(vec![SchemaType::Bool, SchemaType::U128]),
+                ),
+            ],
+        )
+        .typ
+        else {
+            unreachable!()
+        };
+        fields.insert(1, padding);
+        let book = SchemaNode::new("Book", SchemaType::Struct(fields));
+
+        let owner = Pubkey::new_unique();
+        let mut data = 4u32.to_le_bytes().to_vec();
+        data.extend_from_slice(b"SOL1");
+        data.extend_from_slice(&[0xaa; 3]);
+        data.extend_from_slice(&2u32.to_le_bytes());
+        for (price, ask) in [(101u64, false), (99, true)] {
+            data.extend_from_slice(owner.as_ref());
+            data.extend_from_slice(&price.to_le_bytes());
+            data.extend_from_slice(&(-5i32).to_le_bytes());
+            data.push(ask as u8);
+            if ask {
+                data.extend_from_slice(&1.5f64.to_le_bytes());
+            }
+        }
+        data.extend_from_slice(&[1, 2, 0, 0, 0, 7, 8]);
+        data.extend_from_slice(&[2, 1, 0, 2, 0]);
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&1_700_000_000i64.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(&u128::MAX.to_le_bytes());
+
+        let mut bump = Bump::new();
+        for show_hidden in [false, true] {
+            let options = DecodeOptions::new(show_hidden);
+            let heap = book
+                .deserialize_bytes_with_options(&mut data.as_slice(), &options)
+                .unwrap()
+                .unwrap();
+            let mut buf = data.as_slice();
+            let arena = book
+                .deserialize_bytes_in(&mut buf, &bump, &options)
+                .unwrap()
+                .unwrap();
+
+            assert!(buf.is_empty());
+            assert_eq!(arena.to_value_node(), heap);
+            assert_eq!(
+                serde_json::to_value(&arena).unwrap(),
+                serde_json::to_value(&heap).unwrap()
+            );
+            bump.reset();
+        }
+    }
+
+    #[test]
+    fn only_the_outermost_struct_tolerates_missing_trailing_fields() {
+        let options = DecodeOptions {
+            tolerate_missing_trailing_fields: true,
+            ..Default::default()
+        };
+        let point = SchemaType::Struct(vec![
+            SchemaNode::new("x", SchemaType::U64),
+            SchemaNode::new("y", SchemaType::U64),
+        ]);
+        let bump = Bump::new();
+
+        let account = SchemaNode::new_struct(
+            "Account",
+            vec![("count", SchemaType::U64), ("p", point.clone())],
+        );
+        let data = 3u64.to_le_bytes();
+        let value = account
+            .deserialize_bytes_in(&mut &data[..], &bump, &options)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&value.value).unwrap(),
+            serde_json::json!({ "count": "3" })
+        );
+
+        let nested = SchemaNode::new_struct("Account", vec![("p", point.clone())]);
+        let data = 1u64.to_le_bytes();
+        assert!(nested
+            .deserialize_bytes_in(&mut &data[..], &bump, &options)
+            .is_err());
+
+        let points = SchemaNode::new_struct("Account", vec![("ps", SchemaType::vec(point))]);
+        let mut data = 3u32.to_le_bytes().to_vec();
+        data.extend_from_slice(&[1; 16]);
+        assert!(points
+            .deserialize_bytes_in(&mut data.as_slice(), &bump, &options)
+            .is_err());
+    }
+}
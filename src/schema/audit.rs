@@ -0,0 +1,195 @@
+//! Decoding that also records which bytes every value was read from, so
+//! auditors can check the decoder consumed exactly the right ones.
+
+use std::sync::Arc;
+
+use crate::{
+    schema::{bytes_deserialize::DecodeContext, DecodeOptions, SchemaNode},
+    value::ValueNode,
+};
+
+/// The bytes a decoded value was read from, with one child per nested
+/// value in the order they were read. Bytes a value read itself rather than
+/// through a child (length prefixes, option tags, enum discriminants) are
+/// unnamed leaves, so a node's leaves always add up to its `bytes`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditTree {
+    /// the struct field or enum variant, if the value is one
+    pub name: Option<Arc<str>>,
+    /// offset of `bytes` in the decoded buffer
+    pub offset: usize,
+    pub bytes: Vec<u8>,
+    pub children: Vec<AuditTree>,
+}
+
+impl AuditTree {
+    fn new(offset: usize, bytes: &[u8], children: Vec<AuditTree>) -> Self {
+        let mut filled = Vec::with_capacity(children.len());
+        let mut end = offset;
+        for child in children {
+            if child.offset > end {
+                filled.push(Self::leaf(end, &bytes[end - offset..child.offset - offset]));
+            }
+            end = child.offset + child.bytes.len();
+            filled.push(child);
+        }
+        if !filled.is_empty() && end < offset + bytes.len() {
+            filled.push(Self::leaf(end, &bytes[end - offset..]));
+        }
+        Self {
+            name: None,
+            offset,
+            bytes: bytes.to_vec(),
+            children: filled,
+        }
+    }
+
+    fn leaf(offset: usize, bytes: &[u8]) -> Self {
+        Self {
+            name: None,
+            offset,
+            bytes: bytes.to_vec(),
+            children: Vec::new(),
+        }
+    }
+
+    /// Nodes without children, in buffer order
+    pub fn leaves(&self) -> Vec<&AuditTree> {
+        if self.children.is_empty() {
+            return vec![self];
+        }
+        self.children.iter().flat_map(AuditTree::leaves).collect()
+    }
+}
+
+/// Audit nodes of the values being decoded, innermost last
+pub(crate) type AuditFrames = Vec<Vec<AuditTree>>;
+
+impl DecodeContext<'_> {
+    /// Runs `decode`, recording the bytes it consumed as a node under the
+    /// value being decoded when auditing
+    pub(crate) fn audited<'d, T>(
+        &mut self,
+        bytes: &mut &'d [u8],
+        decode: impl FnOnce(&mut &'d [u8], &mut Self) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        let Some(frames) = self.audit.as_mut() else {
+            return decode(bytes, self);
+        };
+        frames.push(Vec::new());
+        let start = *bytes;
+        let offset = self.offset(start.len());
+        let result = decode(bytes, self);
+
+        let frames = self
+            .audit
+            .as_mut()
+            .expect("audit frames live for the decode");
+        let children = frames.pop().unwrap_or_default();
+        if result.is_ok() {
+            let consumed = &start[..start.len() - bytes.len()];
+            let node = AuditTree::new(offset, consumed, children);
+            match frames.last_mut() {
+                Some(parent) => parent.push(node),
+                None => frames.push(vec![node]),
+            }
+        }
+        result
+    }
+
+    /// Names the node just recorded after the field or variant it decoded
+    pub(crate) fn name_audited(&mut self, name: &Arc<str>) {
+        if let Some(node) = self
+            .audit
+            .as_mut()
+            .and_then(|frames| frames.last_mut())
+            .and_then(|nodes| nodes.last_mut())
+        {
+            node.name = Some(name.clone());
+        }
+    }
+}
+
+impl SchemaNode {
+    /// Decodes like `deserialize_bytes_with_options`, alongside an
+    /// `AuditTree` of the bytes each value came from. Hidden fields are
+    /// left out of the value but not the tree, since their bytes were read.
+    pub fn deserialize_bytes_audited(
+        &self,
+        bytes: &mut &[u8],
+        options: &DecodeOptions,
+    ) -> anyhow::Result<(ValueNode, AuditTree)> {
+        let mut ctx = DecodeContext::new(options, bytes);
+        ctx.audit = Some(Vec::new());
+        let result = self.decode(bytes, &mut ctx);
+        let value = ctx
+            .finish(result)?
+            .ok_or_else(|| anyhow::anyhow!("{} is hidden", self.name))?;
+        let audit = ctx
+            .audit
+            .take()
+            .and_then(|mut frames| frames.pop())
+            .and_then(|mut nodes| nodes.pop())
+            .ok_or_else(|| anyhow::anyhow!("No audit recorded for {}", self.name))?;
+        Ok((value, audit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::schema::{DecodeOptions, SchemaNode, SchemaType};
+
+    #[test]
+    fn audit_leaves_reconstruct_the_buffer() {
+        let side = SchemaType::Enum(vec![
+            SchemaNode::new("Bid", SchemaType::Empty),
+            SchemaNode::new("Ask", SchemaType::Empty),
+        ]);
+        let schema = SchemaNode::new_struct(
+            "Order",
+            vec![
+                ("owner", SchemaType::Pubkey),
+                ("side", side),
+                ("prices", SchemaType::vec(SchemaType::U64)),
+                ("memo", SchemaType::option(SchemaType::String)),
+                ("seed", SchemaType::array(4, SchemaType::U8)),
+            ],
+        );
+        let mut data = [7u8; 32].to_vec();
+        data.push(0);
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&10u64.to_le_bytes());
+        data.extend_from_slice(&11u64.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(b"hi");
+        data.extend_from_slice(&[1, 2, 3, 4]);
+
+        let (value, audit) = schema
+            .deserialize_bytes_audited(&mut data.as_slice(), &DecodeOptions::default())
+            .unwrap();
+        assert_eq!(
+            value,
+            schema
+                .deserialize_bytes(&mut data.as_slice(), false)
+                .unwrap()
+                .unwrap()
+        );
+
+        assert_eq!(audit.bytes, data);
+        let reconstructed: Vec<u8> = audit
+            .leaves()
+            .iter()
+            .flat_map(|leaf| leaf.bytes.iter().copied())
+            .collect();
+        assert_eq!(reconstructed, data);
+
+        let prices = &audit.children[2];
+        assert_eq!(prices.name.as_deref(), Some("prices"));
+        assert_eq!(prices.offset, 33);
+        // length prefix, then one node per element
+        assert_eq!(prices.children.len(), 3);
+        assert_eq!(prices.children[0].bytes, 2u32.to_le_bytes());
+        assert_eq!(prices.children[2].bytes, 11u64.to_le_bytes());
+    }
+}
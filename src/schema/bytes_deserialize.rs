@@ -1,21 +1,268 @@
+use std::{collections::BTreeMap, sync::Arc};
+
 use crate::{
-    schema::{SchemaNode, SchemaType, SmallVecLen},
+    schema::{audit::AuditFrames, IntWidth, SchemaNode, SchemaType, SmallVecLen},
     value::{TypedValue, ValueNode},
 };
 use borsh::BorshDeserialize;
 use solana_program::pubkey::Pubkey;
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DecodeOptions {
+    pub show_hidden: bool,
+    /// Caps the cumulative number of elements decoded across all (nested)
+    /// collections. Byte blobs (`[u8; N]`, `Vec<u8>`, ...) are read in one
+    /// go and don't count towards it.
+    pub max_total_elements: usize,
+    /// Data written before an IDL upgrade added trailing fields: when the
+    /// buffer runs out exactly at a field boundary of the outermost struct,
+    /// its remaining fields are omitted instead of failing the decode.
+    /// Running out anywhere else, nested structs and collections included,
+    /// is still an error.
+    pub tolerate_missing_trailing_fields: bool,
+    /// Give decoded `ValueNode`s the schema's own field names instead of a
+    /// fresh allocation each, so every decode against the same schema
+    /// shares them; for large batches
+    pub intern_names: bool,
+    /// Append the offset into the input buffer of the value that failed to
+    /// decode to error messages, e.g. `... (at byte offset 40)`
+    pub error_offsets: bool,
+    /// Fail a top-level decode (and so account, instruction and event
+    /// parsing) that leaves bytes unread, which usually means the schema is
+    /// missing trailing fields
+    pub reject_trailing_bytes: bool,
+    /// Width of the discriminant of enums that don't declare one
+    /// (`TaggedEnum`s keep theirs). `None` reads Borsh's `u8`, or the IDL's
+    /// `enum_disc_width` when decoding through an `OnChainIdl`.
+    pub enum_disc_width: Option<IntWidth>,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self {
+            show_hidden: false,
+            max_total_elements: usize::MAX,
+            tolerate_missing_trailing_fields: false,
+            intern_names: false,
+            error_offsets: false,
+            reject_trailing_bytes: false,
+            enum_disc_width: None,
+        }
+    }
+}
+
+impl DecodeOptions {
+    pub fn new(show_hidden: bool) -> Self {
+        Self {
+            show_hidden,
+            ..Default::default()
+        }
+    }
+}
+
+/// State shared across one decode
+pub(crate) struct DecodeContext<'a> {
+    pub(crate) options: &'a DecodeOptions,
+    total_elements: usize,
+    /// fields a failing struct decoded before its error
+    partial: Option<TypedValue>,
+    /// offset in the input buffer at which the slice being decoded ends, so
+    /// the current offset is this minus the bytes remaining
+    end_offset: usize,
+    /// offset of the innermost value that failed to decode
+    error_offset: Option<usize>,
+    /// bytes consumed by each value, for `deserialize_bytes_audited`
+    pub(crate) audit: Option<AuditFrames>,
+    /// nothing has been decoded yet, so the next value is the outermost one
+    top_level: bool,
+}
+
+impl<'a> DecodeContext<'a> {
+    pub(crate) fn new(options: &'a DecodeOptions, input: &[u8]) -> Self {
+        Self {
+            options,
+            total_elements: 0,
+            partial: None,
+            end_offset: input.len(),
+            error_offset: None,
+            audit: None,
+            top_level: true,
+        }
+    }
+
+    /// Whether the struct about to be decoded may stop at the end of the
+    /// buffer: with `tolerate_missing_trailing_fields`, only the outermost
+    /// value's own fields may be missing. Call once per value.
+    pub(crate) fn take_trailing_tolerance(&mut self) -> bool {
+        std::mem::take(&mut self.top_level) && self.options.tolerate_missing_trailing_fields
+    }
+
+    /// Discriminant width of untagged `Enum`s
+    pub(crate) fn enum_width(&self) -> IntWidth {
+        self.options.enum_disc_width.unwrap_or(IntWidth::U8)
+    }
+
+    /// Offset in the input buffer when `remaining` bytes are left to read
+    pub(crate) fn offset(&self, remaining: usize) -> usize {
+        self.end_offset.saturating_sub(remaining)
+    }
+
+    /// Records where a value that had `remaining` bytes left to read from
+    /// failed, unless a value nested in it already did
+    pub(crate) fn fail_at(&mut self, remaining: usize) {
+        if self.error_offset.is_none() {
+            self.error_offset = Some(self.offset(remaining));
+        }
+    }
+
+    /// Adds the failure offset to the error of a whole decode, with
+    /// `error_offsets`
+    pub(crate) fn finish<T>(&self, result: anyhow::Result<T>) -> anyhow::Result<T> {
+        match (result, self.error_offset) {
+            (Err(e), Some(offset)) if self.options.error_offsets => {
+                Err(anyhow::anyhow!("{:#} (at byte offset {})", e, offset))
+            }
+            (result, _) => result,
+        }
+    }
+
+    /// Name for a node decoded from a schema node called `name`: the
+    /// schema's own allocation when interning, else a copy
+    pub(crate) fn name(&self, name: &Arc<str>) -> Arc<str> {
+        if self.options.intern_names {
+            name.clone()
+        } else {
+            Arc::from(&**name)
+        }
+    }
+
+    pub(crate) fn count_elements(&mut self, count: usize) -> anyhow::Result<()> {
+        self.total_elements = self.total_elements.saturating_add(count);
+        if self.total_elements > self.options.max_total_elements {
+            return Err(anyhow::anyhow!(
+                "Decoded element budget exceeded: {} > {}",
+                self.total_elements,
+                self.options.max_total_elements
+            ));
+        }
+        Ok(())
+    }
+}
+
 impl SchemaNode {
     pub fn deserialize_bytes(
         &self,
         bytes: &mut &[u8],
         show_hidden: bool,
     ) -> anyhow::Result<Option<ValueNode>> {
-        let value = self.typ.deserialize_bytes(&mut *bytes, show_hidden)?;
-        if self.is_hidden && !show_hidden {
+        self.deserialize_bytes_with_options(bytes, &DecodeOptions::new(show_hidden))
+    }
+
+    pub fn deserialize_bytes_with_options(
+        &self,
+        bytes: &mut &[u8],
+        options: &DecodeOptions,
+    ) -> anyhow::Result<Option<ValueNode>> {
+        let mut ctx = DecodeContext::new(options, bytes);
+        let result = self.decode(bytes, &mut ctx);
+        let value = ctx.finish(result)?;
+        if options.reject_trailing_bytes && !bytes.is_empty() {
+            return Err(anyhow::anyhow!(
+                "{} bytes left over after decoding {}",
+                bytes.len(),
+                self.name
+            ));
+        }
+        Ok(value)
+    }
+
+    /// Like `deserialize_bytes_with_options`, but when a struct fails midway
+    /// the fields decoded before the error are returned alongside it.
+    /// Partial values are kept through nested structs, not through
+    /// collections, options or enums.
+    pub fn deserialize_bytes_partial(
+        &self,
+        bytes: &mut &[u8],
+        options: &DecodeOptions,
+    ) -> (Option<ValueNode>, Option<anyhow::Error>) {
+        let mut ctx = DecodeContext::new(options, bytes);
+        let result = self.decode(bytes, &mut ctx);
+        match ctx.finish(result) {
+            Ok(value) => (value, None),
+            Err(e) => {
+                let partial = match self.typ {
+                    SchemaType::Struct(_) => ctx.partial.take(),
+                    _ => None,
+                };
+                (
+                    partial.map(|value| ValueNode::new(ctx.name(&self.name), value)),
+                    Some(e),
+                )
+            }
+        }
+    }
+
+    /// Decodes only the field at the dotted `path` (e.g. `"marketSizeParams.numSeats"`).
+    /// Preceding fields are skipped using their `fixed_size` where possible and
+    /// decoded then discarded otherwise.
+    pub fn deserialize_field(&self, bytes: &[u8], path: &str) -> anyhow::Result<TypedValue> {
+        let options = DecodeOptions::new(true);
+        let mut ctx = DecodeContext::new(&options, bytes);
+        let mut bytes = bytes;
+        let mut typ = &self.typ;
+        let mut segments = path.split('.').peekable();
+
+        while let Some(segment) = segments.next() {
+            let SchemaType::Struct(fields) = typ else {
+                return Err(anyhow::anyhow!(
+                    "Cannot select {} in {}: not a struct",
+                    segment,
+                    path
+                ));
+            };
+            let mut found = None;
+            for field in serialization_order(fields).map(|index| &fields[index]) {
+                if *field.name == *segment {
+                    found = Some(field);
+                    break;
+                }
+                match field.typ.fixed_size_with(ctx.enum_width()) {
+                    Some(size) if bytes.len() >= size => bytes = &bytes[size..],
+                    Some(size) => {
+                        return Err(anyhow::anyhow!(
+                            "Not enough bytes to skip {}: need {}, have {}",
+                            field.name,
+                            size,
+                            bytes.len()
+                        ))
+                    }
+                    None => {
+                        field.typ.decode(&mut bytes, &mut ctx)?;
+                    }
+                }
+            }
+            let field = found.ok_or_else(|| anyhow::anyhow!("Field {} not found", path))?;
+            if segments.peek().is_none() {
+                return field.typ.decode(&mut bytes, &mut ctx);
+            }
+            typ = &field.typ;
+        }
+
+        Err(anyhow::anyhow!("Empty field path"))
+    }
+
+    pub(crate) fn decode(
+        &self,
+        bytes: &mut &[u8],
+        ctx: &mut DecodeContext,
+    ) -> anyhow::Result<Option<ValueNode>> {
+        let value = self.typ.decode(&mut *bytes, ctx)?;
+        let name = ctx.name(&self.name);
+        ctx.name_audited(&name);
+        if self.is_hidden && !ctx.options.show_hidden {
             Ok(None)
         } else {
-            Ok(Some(ValueNode::new(self.name.clone(), value)))
+            Ok(Some(ValueNode::new(name, value)))
         }
     }
 }
@@ -26,6 +273,50 @@ impl SchemaType {
         &self,
         bytes: &mut &[u8],
         show_hidden: bool,
+    ) -> anyhow::Result<TypedValue> {
+        self.deserialize_bytes_with_options(bytes, &DecodeOptions::new(show_hidden))
+    }
+
+    pub fn deserialize_bytes_with_options(
+        &self,
+        bytes: &mut &[u8],
+        options: &DecodeOptions,
+    ) -> anyhow::Result<TypedValue> {
+        let mut ctx = DecodeContext::new(options, bytes);
+        let result = self.decode(bytes, &mut ctx);
+        ctx.finish(result)
+    }
+
+    /// Decodes one value from the start of `bytes`, hidden fields included,
+    /// returning it with the number of bytes it took up. Handy for poking at
+    /// raw data without building a `SchemaNode` or `OnChainIdl`.
+    pub fn decode_once(&self, bytes: &[u8]) -> anyhow::Result<(TypedValue, usize)> {
+        let mut rest = bytes;
+        let value = self.deserialize_bytes(&mut rest, true)?;
+        Ok((value, bytes.len() - rest.len()))
+    }
+
+    pub(crate) fn decode(
+        &self,
+        bytes: &mut &[u8],
+        ctx: &mut DecodeContext,
+    ) -> anyhow::Result<TypedValue> {
+        let remaining = bytes.len();
+        let tolerate_eof = ctx.take_trailing_tolerance();
+        let result = ctx.audited(bytes, |bytes, ctx| {
+            self.decode_value(bytes, ctx, tolerate_eof)
+        });
+        if result.is_err() {
+            ctx.fail_at(remaining);
+        }
+        result
+    }
+
+    fn decode_value(
+        &self,
+        bytes: &mut &[u8],
+        ctx: &mut DecodeContext,
+        tolerate_eof: bool,
     ) -> anyhow::Result<TypedValue> {
         let value = match self {
             SchemaType::Empty => TypedValue::Empty,
@@ -46,39 +337,63 @@ impl SchemaType {
             SchemaType::F32 => TypedValue::F32(f32::deserialize_reader(&mut *bytes)?),
             SchemaType::F64 => TypedValue::F64(f64::deserialize_reader(&mut *bytes)?),
             SchemaType::Bool => TypedValue::Bool(bool::deserialize_reader(&mut *bytes)?),
+            SchemaType::UnixTimestamp => {
+                TypedValue::UnixTimestamp(i64::deserialize_reader(&mut *bytes)?)
+            }
+            SchemaType::Duration => TypedValue::Duration(i64::deserialize_reader(&mut *bytes)?),
             SchemaType::Option(t) => TypedValue::Option(Box::new({
                 // Option discriminant is 1 byte (u8), 0 => None, 1 => Some
+                match u8::deserialize_reader(&mut *bytes)? {
+                    0 => None,
+                    1 => Some(t.decode(&mut *bytes, ctx)?),
+                    tag => return Err(anyhow::anyhow!("Invalid Option tag: {}", tag)),
+                }
+            })),
+            SchemaType::LenientOption(t) => TypedValue::Option(Box::new({
                 let is_some = u8::deserialize_reader(&mut *bytes)?;
                 if is_some == 1 {
-                    Some(t.deserialize_bytes(&mut *bytes, show_hidden)?)
+                    Some(t.decode(&mut *bytes, ctx)?)
                 } else {
                     None
                 }
             })),
-            SchemaType::Array(size, t) => {
-                if matches!(**t, SchemaType::U8) {
-                    if bytes.len() < *size {
-                        return Err(anyhow::anyhow!(
-                            "Not enough bytes for [u8; {}]: have {}",
-                            size,
-                            bytes.len()
-                        ));
-                    }
-                    let (raw, rest) = bytes.split_at(*size);
-                    *bytes = rest;
-                    TypedValue::Bytes(raw.to_vec())
+            SchemaType::FlaggedOption { flag_width, inner } => TypedValue::Option(Box::new({
+                let flag = flag_width.read(bytes)?;
+                if flag != 0 {
+                    Some(inner.decode(&mut *bytes, ctx)?)
                 } else {
-                    let mut values = Vec::with_capacity(*size);
-                    for _ in 0..*size {
-                        values.push(t.deserialize_bytes(&mut *bytes, show_hidden)?);
-                    }
-                    TypedValue::Array(values)
+                    None
                 }
+            })),
+            SchemaType::Array(size, t) => decode_array(t, *size, bytes, ctx)?,
+            SchemaType::Bitmap(bits) => {
+                let len = bits.div_ceil(8);
+                if bytes.len() < len {
+                    return Err(anyhow::anyhow!(
+                        "Not enough bytes for a {}-bit bitmap: need {}, have {}",
+                        bits,
+                        len,
+                        bytes.len()
+                    ));
+                }
+                let (raw, rest) = bytes.split_at(len);
+                *bytes = rest;
+                TypedValue::Vec(
+                    (0..*bits)
+                        .map(|bit| TypedValue::Bool((raw[bit / 8] >> (bit % 8)) & 1 == 1))
+                        .collect(),
+                )
+            }
+            SchemaType::CountedArray { count_from, .. } => {
+                return Err(anyhow::anyhow!(
+                    "CountedArray counted by {} must be a struct field",
+                    count_from
+                ))
             }
             SchemaType::Tuple(t) => TypedValue::Tuple({
                 let mut values = Vec::with_capacity(t.len());
                 for t in t {
-                    values.push(t.deserialize_bytes(&mut *bytes, show_hidden)?);
+                    values.push(t.decode(&mut *bytes, ctx)?);
                 }
                 values
             }),
@@ -104,43 +419,23 @@ impl SchemaType {
                 } else {
                     // Generic Vec<T> path (unchanged)
                     let size = u32::deserialize_reader(&mut *bytes)?;
-                    let mut values = Vec::with_capacity(size as usize);
+                    ctx.count_elements(size as usize)?;
+                    let mut values = Vec::with_capacity((size as usize).min(bytes.len()));
                     for _ in 0..size {
-                        values.push(t.deserialize_bytes(&mut *bytes, show_hidden)?);
+                        values.push(t.decode(&mut *bytes, ctx)?);
                     }
                     TypedValue::Vec(values)
                 }
             }
-            SchemaType::Struct(t) => TypedValue::Struct({
-                let mut values = Vec::with_capacity(t.len());
-                for t in t {
-                    if let Some(val) = t.deserialize_bytes(&mut *bytes, show_hidden)? {
-                        values.push(val);
-                    }
-                }
-                values
-            }),
-            SchemaType::Enum(t) => TypedValue::Enum({
-                // Enum discriminant is 1 byte (u8)
-                let discriminant = u8::deserialize_reader(&mut *bytes)?;
-                let variant = t.get(discriminant as usize).ok_or_else(|| {
-                    anyhow::anyhow!(
-                        "enum discriminant {} out of bounds (variants: {})",
-                        discriminant,
-                        t.len()
-                    )
-                })?;
-                let value = variant
-                    .deserialize_bytes(&mut *bytes, show_hidden)?
-                    .ok_or(anyhow::anyhow!("is_hidden shouldn't appear in Enum types"))?;
-                Box::new(value)
-            }),
+            SchemaType::Struct(t) => {
+                TypedValue::Struct(decode_struct_fields(t, bytes, ctx, tolerate_eof)?)
+            }
+            // Borsh enum discriminants are 1 byte (u8) unless overridden
+            SchemaType::Enum(t) => decode_enum_variant(t, ctx.enum_width(), bytes, ctx)?,
+            SchemaType::TaggedEnum(width, t) => decode_enum_variant(t, *width, bytes, ctx)?,
             SchemaType::SmallVec(len_ty, elem) => {
                 // read length with the declared LenType
-                let len = match len_ty {
-                    SmallVecLen::U8 => u8::deserialize_reader(&mut *bytes)? as usize,
-                    SmallVecLen::U16 => u16::deserialize_reader(&mut *bytes)? as usize,
-                };
+                let len = len_ty.read(bytes)?;
 
                 // Fast path for bytes: SmallVec<*, u8> => TypedValue::Bytes
                 if matches!(**elem, SchemaType::U8) {
@@ -155,13 +450,59 @@ impl SchemaType {
                     *bytes = rest;
                     TypedValue::Bytes(raw.to_vec())
                 } else {
+                    ctx.count_elements(len)?;
                     let mut values = Vec::with_capacity(len);
                     for _ in 0..len {
-                        values.push(elem.deserialize_bytes(&mut *bytes, show_hidden)?);
+                        values.push(elem.decode(&mut *bytes, ctx)?);
                     }
                     TypedValue::Vec(values)
                 }
             }
+            SchemaType::RemainingVec(t) => {
+                let mut values = Vec::new();
+                while !bytes.is_empty() {
+                    ctx.count_elements(1)?;
+                    let remaining = bytes.len();
+                    values.push(t.decode(&mut *bytes, ctx)?);
+                    if bytes.len() == remaining {
+                        return Err(anyhow::anyhow!(
+                            "Zero-sized element can't fill the remaining {} bytes",
+                            remaining
+                        ));
+                    }
+                }
+                TypedValue::Vec(values)
+            }
+            SchemaType::Utf8Remaining => {
+                let text = lossy_utf8(bytes);
+                *bytes = &[];
+                text
+            }
+            SchemaType::RawString => {
+                let len = u32::deserialize_reader(&mut *bytes)? as usize;
+                if bytes.len() < len {
+                    return Err(anyhow::anyhow!(
+                        "Not enough bytes for a {}-byte string: have {}",
+                        len,
+                        bytes.len()
+                    ));
+                }
+                let (raw, rest) = bytes.split_at(len);
+                *bytes = rest;
+                lossy_utf8(raw)
+            }
+            SchemaType::Tlv(entries) => TypedValue::Struct(decode_tlv(entries, bytes, ctx)?),
+            SchemaType::Map(key, value) => {
+                let size = u32::deserialize_reader(&mut *bytes)? as usize;
+                ctx.count_elements(size)?;
+                // entries stay in stored order; rendering sorts them
+                let mut entries = Vec::with_capacity(size.min(bytes.len()));
+                for _ in 0..size {
+                    let k = key.decode(&mut *bytes, ctx)?;
+                    entries.push((k, value.decode(&mut *bytes, ctx)?));
+                }
+                TypedValue::Map(entries)
+            }
             SchemaType::RemainingBytes => {
                 // take everything that’s left
                 let out = bytes.to_vec();
@@ -173,12 +514,320 @@ impl SchemaType {
     }
 }
 
+/// `raw` as text, flagged lossy when invalid UTF-8 had to be replaced
+fn lossy_utf8(raw: &[u8]) -> TypedValue {
+    match std::str::from_utf8(raw) {
+        Ok(text) => TypedValue::Utf8 {
+            text: text.to_string(),
+            lossy: false,
+        },
+        Err(_) => TypedValue::Utf8 {
+            text: String::from_utf8_lossy(raw).into_owned(),
+            lossy: true,
+        },
+    }
+}
+
+/// Reads TLV entries until the buffer is exhausted. Types without a
+/// registered schema are kept as raw bytes under `unknown_<type>`; a zero
+/// type marks the start of uninitialized padding and ends the list.
+fn decode_tlv(
+    entries: &BTreeMap<u16, SchemaNode>,
+    bytes: &mut &[u8],
+    ctx: &mut DecodeContext,
+) -> anyhow::Result<Vec<ValueNode>> {
+    let mut values = Vec::new();
+    while !bytes.is_empty() {
+        let typ = u16::deserialize_reader(&mut *bytes)?;
+        if typ == 0 {
+            *bytes = &[];
+            break;
+        }
+        let len = u16::deserialize_reader(&mut *bytes)? as usize;
+        if bytes.len() < len {
+            return Err(anyhow::anyhow!(
+                "Not enough bytes for TLV entry {}: need {}, have {}",
+                typ,
+                len,
+                bytes.len()
+            ));
+        }
+        let (mut raw, rest) = bytes.split_at(len);
+        *bytes = rest;
+        ctx.count_elements(1)?;
+        values.push(match entries.get(&typ) {
+            Some(node) => {
+                // the entry is decoded from its own slice, which ends where
+                // `rest` starts
+                let end_offset = ctx.end_offset;
+                ctx.end_offset -= rest.len();
+                let value = node.typ.decode(&mut raw, ctx);
+                ctx.end_offset = end_offset;
+                let name = ctx.name(&node.name);
+                ctx.name_audited(&name);
+                ValueNode::new(name, value?)
+            }
+            None => ValueNode::new(format!("unknown_{}", typ), TypedValue::Bytes(raw.to_vec())),
+        });
+    }
+    Ok(values)
+}
+
+fn decode_array(
+    t: &SchemaType,
+    size: usize,
+    bytes: &mut &[u8],
+    ctx: &mut DecodeContext,
+) -> anyhow::Result<TypedValue> {
+    if matches!(t, SchemaType::U8) {
+        if bytes.len() < size {
+            return Err(anyhow::anyhow!(
+                "Not enough bytes for [u8; {}]: have {}",
+                size,
+                bytes.len()
+            ));
+        }
+        let (raw, rest) = bytes.split_at(size);
+        *bytes = rest;
+        Ok(TypedValue::Bytes(raw.to_vec()))
+    } else {
+        ctx.count_elements(size)?;
+        // counts can come from the data (`CountedArray`), so they don't size
+        // the allocation past what the remaining bytes could hold
+        let mut values = Vec::with_capacity(size.min(bytes.len()));
+        for _ in 0..size {
+            values.push(t.decode(&mut *bytes, ctx)?);
+        }
+        Ok(TypedValue::Array(values))
+    }
+}
+
+/// Values of the fields that a struct's `CountedArray`s take their length
+/// from, kept even when those fields are hidden
+struct CountSources<'a> {
+    names: Vec<&'a str>,
+    values: Vec<(&'a str, TypedValue)>,
+}
+
+impl<'a> CountSources<'a> {
+    fn new(fields: &'a [SchemaNode]) -> Self {
+        let names = fields
+            .iter()
+            .filter_map(|field| match &field.typ {
+                SchemaType::CountedArray { count_from, .. } => count_from.split('.').next(),
+                _ => None,
+            })
+            .collect();
+        Self {
+            names,
+            values: Vec::new(),
+        }
+    }
+
+    fn record(&mut self, field: &'a SchemaNode, value: &TypedValue) {
+        if self.names.contains(&&*field.name) {
+            self.values.push((&field.name, value.clone()));
+        }
+    }
+
+    fn count(&self, path: &str) -> anyhow::Result<usize> {
+        let mut segments = path.split('.');
+        let head = segments.next().unwrap_or_default();
+        let mut value = self
+            .values
+            .iter()
+            .find(|(name, _)| *name == head)
+            .map(|(_, value)| value);
+        for segment in segments {
+            value = match value {
+                Some(TypedValue::Struct(fields)) => fields
+                    .iter()
+                    .find(|field| &*field.name == segment)
+                    .map(|field| &field.value),
+                _ => None,
+            };
+        }
+        let value = value.ok_or_else(|| {
+            anyhow::anyhow!("CountedArray count field {} is not decoded before it", path)
+        })?;
+        let count = value.as_u64().ok_or_else(|| {
+            anyhow::anyhow!("CountedArray count field {} is not an integer", path)
+        })?;
+        Ok(count as usize)
+    }
+}
+
+/// Decodes one struct field, resolving `CountedArray` lengths from the
+/// fields before it
+fn decode_struct_field<'a>(
+    field: &'a SchemaNode,
+    sources: &mut CountSources<'a>,
+    bytes: &mut &[u8],
+    ctx: &mut DecodeContext,
+) -> anyhow::Result<Option<ValueNode>> {
+    let value = match &field.typ {
+        SchemaType::CountedArray {
+            count_from,
+            element,
+        } => {
+            let remaining = bytes.len();
+            let value = ctx.audited(bytes, |bytes, ctx| {
+                sources
+                    .count(count_from)
+                    .and_then(|count| decode_array(element, count, bytes, ctx))
+            });
+            if value.is_err() {
+                ctx.fail_at(remaining);
+            }
+            value?
+        }
+        typ => typ.decode(bytes, ctx)?,
+    };
+    sources.record(field, &value);
+    let name = ctx.name(&field.name);
+    ctx.name_audited(&name);
+    if field.is_hidden && !ctx.options.show_hidden {
+        Ok(None)
+    } else {
+        Ok(Some(ValueNode::new(name, value)))
+    }
+}
+
+/// Field indices in the order they're serialized
+pub(crate) fn serialization_order(fields: &[SchemaNode]) -> impl Iterator<Item = usize> {
+    let mut order: Vec<usize> = (0..fields.len()).collect();
+    order.sort_by_key(|&index| {
+        fields[index]
+            .serialization_order
+            .map_or(index, |order| order as usize)
+    });
+    order.into_iter()
+}
+
+/// Reads fields in serialization order, returning them in declaration order.
+/// On error, the fields decoded so far are left in `ctx.partial`. With
+/// `tolerate_eof`, running out of bytes between fields ends the struct.
+fn decode_struct_fields(
+    fields: &[SchemaNode],
+    bytes: &mut &[u8],
+    ctx: &mut DecodeContext,
+    tolerate_eof: bool,
+) -> anyhow::Result<Vec<ValueNode>> {
+    let mut sources = CountSources::new(fields);
+    if fields
+        .iter()
+        .all(|field| field.serialization_order.is_none())
+    {
+        let mut values = Vec::with_capacity(fields.len());
+        for field in fields {
+            if bytes.is_empty() && tolerate_eof {
+                break;
+            }
+            match decode_struct_field(field, &mut sources, &mut *bytes, ctx) {
+                Ok(Some(val)) => values.push(val),
+                Ok(None) => (),
+                Err(e) => {
+                    values.extend(take_partial_field(field, ctx));
+                    ctx.partial = Some(TypedValue::Struct(values));
+                    return Err(e);
+                }
+            }
+        }
+        return Ok(values);
+    }
+
+    let mut values = vec![None; fields.len()];
+    for index in serialization_order(fields) {
+        if bytes.is_empty() && tolerate_eof {
+            break;
+        }
+        match decode_struct_field(&fields[index], &mut sources, &mut *bytes, ctx) {
+            Ok(val) => values[index] = val,
+            Err(e) => {
+                values[index] = take_partial_field(&fields[index], ctx);
+                ctx.partial = Some(TypedValue::Struct(values.into_iter().flatten().collect()));
+                return Err(e);
+            }
+        }
+    }
+    Ok(values.into_iter().flatten().collect())
+}
+
+/// The partial value left by `field` if it's a struct itself; anything
+/// left deeper (e.g. by a struct inside a vec) is dropped
+fn take_partial_field(field: &SchemaNode, ctx: &mut DecodeContext) -> Option<ValueNode> {
+    let partial = ctx.partial.take()?;
+    matches!(field.typ, SchemaType::Struct(_))
+        .then(|| ValueNode::new(ctx.name(&field.name), partial))
+}
+
+impl SmallVecLen {
+    pub(crate) fn read(&self, bytes: &mut &[u8]) -> anyhow::Result<usize> {
+        match self {
+            SmallVecLen::U8 => Ok(u8::deserialize_reader(&mut *bytes)? as usize),
+            SmallVecLen::U16 => Ok(u16::deserialize_reader(&mut *bytes)? as usize),
+            SmallVecLen::ShortU16 => {
+                let mut len = 0usize;
+                for i in 0..3 {
+                    let byte = u8::deserialize_reader(&mut *bytes)?;
+                    if i > 0 && byte == 0 {
+                        return Err(anyhow::anyhow!("Non-canonical compact-u16 length"));
+                    }
+                    len |= ((byte & 0x7f) as usize) << (7 * i);
+                    if byte & 0x80 == 0 {
+                        if len > u16::MAX as usize {
+                            return Err(anyhow::anyhow!("Compact-u16 length {} exceeds u16", len));
+                        }
+                        return Ok(len);
+                    }
+                }
+                Err(anyhow::anyhow!("Compact-u16 length is longer than 3 bytes"))
+            }
+        }
+    }
+}
+
+impl IntWidth {
+    pub(crate) fn read(&self, bytes: &mut &[u8]) -> anyhow::Result<u64> {
+        Ok(match self {
+            IntWidth::U8 => u8::deserialize_reader(&mut *bytes)? as u64,
+            IntWidth::U16 => u16::deserialize_reader(&mut *bytes)? as u64,
+            IntWidth::U32 => u32::deserialize_reader(&mut *bytes)? as u64,
+            IntWidth::U64 => u64::deserialize_reader(&mut *bytes)?,
+        })
+    }
+}
+
+fn decode_enum_variant(
+    variants: &[SchemaNode],
+    width: IntWidth,
+    bytes: &mut &[u8],
+    ctx: &mut DecodeContext,
+) -> anyhow::Result<TypedValue> {
+    let discriminant = width.read(bytes)?;
+    let variant = usize::try_from(discriminant)
+        .ok()
+        .and_then(|index| variants.get(index))
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "enum discriminant {} out of bounds (variants: {})",
+                discriminant,
+                variants.len()
+            )
+        })?;
+    let value = variant
+        .decode(bytes, ctx)?
+        .ok_or(anyhow::anyhow!("is_hidden shouldn't appear in Enum types"))?;
+    Ok(TypedValue::Enum(Box::new(value)))
+}
+
 // at the bottom of src/schema/bytes_deserialize.rs
 #[cfg(test)]
 mod smallvec_bytes_tests {
-    use crate::schema::{SchemaType, SmallVecLen};
+    use crate::schema::{DecodeOptions, SchemaNode, SchemaType, SmallVecLen};
     use crate::value::TypedValue;
     use solana_program::pubkey::Pubkey;
+    use std::sync::Arc;
 
     #[test]
     fn smallvec_u8_of_u8_returns_bytes() {
@@ -192,6 +841,32 @@ mod smallvec_bytes_tests {
         assert!(buf.is_empty(), "buffer fully consumed");
     }
 
+    #[test]
+    fn short_u16_lengths_use_the_compact_encoding() {
+        let ty = SchemaType::SmallVec(SmallVecLen::ShortU16, Box::new(SchemaType::U8));
+        for (len, prefix) in [
+            (0usize, vec![0x00]),
+            (127, vec![0x7f]),
+            (128, vec![0x80, 0x01]),
+            (16_384, vec![0x80, 0x80, 0x01]),
+            (65_535, vec![0xff, 0xff, 0x03]),
+        ] {
+            let mut data = prefix.clone();
+            data.resize(prefix.len() + len, 7);
+            let value = ty.deserialize_bytes(&mut data.as_slice(), false).unwrap();
+            assert_eq!(value, TypedValue::Bytes(vec![7; len]));
+            assert_eq!(ty.serialize_value(&value).unwrap(), data);
+        }
+
+        for bad in [
+            &[0x80, 0x00][..],         // non-canonical zero continuation
+            &[0xff, 0xff, 0x04],       // exceeds u16
+            &[0x80, 0x80, 0x80, 0x01], // more than 3 bytes
+        ] {
+            assert!(SmallVecLen::ShortU16.read(&mut &bad[..]).is_err());
+        }
+    }
+
     #[test]
     fn smallvec_u16_of_u8_returns_bytes() {
         let ty = SchemaType::SmallVec(SmallVecLen::U16, Box::new(SchemaType::U8));
@@ -236,6 +911,37 @@ mod smallvec_bytes_tests {
         assert!(buf.is_empty(), "buffer fully consumed");
     }
 
+    #[test]
+    fn smallvec_of_smallvecs_decodes_each_inner_blob() {
+        let inner = SchemaType::SmallVec(SmallVecLen::U16, Box::new(SchemaType::U8));
+        let ty = SchemaType::SmallVec(SmallVecLen::U8, Box::new(inner));
+        // outer len=2; inner len=3 (LE u16) then len=1
+        let mut buf: &[u8] = &[2, 3, 0, 1, 2, 3, 1, 0, 9];
+        let v = ty.deserialize_bytes(&mut buf, false).expect("ok");
+        assert_eq!(
+            v,
+            TypedValue::Vec(vec![
+                TypedValue::Bytes(vec![1, 2, 3]),
+                TypedValue::Bytes(vec![9]),
+            ])
+        );
+        assert!(buf.is_empty(), "buffer fully consumed");
+    }
+
+    #[test]
+    fn decode_once_reports_bytes_consumed() {
+        let data = [42u64.to_le_bytes(), [0xff; 8]].concat();
+        assert_eq!(
+            SchemaType::U64.decode_once(&data[..8]).unwrap(),
+            (TypedValue::U64(42), 8)
+        );
+        assert_eq!(
+            SchemaType::U32.decode_once(&data).unwrap(),
+            (TypedValue::U32(42), 4)
+        );
+        assert!(SchemaType::U64.decode_once(&data[..5]).is_err());
+    }
+
     #[test]
     fn array_u8_returns_bytes() {
         let ty = SchemaType::Array(3, Box::new(SchemaType::U8));
@@ -247,4 +953,420 @@ mod smallvec_bytes_tests {
         }
         assert!(buf.is_empty(), "buffer fully consumed");
     }
+
+    #[test]
+    fn utf8_remaining_decodes_text_with_lossy_fallback() {
+        let ty = SchemaType::Utf8Remaining;
+
+        let mut buf: &[u8] = "gm ☀".as_bytes();
+        assert_eq!(
+            ty.deserialize_bytes(&mut buf, false).unwrap(),
+            TypedValue::Utf8 {
+                text: "gm ☀".to_string(),
+                lossy: false
+            }
+        );
+        assert!(buf.is_empty(), "buffer fully consumed");
+
+        let mut buf: &[u8] = &[b'g', b'm', 0xff, b'!'];
+        assert_eq!(
+            ty.deserialize_bytes(&mut buf, false).unwrap(),
+            TypedValue::Utf8 {
+                text: "gm\u{fffd}!".to_string(),
+                lossy: true
+            }
+        );
+        assert!(buf.is_empty(), "buffer fully consumed");
+    }
+
+    #[test]
+    fn raw_string_decodes_invalid_utf8_lossily() {
+        let schema = SchemaNode::new_struct(
+            "Label",
+            vec![("name", SchemaType::RawString), ("flag", SchemaType::U8)],
+        );
+        // "café" in Latin-1, whose 0xe9 is invalid UTF-8
+        let mut data = 4u32.to_le_bytes().to_vec();
+        data.extend_from_slice(&[b'c', b'a', b'f', 0xe9]);
+        data.push(7);
+
+        let mut buf = data.as_slice();
+        let value = schema.deserialize_bytes(&mut buf, false).unwrap().unwrap();
+        assert_eq!(
+            value.value,
+            TypedValue::new_struct(vec![
+                (
+                    "name",
+                    TypedValue::Utf8 {
+                        text: "caf\u{fffd}".to_string(),
+                        lossy: true
+                    }
+                ),
+                ("flag", TypedValue::U8(7)),
+            ])
+        );
+        assert!(buf.is_empty(), "buffer fully consumed");
+
+        // the same bytes fail as a UTF-8 string
+        let strict = SchemaNode::new_struct(
+            "Label",
+            vec![("name", SchemaType::String), ("flag", SchemaType::U8)],
+        );
+        assert!(strict
+            .deserialize_bytes(&mut data.as_slice(), false)
+            .is_err());
+
+        let mut short: &[u8] = &[9, 0, 0, 0, b'c'];
+        assert!(SchemaType::RawString
+            .deserialize_bytes(&mut short, false)
+            .is_err());
+    }
+
+    #[test]
+    fn nested_vecs_exceeding_total_element_budget_error() {
+        // Vec<Vec<u16>>: 3 outer elements, each holding 3 u16s => 12 elements
+        let ty = SchemaType::vec(SchemaType::vec(SchemaType::U16));
+        let mut data = 3u32.to_le_bytes().to_vec();
+        for _ in 0..3 {
+            data.extend_from_slice(&3u32.to_le_bytes());
+            data.extend_from_slice(&[1, 0, 2, 0, 3, 0]);
+        }
+
+        let options = DecodeOptions {
+            max_total_elements: 10,
+            ..Default::default()
+        };
+        let err = ty
+            .deserialize_bytes_with_options(&mut data.as_slice(), &options)
+            .expect_err("budget exceeded");
+        assert!(
+            err.to_string().contains("element budget exceeded"),
+            "{}",
+            err
+        );
+
+        let options = DecodeOptions {
+            max_total_elements: 12,
+            ..Default::default()
+        };
+        assert!(ty
+            .deserialize_bytes_with_options(&mut data.as_slice(), &options)
+            .is_ok());
+    }
+
+    #[test]
+    fn tlv_entries_decode_by_registered_type() {
+        let ty = SchemaType::Tlv(
+            [
+                (
+                    1,
+                    SchemaNode::new_struct(
+                        "transferFeeConfig",
+                        vec![("authority", SchemaType::Pubkey), ("bps", SchemaType::U16)],
+                    ),
+                ),
+                (6, SchemaNode::new("memoTransfer", SchemaType::Bool)),
+            ]
+            .into_iter()
+            .collect(),
+        );
+
+        let authority = Pubkey::new_unique();
+        let mut data = Vec::new();
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&34u16.to_le_bytes());
+        data.extend_from_slice(authority.as_ref());
+        data.extend_from_slice(&50u16.to_le_bytes());
+        data.extend_from_slice(&6u16.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.push(1);
+        // unregistered extension
+        data.extend_from_slice(&9u16.to_le_bytes());
+        data.extend_from_slice(&2u16.to_le_bytes());
+        data.extend_from_slice(&[0xaa, 0xbb]);
+        // zeroed padding
+        data.extend_from_slice(&[0; 6]);
+
+        let mut buf = data.as_slice();
+        assert_eq!(
+            ty.deserialize_bytes(&mut buf, false).unwrap(),
+            TypedValue::new_struct(vec![
+                (
+                    "transferFeeConfig",
+                    TypedValue::new_struct(vec![
+                        ("authority", TypedValue::Pubkey(authority.to_string())),
+                        ("bps", TypedValue::U16(50)),
+                    ])
+                ),
+                ("memoTransfer", TypedValue::Bool(true)),
+                ("unknown_9", TypedValue::Bytes(vec![0xaa, 0xbb])),
+            ])
+        );
+        assert!(buf.is_empty());
+
+        let mut truncated = &data[..10];
+        assert!(ty.deserialize_bytes(&mut truncated, false).is_err());
+    }
+
+    #[test]
+    fn counted_array_reads_its_length_from_an_earlier_field() {
+        let record = SchemaNode::new_struct(
+            "Record",
+            vec![("price", SchemaType::U64), ("qty", SchemaType::U32)],
+        )
+        .typ;
+        let mut num_entries = SchemaNode::new("numEntries", SchemaType::U32);
+        num_entries.is_hidden = true;
+        let ty = SchemaType::Struct(vec![
+            num_entries,
+            SchemaNode::new(
+                "entries",
+                SchemaType::CountedArray {
+                    count_from: "numEntries".to_string(),
+                    element: Box::new(record),
+                },
+            ),
+        ]);
+
+        let mut data = 2u32.to_le_bytes().to_vec();
+        for (price, qty) in [(100u64, 1u32), (105, 7)] {
+            data.extend_from_slice(&price.to_le_bytes());
+            data.extend_from_slice(&qty.to_le_bytes());
+        }
+        data.extend_from_slice(&[0xff; 4]);
+
+        let mut buf = data.as_slice();
+        let record = |price: u64, qty: u32| {
+            TypedValue::new_struct(vec![
+                ("price", TypedValue::U64(price)),
+                ("qty", TypedValue::U32(qty)),
+            ])
+        };
+        // the hidden count still sizes the array
+        assert_eq!(
+            ty.deserialize_bytes(&mut buf, false).unwrap(),
+            TypedValue::new_struct(vec![(
+                "entries",
+                TypedValue::Array(vec![record(100, 1), record(105, 7)])
+            )])
+        );
+        assert_eq!(buf, &[0xff; 4]);
+    }
+
+    #[test]
+    fn huge_counts_fail_instead_of_allocating() {
+        let ty = SchemaType::Struct(vec![
+            SchemaNode::new("count", SchemaType::U64),
+            SchemaNode::new(
+                "values",
+                SchemaType::CountedArray {
+                    count_from: "count".to_string(),
+                    element: Box::new(SchemaType::U16),
+                },
+            ),
+        ]);
+        for count in [u64::MAX, u32::MAX as u64] {
+            let mut data = count.to_le_bytes().to_vec();
+            data.extend_from_slice(&[1, 0, 2, 0]);
+            assert!(ty.deserialize_bytes(&mut data.as_slice(), false).is_err());
+        }
+
+        let mut data = u32::MAX.to_le_bytes().to_vec();
+        data.extend_from_slice(&[1, 0]);
+        let ty = SchemaType::vec(SchemaType::U16);
+        assert!(ty.deserialize_bytes(&mut data.as_slice(), false).is_err());
+    }
+
+    #[test]
+    fn float_arrays_decode_element_by_element() {
+        let ty = SchemaType::array(4, SchemaType::F32);
+        let mut data = Vec::new();
+        for v in [1.5f32, -0.25, f32::MAX, 0.0] {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        assert_eq!(
+            ty.deserialize_bytes(&mut data.as_slice(), false).unwrap(),
+            TypedValue::Array(vec![
+                TypedValue::F32(1.5),
+                TypedValue::F32(-0.25),
+                TypedValue::F32(f32::MAX),
+                TypedValue::F32(0.0),
+            ])
+        );
+
+        let ty = SchemaType::array(2, SchemaType::F64);
+        // 1e-9 and -123456.789 as little-endian IEEE 754
+        let data: Vec<u8> = [
+            0x3e11_2e0b_e826_d695u64.to_le_bytes(),
+            0xc0fe_240c_9fbe_76c9u64.to_le_bytes(),
+        ]
+        .concat();
+        let mut buf = data.as_slice();
+        assert_eq!(
+            ty.deserialize_bytes(&mut buf, false).unwrap(),
+            TypedValue::Array(vec![TypedValue::F64(1e-9), TypedValue::F64(-123456.789)])
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn interned_names_share_one_allocation_per_field() {
+        let order = SchemaNode::new_struct(
+            "Order",
+            vec![("price", SchemaType::U64), ("size", SchemaType::U32)],
+        );
+        let ty = SchemaType::array(10_000, order.typ);
+        let data = vec![0u8; 10_000 * 12];
+
+        let names = |intern_names: bool| {
+            let options = DecodeOptions {
+                intern_names,
+                ..DecodeOptions::new(false)
+            };
+            let TypedValue::Array(orders) = ty
+                .deserialize_bytes_with_options(&mut data.as_slice(), &options)
+                .unwrap()
+            else {
+                panic!("expected an array");
+            };
+            orders
+                .into_iter()
+                .map(|order| match order {
+                    TypedValue::Struct(fields) => fields[0].name.clone(),
+                    other => panic!("expected a struct, got {:?}", other),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let interned = names(true);
+        assert!(interned.iter().all(|name| Arc::ptr_eq(name, &interned[0])));
+        assert_eq!(&*interned[0], "price");
+
+        let separate = names(false);
+        assert!(!Arc::ptr_eq(&separate[0], &separate[1]));
+    }
+
+    #[test]
+    fn interned_names_are_shared_across_decodes() {
+        let schema = SchemaNode::new_struct("Counter", vec![("count", SchemaType::U64)]);
+        let options = DecodeOptions {
+            intern_names: true,
+            ..DecodeOptions::new(false)
+        };
+        let decode = || {
+            let data = 7u64.to_le_bytes();
+            match schema
+                .typ
+                .deserialize_bytes_with_options(&mut data.as_slice(), &options)
+                .unwrap()
+            {
+                TypedValue::Struct(fields) => fields[0].name.clone(),
+                other => panic!("expected a struct, got {:?}", other),
+            }
+        };
+
+        let (first, second) = (decode(), decode());
+        assert!(Arc::ptr_eq(&first, &second));
+        let SchemaType::Struct(fields) = &schema.typ else {
+            unreachable!()
+        };
+        assert!(Arc::ptr_eq(&first, &fields[0].name));
+    }
+
+    #[test]
+    fn errors_report_the_offset_of_the_failing_value() {
+        let schema = SchemaNode::new_struct(
+            "Position",
+            vec![
+                ("owner", SchemaType::Pubkey),
+                ("size", SchemaType::U64),
+                ("entryPrice", SchemaType::U64),
+            ],
+        );
+        // entryPrice is cut short after 3 of its 8 bytes
+        let data = vec![0u8; 32 + 8 + 3];
+        let options = DecodeOptions {
+            error_offsets: true,
+            ..DecodeOptions::new(false)
+        };
+
+        let err = schema
+            .deserialize_bytes_with_options(&mut data.as_slice(), &options)
+            .unwrap_err();
+        assert!(err.to_string().ends_with("(at byte offset 40)"), "{}", err);
+
+        let err = schema
+            .deserialize_bytes(&mut data.as_slice(), false)
+            .unwrap_err();
+        assert!(!err.to_string().contains("byte offset"), "{}", err);
+    }
+
+    #[test]
+    fn remaining_bytes_take_the_whole_tail() {
+        let schema = SchemaNode::new_struct(
+            "Memo",
+            vec![
+                ("kind", SchemaType::U16),
+                ("rest", SchemaType::RemainingBytes),
+            ],
+        );
+        let decode = |data: &[u8]| {
+            let mut buf = data;
+            let value = schema.deserialize_bytes(&mut buf, false).unwrap().unwrap();
+            assert!(buf.is_empty(), "buffer fully consumed");
+            match value.value {
+                TypedValue::Struct(fields) => fields[1].value.clone(),
+                other => panic!("expected Struct, got {:?}", other),
+            }
+        };
+
+        assert_eq!(
+            decode(&[7, 0, 0xde, 0xad, 0xbe, 0xef]),
+            TypedValue::Bytes(vec![0xde, 0xad, 0xbe, 0xef])
+        );
+        // nothing after the fixed field is still a valid, empty tail
+        assert_eq!(decode(&[7, 0]), TypedValue::Bytes(vec![]));
+    }
+
+    #[test]
+    fn only_the_outermost_struct_tolerates_missing_trailing_fields() {
+        let options = DecodeOptions {
+            tolerate_missing_trailing_fields: true,
+            ..Default::default()
+        };
+        let point = SchemaType::Struct(vec![
+            SchemaNode::new("x", SchemaType::U64),
+            SchemaNode::new("y", SchemaType::U64),
+        ]);
+
+        // fields missing from the end of the outermost struct are left out
+        let account = SchemaNode::new_struct(
+            "Account",
+            vec![("count", SchemaType::U64), ("p", point.clone())],
+        );
+        let data = 3u64.to_le_bytes();
+        let value = account
+            .deserialize_bytes_with_options(&mut &data[..], &options)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            value.value,
+            TypedValue::new_struct(vec![("count", TypedValue::U64(3))])
+        );
+
+        // a nested struct cut between its fields is truncated mid-field
+        let nested = SchemaNode::new_struct("Account", vec![("p", point.clone())]);
+        let data = 1u64.to_le_bytes();
+        assert!(nested
+            .deserialize_bytes_with_options(&mut &data[..], &options)
+            .is_err());
+
+        // so is a vec holding fewer elements than its length says
+        let points = SchemaNode::new_struct("Account", vec![("ps", SchemaType::vec(point))]);
+        let mut data = 3u32.to_le_bytes().to_vec();
+        data.extend_from_slice(&[1; 16]);
+        assert!(points
+            .deserialize_bytes_with_options(&mut data.as_slice(), &options)
+            .is_err());
+    }
 }
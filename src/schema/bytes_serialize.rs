@@ -0,0 +1,408 @@
+//! Encoding decoded values back into the bytes they were decoded from, the
+//! inverse of `bytes_deserialize`
+
+use std::str::FromStr;
+
+use solana_program::pubkey::Pubkey;
+
+use crate::{
+    schema::{
+        bytes_deserialize::serialization_order, IntWidth, SchemaNode, SchemaType, SmallVecLen,
+    },
+    value::{TypedValue, ValueNode},
+};
+
+impl SchemaType {
+    /// Encodes `value` with this schema, e.g. to re-emit decoded instruction
+    /// data or build it programmatically. Struct values need every field,
+    /// so values of schemas with hidden fields must be decoded with
+    /// `show_hidden`. Errors when the value doesn't match the schema.
+    pub fn serialize_value(&self, value: &TypedValue) -> anyhow::Result<Vec<u8>> {
+        self.serialize_value_with(value, IntWidth::U8)
+    }
+
+    /// `serialize_value` with the discriminant of untagged `Enum`s written
+    /// as `enum_width`, see `DecodeOptions::enum_disc_width`
+    pub fn serialize_value_with(
+        &self,
+        value: &TypedValue,
+        enum_width: IntWidth,
+    ) -> anyhow::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        self.encode(value, enum_width, &mut out)?;
+        Ok(out)
+    }
+
+    fn encode(
+        &self,
+        value: &TypedValue,
+        enum_width: IntWidth,
+        out: &mut Vec<u8>,
+    ) -> anyhow::Result<()> {
+        match (self, value) {
+            (SchemaType::Empty, TypedValue::Empty) => (),
+            (SchemaType::Pubkey, TypedValue::Pubkey(v)) => {
+                out.extend_from_slice(&Pubkey::from_str(v)?.to_bytes())
+            }
+            (SchemaType::String, TypedValue::String(v)) => {
+                write_u32_len(v.len(), out)?;
+                out.extend_from_slice(v.as_bytes());
+            }
+            (SchemaType::I8, TypedValue::I8(v)) => out.extend_from_slice(&v.to_le_bytes()),
+            (SchemaType::U8, TypedValue::U8(v)) => out.push(*v),
+            (SchemaType::I16, TypedValue::I16(v)) => out.extend_from_slice(&v.to_le_bytes()),
+            (SchemaType::U16, TypedValue::U16(v)) => out.extend_from_slice(&v.to_le_bytes()),
+            (SchemaType::I32, TypedValue::I32(v)) => out.extend_from_slice(&v.to_le_bytes()),
+            (SchemaType::U32, TypedValue::U32(v)) => out.extend_from_slice(&v.to_le_bytes()),
+            (SchemaType::I64, TypedValue::I64(v))
+            | (SchemaType::UnixTimestamp, TypedValue::UnixTimestamp(v))
+            | (SchemaType::Duration, TypedValue::Duration(v)) => {
+                out.extend_from_slice(&v.to_le_bytes())
+            }
+            (SchemaType::U64, TypedValue::U64(v)) => out.extend_from_slice(&v.to_le_bytes()),
+            (SchemaType::I128, TypedValue::I128(v)) => out.extend_from_slice(&v.to_le_bytes()),
+            (SchemaType::U128, TypedValue::U128(v)) => out.extend_from_slice(&v.to_le_bytes()),
+            (SchemaType::F32, TypedValue::F32(v)) => out.extend_from_slice(&v.to_le_bytes()),
+            (SchemaType::F64, TypedValue::F64(v)) => out.extend_from_slice(&v.to_le_bytes()),
+            (SchemaType::Bool, TypedValue::Bool(v)) => out.push(*v as u8),
+            (SchemaType::Option(t) | SchemaType::LenientOption(t), TypedValue::Option(v)) => {
+                match v.as_ref() {
+                    None => out.push(0),
+                    Some(v) => {
+                        out.push(1);
+                        t.encode(v, enum_width, out)?;
+                    }
+                }
+            }
+            (SchemaType::FlaggedOption { flag_width, inner }, TypedValue::Option(v)) => {
+                match v.as_ref() {
+                    None => flag_width.write(0, out)?,
+                    Some(v) => {
+                        flag_width.write(1, out)?;
+                        inner.encode(v, enum_width, out)?;
+                    }
+                }
+            }
+            (SchemaType::Array(size, t), value) => {
+                encode_array(t, Some(*size), value, enum_width, out)?
+            }
+            (SchemaType::CountedArray { element, .. }, value) => {
+                encode_array(element, None, value, enum_width, out)?
+            }
+            (SchemaType::Bitmap(bits), TypedValue::Vec(values)) => {
+                if values.len() != *bits {
+                    return Err(anyhow::anyhow!(
+                        "{} flags for a {}-bit bitmap",
+                        values.len(),
+                        bits
+                    ));
+                }
+                let mut raw = vec![0u8; bits.div_ceil(8)];
+                for (bit, value) in values.iter().enumerate() {
+                    match value {
+                        TypedValue::Bool(true) => raw[bit / 8] |= 1 << (bit % 8),
+                        TypedValue::Bool(false) => (),
+                        value => return Err(mismatch(&SchemaType::Bool, value)),
+                    }
+                }
+                out.extend(raw);
+            }
+            (SchemaType::Tuple(types), TypedValue::Tuple(values)) => {
+                if types.len() != values.len() {
+                    return Err(anyhow::anyhow!(
+                        "Tuple of {} values for {} types",
+                        values.len(),
+                        types.len()
+                    ));
+                }
+                for (t, v) in types.iter().zip(values) {
+                    t.encode(v, enum_width, out)?;
+                }
+            }
+            (SchemaType::Vec(t), TypedValue::Bytes(v)) if matches!(**t, SchemaType::U8) => {
+                write_u32_len(v.len(), out)?;
+                out.extend_from_slice(v);
+            }
+            (SchemaType::Vec(t), TypedValue::Vec(values)) => {
+                write_u32_len(values.len(), out)?;
+                for v in values {
+                    t.encode(v, enum_width, out)?;
+                }
+            }
+            (SchemaType::SmallVec(len_ty, elem), value) => {
+                let values = match value {
+                    TypedValue::Bytes(v) if matches!(**elem, SchemaType::U8) => {
+                        write_small_len(*len_ty, v.len(), out)?;
+                        out.extend_from_slice(v);
+                        return Ok(());
+                    }
+                    TypedValue::Vec(values) => values,
+                    value => return Err(mismatch(self, value)),
+                };
+                write_small_len(*len_ty, values.len(), out)?;
+                for v in values {
+                    elem.encode(v, enum_width, out)?;
+                }
+            }
+            (SchemaType::Struct(fields), TypedValue::Struct(values)) => {
+                encode_struct_fields(fields, values, enum_width, out)?
+            }
+            (SchemaType::Enum(variants), TypedValue::Enum(variant)) => {
+                encode_enum_variant(variants, enum_width, variant, enum_width, out)?
+            }
+            (SchemaType::TaggedEnum(width, variants), TypedValue::Enum(variant)) => {
+                encode_enum_variant(variants, *width, variant, enum_width, out)?
+            }
+            (SchemaType::RemainingBytes, TypedValue::Bytes(v)) => out.extend_from_slice(v),
+            (SchemaType::RemainingVec(t), TypedValue::Vec(values)) => {
+                for v in values {
+                    t.encode(v, enum_width, out)?;
+                }
+            }
+            (SchemaType::Utf8Remaining, TypedValue::Utf8 { text, lossy }) => {
+                if *lossy {
+                    return Err(anyhow::anyhow!(
+                        "Text decoded lossily can't be encoded back to its bytes"
+                    ));
+                }
+                out.extend_from_slice(text.as_bytes());
+            }
+            (SchemaType::RawString, TypedValue::Utf8 { text, lossy }) => {
+                if *lossy {
+                    return Err(anyhow::anyhow!(
+                        "Text decoded lossily can't be encoded back to its bytes"
+                    ));
+                }
+                write_u32_len(text.len(), out)?;
+                out.extend_from_slice(text.as_bytes());
+            }
+            (SchemaType::Tlv(entries), TypedValue::Struct(values)) => {
+                for value in values {
+                    let (typ, payload) = match entries
+                        .iter()
+                        .find(|(_, node)| node.name == value.name)
+                    {
+                        Some((typ, node)) => (
+                            *typ,
+                            node.typ.serialize_value_with(&value.value, enum_width)?,
+                        ),
+                        None => match (
+                            value.name.strip_prefix("unknown_").map(u16::from_str),
+                            &value.value,
+                        ) {
+                            (Some(Ok(typ)), TypedValue::Bytes(raw)) => (typ, raw.clone()),
+                            _ => return Err(anyhow::anyhow!("Unknown TLV entry {}", value.name)),
+                        },
+                    };
+                    let len = u16::try_from(payload.len()).map_err(|_| {
+                        anyhow::anyhow!("TLV entry {} is too long: {}", value.name, payload.len())
+                    })?;
+                    out.extend_from_slice(&typ.to_le_bytes());
+                    out.extend_from_slice(&len.to_le_bytes());
+                    out.extend_from_slice(&payload);
+                }
+            }
+            (SchemaType::Map(key, value_type), TypedValue::Map(entries)) => {
+                write_u32_len(entries.len(), out)?;
+                for (k, v) in entries {
+                    key.encode(k, enum_width, out)?;
+                    value_type.encode(v, enum_width, out)?;
+                }
+            }
+            (schema, value) => return Err(mismatch(schema, value)),
+        }
+        Ok(())
+    }
+}
+
+fn mismatch(schema: &SchemaType, value: &TypedValue) -> anyhow::Error {
+    anyhow::anyhow!("Value {:?} doesn't match schema {:?}", value, schema)
+}
+
+fn write_u32_len(len: usize, out: &mut Vec<u8>) -> anyhow::Result<()> {
+    let len = u32::try_from(len).map_err(|_| anyhow::anyhow!("Length {} exceeds u32", len))?;
+    out.extend_from_slice(&len.to_le_bytes());
+    Ok(())
+}
+
+fn write_small_len(len_ty: SmallVecLen, len: usize, out: &mut Vec<u8>) -> anyhow::Result<()> {
+    let width = match len_ty {
+        SmallVecLen::U8 => IntWidth::U8,
+        SmallVecLen::U16 => IntWidth::U16,
+        SmallVecLen::ShortU16 => {
+            let mut len = u16::try_from(len)
+                .map_err(|_| anyhow::anyhow!("{} doesn't fit in a compact-u16", len))?;
+            loop {
+                let byte = (len & 0x7f) as u8;
+                len >>= 7;
+                if len == 0 {
+                    out.push(byte);
+                    return Ok(());
+                }
+                out.push(byte | 0x80);
+            }
+        }
+    };
+    width.write(len as u64, out)
+}
+
+/// Elements of an array with `size` elements, or any number without one
+fn encode_array(
+    t: &SchemaType,
+    size: Option<usize>,
+    value: &TypedValue,
+    enum_width: IntWidth,
+    out: &mut Vec<u8>,
+) -> anyhow::Result<()> {
+    let len = match value {
+        TypedValue::Bytes(v) if matches!(t, SchemaType::U8) => v.len(),
+        TypedValue::Array(values) => values.len(),
+        value => {
+            return Err(anyhow::anyhow!(
+                "Value {:?} doesn't match an array of {:?}",
+                value,
+                t
+            ))
+        }
+    };
+    if let Some(size) = size.filter(|size| *size != len) {
+        return Err(anyhow::anyhow!(
+            "Array of {} elements for [{:?}; {}]",
+            len,
+            t,
+            size
+        ));
+    }
+    match value {
+        TypedValue::Bytes(v) => out.extend_from_slice(v),
+        TypedValue::Array(values) => {
+            for v in values {
+                t.encode(v, enum_width, out)?;
+            }
+        }
+        _ => unreachable!("checked above"),
+    }
+    Ok(())
+}
+
+/// Writes fields in serialization order, looking their values up by name
+fn encode_struct_fields(
+    fields: &[SchemaNode],
+    values: &[ValueNode],
+    enum_width: IntWidth,
+    out: &mut Vec<u8>,
+) -> anyhow::Result<()> {
+    for field in serialization_order(fields).map(|index| &fields[index]) {
+        let value = values
+            .iter()
+            .find(|value| value.name == field.name)
+            .ok_or_else(|| anyhow::anyhow!("Missing field {}", field.name))?;
+        field.typ.encode(&value.value, enum_width, out)?;
+    }
+    Ok(())
+}
+
+fn encode_enum_variant(
+    variants: &[SchemaNode],
+    width: IntWidth,
+    variant: &ValueNode,
+    enum_width: IntWidth,
+    out: &mut Vec<u8>,
+) -> anyhow::Result<()> {
+    let index = variants
+        .iter()
+        .position(|schema| variant.name == schema.name)
+        .ok_or_else(|| anyhow::anyhow!("Unknown enum variant {}", variant.name))?;
+    width.write(index as u64, out)?;
+    variants[index].typ.encode(&variant.value, enum_width, out)
+}
+
+impl IntWidth {
+    pub(crate) fn write(&self, value: u64, out: &mut Vec<u8>) -> anyhow::Result<()> {
+        let too_wide = || anyhow::anyhow!("{} doesn't fit in {}", value, self.name());
+        match self {
+            IntWidth::U8 => out.push(u8::try_from(value).map_err(|_| too_wide())?),
+            IntWidth::U16 => {
+                out.extend_from_slice(&u16::try_from(value).map_err(|_| too_wide())?.to_le_bytes())
+            }
+            IntWidth::U32 => {
+                out.extend_from_slice(&u32::try_from(value).map_err(|_| too_wide())?.to_le_bytes())
+            }
+            IntWidth::U64 => out.extend_from_slice(&value.to_le_bytes()),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        schema::{IntWidth, SchemaNode, SchemaType, SmallVecLen},
+        value::TypedValue,
+    };
+
+    #[test]
+    fn decoded_values_encode_to_the_same_bytes() {
+        let side = SchemaType::TaggedEnum(
+            IntWidth::U16,
+            vec![
+                SchemaNode::new("Bid", SchemaType::Empty),
+                SchemaNode::new(
+                    "Ask",
+                    SchemaNode::new_struct("Ask", vec![("price", SchemaType::U32)]).typ,
+                ),
+            ],
+        );
+        let mut reordered = SchemaNode::new("late", SchemaType::U8);
+        reordered.serialization_order = Some(0);
+        let mut schema = SchemaNode::new_struct(
+            "Order",
+            vec![
+                ("owner", SchemaType::Pubkey),
+                ("side", side),
+                ("sizes", SchemaType::vec(SchemaType::U64)),
+                ("memo", SchemaType::option(SchemaType::String)),
+                (
+                    "tags",
+                    SchemaType::SmallVec(SmallVecLen::U8, Box::new(SchemaType::U8)),
+                ),
+                ("seed", SchemaType::array(2, SchemaType::I16)),
+            ],
+        );
+        let SchemaType::Struct(fields) = &mut schema.typ else {
+            unreachable!()
+        };
+        fields.push(reordered);
+        for (index, field) in fields.iter_mut().take(6).enumerate() {
+            field.serialization_order = Some(index as u32 + 1);
+        }
+
+        let mut data = vec![9];
+        data.extend_from_slice(&[3u8; 32]);
+        data.extend_from_slice(&[1, 0, 42, 0, 0, 0]);
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&7u64.to_le_bytes());
+        data.extend_from_slice(&[1, 2, 0, 0, 0, b'h', b'i']);
+        data.extend_from_slice(&[2, 0xaa, 0xbb]);
+        data.extend_from_slice(&[0xff, 0xff, 5, 0]);
+
+        let value = schema
+            .typ
+            .deserialize_bytes(&mut data.as_slice(), true)
+            .unwrap();
+        assert_eq!(schema.typ.serialize_value(&value).unwrap(), data);
+    }
+
+    #[test]
+    fn mismatched_values_are_rejected() {
+        assert!(SchemaType::U16.serialize_value(&TypedValue::U8(1)).is_err());
+        assert!(SchemaType::array(3, SchemaType::U8)
+            .serialize_value(&TypedValue::Bytes(vec![1, 2]))
+            .is_err());
+        let schema = SchemaNode::new_struct("Pair", vec![("a", SchemaType::U8)]);
+        assert!(schema
+            .typ
+            .serialize_value(&TypedValue::Struct(vec![]))
+            .is_err());
+    }
+}
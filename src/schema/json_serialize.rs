@@ -3,22 +3,52 @@ use serde::{
     Serialize,
 };
 
-use crate::schema::{SchemaNode, SchemaType, SmallVecLen};
+use crate::{
+    schema::{SchemaNode, SchemaType, SmallVecLen},
+    value::{RenderOptions, Rendered},
+};
+
+impl SchemaNode {
+    pub fn render<'a>(&'a self, options: &'a RenderOptions) -> Rendered<'a, SchemaNode> {
+        Rendered {
+            value: self,
+            options,
+        }
+    }
+}
+
+impl SchemaType {
+    pub fn render<'a>(&'a self, options: &'a RenderOptions) -> Rendered<'a, SchemaType> {
+        Rendered {
+            value: self,
+            options,
+        }
+    }
+}
 
 impl Serialize for SchemaNode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.render(&RenderOptions::default()).serialize(serializer)
+    }
+}
+
+impl Serialize for Rendered<'_, SchemaNode> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
         let mut state = serializer.serialize_map(Some(2))?;
-        state.serialize_entry("name", &self.name)?;
-        state.serialize_entry("type", &self.typ)?;
+        state.serialize_entry("name", &self.value.name)?;
+        state.serialize_entry("type", &self.value.typ.render(self.options))?;
         state.end()
     }
 }
 
 impl SchemaType {
-    fn typename(&self) -> &str {
+    pub(crate) fn typename(&self) -> &str {
         match self {
             SchemaType::Empty => "empty",
             SchemaType::Pubkey => "pubkey",
@@ -44,6 +74,18 @@ impl SchemaType {
             SchemaType::Struct(_) => "struct",
             SchemaType::SmallVec(_, _) => "smallvec",
             SchemaType::RemainingBytes => "bytes_remaining",
+            SchemaType::RemainingVec(_) => "remaining_vec",
+            SchemaType::UnixTimestamp => "unix_timestamp",
+            SchemaType::Duration => "duration",
+            SchemaType::TaggedEnum(_, _) => "tagged_enum",
+            SchemaType::FlaggedOption { .. } => "flagged_option",
+            SchemaType::LenientOption(_) => "lenient_option",
+            SchemaType::Utf8Remaining => "utf8_remaining",
+            SchemaType::RawString => "raw_string",
+            SchemaType::Tlv(_) => "tlv",
+            SchemaType::CountedArray { .. } => "counted_array",
+            SchemaType::Map(_, _) => "map",
+            SchemaType::Bitmap(_) => "bitmap",
         }
     }
 }
@@ -53,59 +95,185 @@ impl Serialize for SchemaType {
     where
         S: serde::Serializer,
     {
-        match &self {
+        self.render(&RenderOptions::default()).serialize(serializer)
+    }
+}
+
+impl Serialize for Rendered<'_, SchemaType> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let options = self.options;
+        match self.value {
             SchemaType::Empty => serializer.serialize_none(),
             SchemaType::Option(inner_type) => {
                 let mut state = serializer.serialize_map(Some(1))?;
-                state.serialize_entry("type:option", inner_type)?;
+                state.serialize_entry("type:option", &inner_type.render(options))?;
+                state.end()
+            }
+            SchemaType::FlaggedOption { flag_width, inner } => {
+                // { "type:flagged_option": { "flag": "u32", "type": <SchemaType> } }
+                #[derive(serde::Serialize)]
+                struct FlaggedOptionRepr<'a> {
+                    flag: &'a str,
+                    #[serde(rename = "type")]
+                    typ: Rendered<'a, SchemaType>,
+                }
+                let mut state = serializer.serialize_map(Some(1))?;
+                state.serialize_entry(
+                    "type:flagged_option",
+                    &FlaggedOptionRepr {
+                        flag: flag_width.name(),
+                        typ: inner.render(options),
+                    },
+                )?;
+                state.end()
+            }
+            SchemaType::LenientOption(inner_type) => {
+                let mut state = serializer.serialize_map(Some(1))?;
+                state.serialize_entry("type:lenient_option", &inner_type.render(options))?;
                 state.end()
             }
             SchemaType::Array(size, inner_type) => {
                 let mut state = serializer.serialize_struct("type:array", 2)?;
                 state.serialize_field("size", size)?;
-                state.serialize_field("type", inner_type)?;
+                state.serialize_field("type", &inner_type.render(options))?;
+                state.end()
+            }
+            SchemaType::Bitmap(bits) => {
+                let mut state = serializer.serialize_map(Some(1))?;
+                state.serialize_entry("type:bitmap", bits)?;
                 state.end()
             }
             SchemaType::Tuple(types) => {
                 let mut state = serializer.serialize_map(Some(1))?;
-                state.serialize_entry("type:tuple", types)?;
+                let types: Vec<_> = types.iter().map(|typ| typ.render(options)).collect();
+                state.serialize_entry("type:tuple", &types)?;
                 state.end()
             }
             SchemaType::Vec(inner_type) => {
                 let mut state = serializer.serialize_map(Some(1))?;
-                state.serialize_entry("type:vec", inner_type)?;
+                state.serialize_entry("type:vec", &inner_type.render(options))?;
+                state.end()
+            }
+            SchemaType::RemainingVec(inner_type) => {
+                let mut state = serializer.serialize_map(Some(1))?;
+                state.serialize_entry("type:remaining_vec", &inner_type.render(options))?;
                 state.end()
             }
             SchemaType::Struct(fields) => {
                 let mut state = serializer.serialize_map(Some(fields.len()))?;
                 for field in fields {
-                    state.serialize_entry(&field.name, &field.typ)?;
+                    state.serialize_entry(&field.name, &field.typ.render(options))?;
                 }
                 state.end()
             }
             SchemaType::Enum(variants) => {
                 let mut state = serializer.serialize_map(Some(1))?;
-                state.serialize_entry("type:enum", &Variants { variants })?;
+                state.serialize_entry("type:enum", &Variants { variants, options })?;
+                state.end()
+            }
+            SchemaType::TaggedEnum(width, variants) => {
+                // { "type:tagged_enum": { "tag": "u32", "variants": { ... } } }
+                #[derive(serde::Serialize)]
+                struct TaggedEnumRepr<'a> {
+                    tag: &'a str,
+                    variants: Variants<'a>,
+                }
+                let mut state = serializer.serialize_map(Some(1))?;
+                state.serialize_entry(
+                    "type:tagged_enum",
+                    &TaggedEnumRepr {
+                        tag: width.name(),
+                        variants: Variants { variants, options },
+                    },
+                )?;
                 state.end()
             }
             SchemaType::SmallVec(len_ty, elem) => {
-                use serde::ser::SerializeMap;
                 let mut map = serializer.serialize_map(Some(1))?;
-                // Represent as: { "type:smallvec": { "len": "u8|u16", "elem": <SchemaType> } }
+                // Represent as: { "type:smallvec": { "len": "u8|u16|shortU16", "elem": <SchemaType> } }
                 #[derive(serde::Serialize)]
                 struct SmallVecRepr<'a> {
                     len: &'a str,
                     #[serde(rename = "elem")]
-                    elem: &'a SchemaType,
+                    elem: Rendered<'a, SchemaType>,
                 }
                 let len_str = match len_ty {
                     SmallVecLen::U8 => "u8",
                     SmallVecLen::U16 => "u16",
+                    SmallVecLen::ShortU16 => "shortU16",
                 };
-                map.serialize_entry("type:smallvec", &SmallVecRepr { len: len_str, elem })?;
+                map.serialize_entry(
+                    "type:smallvec",
+                    &SmallVecRepr {
+                        len: len_str,
+                        elem: elem.render(options),
+                    },
+                )?;
                 map.end()
             }
-            _ => Serialize::serialize(&self.typename(), serializer),
+            SchemaType::Tlv(entries) => {
+                // { "type:tlv": { "<type>": { "name": ..., "type": ... } } }
+                struct Entries<'a> {
+                    entries: &'a std::collections::BTreeMap<u16, SchemaNode>,
+                    options: &'a RenderOptions,
+                }
+                impl Serialize for Entries<'_> {
+                    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+                    where
+                        S: serde::Serializer,
+                    {
+                        let mut state = serializer.serialize_map(Some(self.entries.len()))?;
+                        for (tag, node) in self.entries {
+                            state.serialize_entry(tag, &node.render(self.options))?;
+                        }
+                        state.end()
+                    }
+                }
+                let mut state = serializer.serialize_map(Some(1))?;
+                state.serialize_entry("type:tlv", &Entries { entries, options })?;
+                state.end()
+            }
+            SchemaType::CountedArray {
+                count_from,
+                element,
+            } => {
+                // { "type:counted_array": { "count_from": "numEntries", "element": <SchemaType> } }
+                #[derive(serde::Serialize)]
+                struct CountedArrayRepr<'a> {
+                    count_from: &'a str,
+                    element: Rendered<'a, SchemaType>,
+                }
+                let mut state = serializer.serialize_map(Some(1))?;
+                state.serialize_entry(
+                    "type:counted_array",
+                    &CountedArrayRepr {
+                        count_from,
+                        element: element.render(options),
+                    },
+                )?;
+                state.end()
+            }
+            SchemaType::Map(key, value) => {
+                // { "type:map": { "key": <SchemaType>, "value": <SchemaType> } }
+                #[derive(serde::Serialize)]
+                struct MapRepr<'a> {
+                    key: Rendered<'a, SchemaType>,
+                    value: Rendered<'a, SchemaType>,
+                }
+                let mut state = serializer.serialize_map(Some(1))?;
+                state.serialize_entry(
+                    "type:map",
+                    &MapRepr {
+                        key: key.render(options),
+                        value: value.render(options),
+                    },
+                )?;
+                state.end()
+            }
+            other => Serialize::serialize(&other.typename(), serializer),
         }
     }
 }
@@ -113,6 +281,7 @@ impl Serialize for SchemaType {
 /// Helper struct for serializing enum variants
 struct Variants<'a> {
     variants: &'a Vec<SchemaNode>,
+    options: &'a RenderOptions,
 }
 
 impl Serialize for Variants<'_> {
@@ -120,9 +289,22 @@ impl Serialize for Variants<'_> {
     where
         S: serde::Serializer,
     {
+        /// `{ "index": <discriminant>, "type": <SchemaType> }`
+        #[derive(serde::Serialize)]
+        struct IndexedVariant<'a> {
+            index: usize,
+            #[serde(rename = "type")]
+            typ: Rendered<'a, SchemaType>,
+        }
+
         let mut variants_state = serializer.serialize_map(Some(self.variants.len()))?;
-        for variant in self.variants {
-            variants_state.serialize_entry(&variant.name, &variant.typ)?;
+        for (index, variant) in self.variants.iter().enumerate() {
+            let typ = variant.typ.render(self.options);
+            if self.options.enum_variant_indices {
+                variants_state.serialize_entry(&variant.name, &IndexedVariant { index, typ })?;
+            } else {
+                variants_state.serialize_entry(&variant.name, &typ)?;
+            }
         }
         variants_state.end()
     }
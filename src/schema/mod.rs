@@ -1,27 +1,49 @@
+#[cfg(feature = "arena")]
+mod arena_deserialize;
+mod audit;
 mod bytes_deserialize;
+mod bytes_serialize;
 mod json_serialize;
 mod on_chain_serialization;
-use borsh::{BorshDeserialize, BorshSerialize};
+mod shorthand;
+mod validate_json;
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::Arc,
+};
 
-#[derive(Debug, Clone, BorshSerialize, BorshDeserialize, PartialEq, Eq)]
+#[cfg(feature = "arena")]
+pub use arena_deserialize::{ArenaNode, ArenaValue};
+pub use audit::AuditTree;
+pub use bytes_deserialize::DecodeOptions;
+pub(crate) use on_chain_serialization::NodeLayout;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[repr(C)]
 pub struct SchemaNode {
-    pub name: String,
+    /// shared with the `ValueNode`s decoded from this node when decoding
+    /// with `DecodeOptions::intern_names`
+    pub name: Arc<str>,
     pub typ: SchemaType,
     pub is_hidden: bool,
+    /// Position of this field in the serialized struct, when it differs from
+    /// the declaration order (e.g. ported C structs). Fields without one keep
+    /// their declaration index.
+    pub serialization_order: Option<u32>,
 }
 
 impl SchemaNode {
-    pub fn new(name: impl Into<String>, typ: SchemaType) -> Self {
+    pub fn new(name: impl Into<Arc<str>>, typ: SchemaType) -> Self {
         Self {
             name: name.into(),
             typ,
             is_hidden: false,
+            serialization_order: None,
         }
     }
     pub fn new_struct(
-        name: impl Into<String>,
-        fields: Vec<(impl Into<String>, SchemaType)>,
+        name: impl Into<Arc<str>>,
+        fields: Vec<(impl Into<Arc<str>>, SchemaType)>,
     ) -> Self {
         let types = SchemaType::Struct(
             fields
@@ -31,12 +53,82 @@ impl SchemaNode {
         );
         Self::new(name, types)
     }
+
+    /// Names of the types in `defined` (e.g. `OnChainIdl::types`) used
+    /// anywhere below this schema. Resolved schemas hold defined types by
+    /// value, so structs and enums are matched to them by layout; defined
+    /// types with identical layouts can't be told apart, and all of them are
+    /// reported when any one is used.
+    pub fn referenced_types(&self, defined: &[SchemaNode]) -> HashSet<String> {
+        let mut names = HashSet::new();
+        let mut pending = self.typ.nested_types();
+        while let Some(typ) = pending.pop() {
+            if matches!(
+                typ,
+                SchemaType::Struct(_) | SchemaType::Enum(_) | SchemaType::TaggedEnum(_, _)
+            ) {
+                names.extend(
+                    defined
+                        .iter()
+                        .filter(|node| node.typ == *typ)
+                        .map(|node| node.name.to_string()),
+                );
+            }
+            pending.extend(typ.nested_types());
+        }
+        names
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, borsh::BorshDeserialize, borsh::BorshSerialize)]
 pub enum SmallVecLen {
     U8,
     U16,
+    /// Solana's compact-u16 ("shortvec"): 7 bits per byte, low bits first,
+    /// with the high bit set on every byte but the last; at most 3 bytes
+    ShortU16,
+}
+
+/// Width of a little-endian integer tag, such as a non-Borsh enum discriminant
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, borsh::BorshDeserialize, borsh::BorshSerialize,
+)]
+pub enum IntWidth {
+    #[default]
+    U8,
+    U16,
+    U32,
+    U64,
+}
+
+impl IntWidth {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "u8" => Some(Self::U8),
+            "u16" => Some(Self::U16),
+            "u32" => Some(Self::U32),
+            "u64" => Some(Self::U64),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::U8 => "u8",
+            Self::U16 => "u16",
+            Self::U32 => "u32",
+            Self::U64 => "u64",
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            Self::U8 => 1,
+            Self::U16 => 2,
+            Self::U32 => 4,
+            Self::U64 => 8,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -66,6 +158,46 @@ pub enum SchemaType {
     Enum(Vec<SchemaNode>),
     SmallVec(SmallVecLen, Box<SchemaType>),
     RemainingBytes,
+    /// elements repeated until the end of the buffer, without a length prefix
+    RemainingVec(Box<SchemaType>),
+    /// `i64` seconds since the Unix epoch
+    UnixTimestamp,
+    /// `i64` seconds
+    Duration,
+    /// enum whose discriminant is an integer of the given width rather than
+    /// Borsh's `u8`
+    TaggedEnum(IntWidth, Vec<SchemaNode>),
+    /// option whose presence flag is an integer of the given width, with the
+    /// value following only when the flag is non-zero
+    FlaggedOption {
+        flag_width: IntWidth,
+        inner: Box<SchemaType>,
+    },
+    /// Borsh option that tolerates tags other than 0/1, reading them as
+    /// `None`; for fields known to hold garbage tags
+    LenientOption(Box<SchemaType>),
+    /// the rest of the buffer as UTF-8 text (memos, URIs), replacing invalid
+    /// sequences rather than failing
+    Utf8Remaining,
+    /// Borsh-style `u32` length-prefixed text that isn't necessarily UTF-8
+    /// (Latin-1, raw bytes), replacing invalid sequences rather than failing
+    RawString,
+    /// `(type: u16, length: u16, value)` entries until the end of the buffer
+    /// (e.g. token-2022 extensions), each value decoded with the node
+    /// registered for its type; the node name becomes the field name
+    Tlv(BTreeMap<u16, SchemaNode>),
+    /// packed elements whose count is the integer in an earlier field of the
+    /// same struct, named by `count_from` (a dotted path for nested fields).
+    /// Only valid as a struct field.
+    CountedArray {
+        count_from: String,
+        element: Box<SchemaType>,
+    },
+    /// Borsh `HashMap`/`BTreeMap`: a `u32` count followed by key-value pairs
+    Map(Box<SchemaType>, Box<SchemaType>),
+    /// this many one-bit flags packed least significant bit first into
+    /// whole bytes, decoded as a list of bools
+    Bitmap(usize),
 }
 
 impl SchemaType {
@@ -80,16 +212,309 @@ impl SchemaType {
     pub fn array(len: usize, typ: SchemaType) -> Self {
         Self::Array(len, Box::new(typ))
     }
+
+    /// Number of bytes every value of this type occupies, or `None` if the
+    /// encoded size depends on the data.
+    pub fn fixed_size(&self) -> Option<usize> {
+        self.fixed_size_with(IntWidth::U8)
+    }
+
+    /// `fixed_size` with the discriminant of untagged `Enum`s read as
+    /// `enum_width`, see `DecodeOptions::enum_disc_width`
+    pub fn fixed_size_with(&self, enum_width: IntWidth) -> Option<usize> {
+        match self {
+            SchemaType::Empty => Some(0),
+            SchemaType::Pubkey => Some(32),
+            SchemaType::I8 | SchemaType::U8 | SchemaType::Bool => Some(1),
+            SchemaType::I16 | SchemaType::U16 => Some(2),
+            SchemaType::I32 | SchemaType::U32 | SchemaType::F32 => Some(4),
+            SchemaType::I64
+            | SchemaType::U64
+            | SchemaType::F64
+            | SchemaType::UnixTimestamp
+            | SchemaType::Duration => Some(8),
+            SchemaType::I128 | SchemaType::U128 => Some(16),
+            SchemaType::Array(len, typ) => typ.fixed_size_with(enum_width)?.checked_mul(*len),
+            SchemaType::Bitmap(bits) => Some(bits.div_ceil(8)),
+            SchemaType::Tuple(types) => types
+                .iter()
+                .map(|typ| typ.fixed_size_with(enum_width))
+                .sum(),
+            SchemaType::Struct(fields) => fields
+                .iter()
+                .map(|field| field.typ.fixed_size_with(enum_width))
+                .sum(),
+            SchemaType::Enum(variants) => fixed_enum_size(enum_width, variants, enum_width),
+            SchemaType::TaggedEnum(width, variants) => {
+                fixed_enum_size(*width, variants, enum_width)
+            }
+            SchemaType::String
+            | SchemaType::RawString
+            | SchemaType::Option(_)
+            | SchemaType::FlaggedOption { .. }
+            | SchemaType::LenientOption(_)
+            | SchemaType::Vec(_)
+            | SchemaType::SmallVec(_, _)
+            | SchemaType::RemainingBytes
+            | SchemaType::Utf8Remaining
+            | SchemaType::Tlv(_)
+            | SchemaType::CountedArray { .. }
+            | SchemaType::Map(_, _)
+            | SchemaType::RemainingVec(_) => None,
+        }
+    }
+
+    /// Fewest bytes any value of this type can occupy: the full size of fixed
+    /// types, and just the length prefix or tag of variable ones.
+    pub fn min_size(&self) -> usize {
+        self.min_size_with(IntWidth::U8)
+    }
+
+    /// `min_size` with the discriminant of untagged `Enum`s read as
+    /// `enum_width`, see `DecodeOptions::enum_disc_width`
+    pub fn min_size_with(&self, enum_width: IntWidth) -> usize {
+        if let Some(size) = self.fixed_size_with(enum_width) {
+            return size;
+        }
+        match self {
+            SchemaType::String
+            | SchemaType::RawString
+            | SchemaType::Vec(_)
+            | SchemaType::Map(_, _) => 4,
+            SchemaType::Option(_) | SchemaType::LenientOption(_) => 1,
+            SchemaType::FlaggedOption { flag_width, .. } => flag_width.size(),
+            SchemaType::SmallVec(SmallVecLen::U8, _) => 1,
+            SchemaType::SmallVec(SmallVecLen::U16, _) => 2,
+            SchemaType::SmallVec(SmallVecLen::ShortU16, _) => 1,
+            SchemaType::Array(len, typ) => typ.min_size_with(enum_width).saturating_mul(*len),
+            SchemaType::Tuple(types) => types.iter().map(|typ| typ.min_size_with(enum_width)).sum(),
+            SchemaType::Struct(fields) => fields
+                .iter()
+                .map(|field| field.typ.min_size_with(enum_width))
+                .sum(),
+            SchemaType::Enum(variants) => {
+                enum_width.size() + min_variant_size(variants, enum_width)
+            }
+            SchemaType::TaggedEnum(width, variants) => {
+                width.size() + min_variant_size(variants, enum_width)
+            }
+            _ => 0,
+        }
+    }
+
+    /// Types directly inside this one: elements, fields, variant payloads
+    fn nested_types(&self) -> Vec<&SchemaType> {
+        match self {
+            SchemaType::Option(typ)
+            | SchemaType::LenientOption(typ)
+            | SchemaType::FlaggedOption { inner: typ, .. }
+            | SchemaType::Array(_, typ)
+            | SchemaType::Vec(typ)
+            | SchemaType::SmallVec(_, typ)
+            | SchemaType::RemainingVec(typ)
+            | SchemaType::CountedArray { element: typ, .. } => vec![typ],
+            SchemaType::Map(key, value) => vec![key, value],
+            SchemaType::Tuple(types) => types.iter().collect(),
+            SchemaType::Struct(nodes)
+            | SchemaType::Enum(nodes)
+            | SchemaType::TaggedEnum(_, nodes) => nodes.iter().map(|node| &node.typ).collect(),
+            SchemaType::Tlv(entries) => entries.values().map(|node| &node.typ).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Variants of an `Enum` or `TaggedEnum`
+    fn enum_variants(&self) -> Option<&[SchemaNode]> {
+        match self {
+            SchemaType::Enum(variants) | SchemaType::TaggedEnum(_, variants) => Some(variants),
+            _ => None,
+        }
+    }
+
+    /// Discriminant of the variant called `name`, for enums
+    pub fn enum_variant_index(&self, name: &str) -> Option<usize> {
+        self.enum_variants()?
+            .iter()
+            .position(|variant| *variant.name == *name)
+    }
+
+    /// Payload schema of the variant called `name` (`Empty` for unit
+    /// variants), for enums
+    pub fn enum_variant_schema(&self, name: &str) -> Option<&SchemaType> {
+        self.enum_variants()?
+            .iter()
+            .find(|variant| *variant.name == *name)
+            .map(|variant| &variant.typ)
+    }
+
+    /// Resolves a JSON enum value to its discriminant, payload schema and
+    /// payload. Accepts a bare variant name (`"Ask"`), the externally tagged
+    /// `{ "Ask": <payload> }` and the internally tagged
+    /// `{ "name": "Ask", "value": <payload> }` that decoded values render as.
+    pub fn enum_variant_from_json<'a>(
+        &self,
+        value: &'a serde_json::Value,
+    ) -> Option<(usize, &SchemaType, Option<&'a serde_json::Value>)> {
+        let (name, payload) = match value {
+            serde_json::Value::String(name) => (name.as_str(), None),
+            serde_json::Value::Object(object) => match object.get("name") {
+                Some(serde_json::Value::String(name)) if object.len() <= 2 => {
+                    (name.as_str(), object.get("value"))
+                }
+                _ if object.len() == 1 => {
+                    let (name, payload) = object.iter().next()?;
+                    (name.as_str(), Some(payload))
+                }
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let index = self.enum_variant_index(name)?;
+        Some((index, self.enum_variant_schema(name)?, payload))
+    }
+}
+
+fn min_variant_size(variants: &[SchemaNode], enum_width: IntWidth) -> usize {
+    variants
+        .iter()
+        .map(|variant| variant.typ.min_size_with(enum_width))
+        .min()
+        .unwrap_or_default()
+}
+
+/// An enum is only fixed if every variant has the same payload size
+fn fixed_enum_size(tag: IntWidth, variants: &[SchemaNode], enum_width: IntWidth) -> Option<usize> {
+    let mut sizes = variants
+        .iter()
+        .map(|variant| variant.typ.fixed_size_with(enum_width));
+    let first = sizes.next()??;
+    sizes
+        .all(|size| size == Some(first))
+        .then_some(tag.size() + first)
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
-        schema::{SchemaNode, SchemaType},
-        value::{TypedValue, ValueNode},
+        schema::{SchemaNode, SchemaType, SmallVecLen},
+        value::{RenderOptions, TypedValue, ValueNode},
     };
     use borsh::{BorshDeserialize, BorshSerialize};
 
+    #[test]
+    fn enum_variants_are_looked_up_by_name() {
+        let side = SchemaType::Enum(vec![
+            SchemaNode::new("Variant1", SchemaType::Empty),
+            SchemaNode::new("Variant2", SchemaType::U64),
+            SchemaNode::new("Variant3", SchemaType::Empty),
+        ]);
+        assert_eq!(side.enum_variant_index("Variant2"), Some(1));
+        assert_eq!(side.enum_variant_schema("Variant2"), Some(&SchemaType::U64));
+        assert_eq!(side.enum_variant_index("Variant4"), None);
+        assert_eq!(SchemaType::U8.enum_variant_index("Variant2"), None);
+
+        let payload = serde_json::json!(5);
+        for value in [
+            serde_json::json!({ "Variant2": 5 }),
+            serde_json::json!({ "name": "Variant2", "value": 5 }),
+        ] {
+            assert_eq!(
+                side.enum_variant_from_json(&value),
+                Some((1, &SchemaType::U64, Some(&payload)))
+            );
+        }
+        assert_eq!(
+            side.enum_variant_from_json(&serde_json::json!("Variant3")),
+            Some((2, &SchemaType::Empty, None))
+        );
+    }
+
+    #[test]
+    fn referenced_types_are_found_by_layout() {
+        let fees = SchemaNode::new_struct(
+            "Fees",
+            vec![("maker", SchemaType::I16), ("taker", SchemaType::U16)],
+        );
+        let market_size_params = SchemaNode::new_struct(
+            "MarketSizeParams",
+            vec![
+                ("bidsSize", SchemaType::U64),
+                ("asksSize", SchemaType::U64),
+                ("fees", SchemaType::vec(fees.typ.clone())),
+            ],
+        );
+        let unused = SchemaNode::new_struct("Unused", vec![("flag", SchemaType::Bool)]);
+        let schema = SchemaNode::new_struct(
+            "InitializeParams",
+            vec![
+                ("marketSizeParams", market_size_params.typ.clone()),
+                ("takerFeeBps", SchemaType::U16),
+            ],
+        );
+
+        let defined = [fees, market_size_params, unused];
+        let names = schema.referenced_types(&defined);
+        assert_eq!(
+            names,
+            ["MarketSizeParams", "Fees"]
+                .into_iter()
+                .map(String::from)
+                .collect()
+        );
+    }
+
+    #[test]
+    fn referenced_types_report_every_type_sharing_a_layout() {
+        let deposit = SchemaNode::new_struct("DepositParams", vec![("amount", SchemaType::U64)]);
+        let withdraw = SchemaNode::new_struct("WithdrawParams", vec![("amount", SchemaType::U64)]);
+        let schema = SchemaNode::new_struct("Deposit", vec![("params", deposit.typ.clone())]);
+
+        let names = schema.referenced_types(&[deposit, withdraw]);
+        assert_eq!(
+            names,
+            ["DepositParams", "WithdrawParams"]
+                .into_iter()
+                .map(String::from)
+                .collect()
+        );
+    }
+
+    #[test]
+    fn fixed_size_of_schemas() {
+        assert_eq!(SchemaType::Pubkey.fixed_size(), Some(32));
+        assert_eq!(SchemaType::array(4, SchemaType::U64).fixed_size(), Some(32));
+        assert_eq!(
+            SchemaType::Tuple(vec![SchemaType::U8, SchemaType::I128]).fixed_size(),
+            Some(17)
+        );
+        assert_eq!(
+            SchemaType::Enum(vec![
+                SchemaNode::new("A", SchemaType::U32),
+                SchemaNode::new("B", SchemaType::F32),
+            ])
+            .fixed_size(),
+            Some(5)
+        );
+        assert_eq!(
+            SchemaType::Enum(vec![
+                SchemaNode::new("A", SchemaType::Empty),
+                SchemaNode::new("B", SchemaType::U8),
+            ])
+            .fixed_size(),
+            None
+        );
+        assert_eq!(
+            SchemaType::TaggedEnum(
+                crate::schema::IntWidth::U32,
+                vec![SchemaNode::new("A", SchemaType::U32)]
+            )
+            .fixed_size(),
+            Some(8)
+        );
+        assert_eq!(SchemaType::option(SchemaType::U8).fixed_size(), None);
+        assert_eq!(SchemaType::vec(SchemaType::U8).fixed_size(), None);
+    }
+
     #[test]
     fn identical_after_serialization() {
         let market_size_params_struct = SchemaNode::new_struct(
@@ -121,6 +546,89 @@ mod tests {
         assert_eq!(schema, deserialized);
     }
 
+    #[test]
+    fn remaining_bytes_survive_serialization() {
+        let schema = SchemaNode::new_struct(
+            "Memo",
+            vec![
+                ("kind", SchemaType::U8),
+                ("rest", SchemaType::RemainingBytes),
+            ],
+        );
+        let serialized = schema.try_to_vec().unwrap();
+        assert_eq!(SchemaNode::try_from_slice(&serialized).unwrap(), schema);
+
+        // tag 23, no payload
+        let serialized = SchemaType::RemainingBytes.try_to_vec().unwrap();
+        assert_eq!(serialized, 23u16.to_le_bytes());
+    }
+
+    #[test]
+    fn smallvec_len_survives_serialization_for_every_width() {
+        for len_ty in [SmallVecLen::U8, SmallVecLen::U16, SmallVecLen::ShortU16] {
+            // fails to compile once a width is added, until it's listed above
+            match len_ty {
+                SmallVecLen::U8 | SmallVecLen::U16 | SmallVecLen::ShortU16 => (),
+            }
+            let typ = SchemaType::SmallVec(len_ty, Box::new(SchemaType::Pubkey));
+            let serialized = typ.try_to_vec().unwrap();
+            assert_eq!(serialized[0], 22);
+            let deserialized = SchemaType::deserialize(&mut serialized.as_slice()).unwrap();
+            assert_eq!(deserialized, typ);
+        }
+    }
+
+    #[test]
+    fn array_length_is_only_part_of_the_schema_layout() {
+        let typ = SchemaType::array(1000, SchemaType::U8);
+        let serialized = typ.try_to_vec().unwrap();
+        // u16 tag, then the length as 8 little-endian bytes whatever the
+        // target's pointer width, then the element type
+        let mut expected = 17u16.to_le_bytes().to_vec();
+        expected.extend_from_slice(&1000u64.to_le_bytes());
+        expected.extend_from_slice(&4u16.to_le_bytes());
+        assert_eq!(serialized, expected);
+        assert_eq!(
+            SchemaType::deserialize(&mut serialized.as_slice()).unwrap(),
+            typ
+        );
+
+        // decoding reads no length: the 1000 bytes are the data
+        let data = vec![7u8; 1000];
+        let mut buf = data.as_slice();
+        assert_eq!(
+            typ.deserialize_bytes(&mut buf, false).unwrap(),
+            TypedValue::Bytes(data.clone())
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn collection_lengths_are_serialized_as_u64() {
+        let typ = SchemaType::Tuple(vec![SchemaType::Bool, SchemaType::U16]);
+        let mut expected = 18u16.to_le_bytes().to_vec();
+        expected.extend_from_slice(&2u64.to_le_bytes());
+        expected.extend_from_slice(&15u16.to_le_bytes());
+        expected.extend_from_slice(&6u16.to_le_bytes());
+        assert_eq!(typ.try_to_vec().unwrap(), expected);
+
+        let schema = SchemaNode::new(
+            "Side",
+            SchemaType::Enum(vec![
+                SchemaNode::new("Bid", SchemaType::Empty),
+                SchemaNode::new("Ask", SchemaType::Empty),
+            ]),
+        );
+        let serialized = schema.try_to_vec().unwrap();
+        // name, then the enum tag and its 8-byte variant count
+        let variants_at = 4 + "Side".len() + 2;
+        assert_eq!(serialized[variants_at..variants_at + 8], 2u64.to_le_bytes());
+        assert_eq!(
+            SchemaNode::deserialize(&mut serialized.as_slice()).unwrap(),
+            schema
+        );
+    }
+
     #[repr(C)]
     #[derive(Debug, Clone, BorshSerialize, BorshDeserialize, PartialEq, Eq)]
     pub struct MarketSizeParams {
@@ -170,6 +678,49 @@ mod tests {
         );
     }
 
+    #[test]
+    fn deserialize_single_field() {
+        let market_size_params = MarketSizeParams {
+            bids_size: 100,
+            asks_size: 50,
+            num_seats: 10,
+        };
+
+        let market_size_params_struct = SchemaNode::new_struct(
+            "MarketSizeParams",
+            vec![
+                ("bidsSize", SchemaType::U64),
+                ("asksSize", SchemaType::U64),
+                ("numSeats", SchemaType::U64),
+            ],
+        );
+
+        let serialized_data = market_size_params.try_to_vec().unwrap();
+        assert_eq!(
+            market_size_params_struct
+                .deserialize_field(&serialized_data, "numSeats")
+                .unwrap(),
+            TypedValue::U64(10)
+        );
+
+        let schema = SchemaNode::new_struct(
+            "InitializeParams",
+            vec![
+                ("memo", SchemaType::String),
+                ("marketSizeParams", market_size_params_struct.typ),
+            ],
+        );
+        let mut data = "hi".to_string().try_to_vec().unwrap();
+        data.extend_from_slice(&serialized_data);
+        assert_eq!(
+            schema
+                .deserialize_field(&data, "marketSizeParams.asksSize")
+                .unwrap(),
+            TypedValue::U64(50)
+        );
+        assert!(schema.deserialize_field(&data, "missing").is_err());
+    }
+
     #[test]
     fn json_serialization() {
         let market_size_params_struct = SchemaNode::new_struct(
@@ -219,6 +770,42 @@ mod tests {
         let serialized = serde_json::to_string_pretty(&schema).unwrap();
         println!("{}", serialized);
     }
+    #[test]
+    fn schema_json_can_list_enum_variant_indices() {
+        let schema = SchemaNode::new(
+            "Side",
+            SchemaType::Enum(vec![
+                SchemaNode::new("Bid", SchemaType::Empty),
+                SchemaNode::new("Ask", SchemaType::U64),
+                SchemaNode::new("Cancel", SchemaType::Pubkey),
+            ]),
+        );
+
+        assert_eq!(
+            serde_json::to_value(&schema).unwrap(),
+            serde_json::json!({
+                "name": "Side",
+                "type": {"type:enum": {"Bid": null, "Ask": "u64", "Cancel": "pubkey"}}
+            })
+        );
+
+        let options = RenderOptions {
+            enum_variant_indices: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(schema.render(&options)).unwrap(),
+            serde_json::json!({
+                "name": "Side",
+                "type": {"type:enum": {
+                    "Bid": {"index": 0, "type": null},
+                    "Ask": {"index": 1, "type": "u64"},
+                    "Cancel": {"index": 2, "type": "pubkey"}
+                }}
+            })
+        );
+    }
+
     #[test]
     fn deserialize_vec_u8_as_bytes() {
         use borsh::BorshSerialize;
@@ -255,7 +842,7 @@ mod tests {
         if let crate::value::TypedValue::Struct(fields) = node.value.clone() {
             let tm = fields
                 .iter()
-                .find(|f| f.name == "transactionMessage")
+                .find(|f| &*f.name == "transactionMessage")
                 .expect("missing field");
             match &tm.value {
                 crate::value::TypedValue::Bytes(b) => assert_eq!(b, &vec![1, 2, 3]),
@@ -1,10 +1,65 @@
-use crate::schema::SmallVecLen;
+use std::collections::BTreeMap;
+
+use crate::schema::{IntWidth, SmallVecLen};
 
 use super::{SchemaNode, SchemaType};
 use borsh::{BorshDeserialize, BorshSerialize};
 
+/// How `SchemaNode`s are laid out. Layouts written before
+/// `serialization_order` existed end each node at `is_hidden`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum NodeLayout {
+    WithoutSerializationOrder,
+    Current,
+}
+
+/// Lengths come from the blob being read, so they only size the initial
+/// allocation up to this many elements
+const PREALLOCATE_LIMIT: usize = 1024;
+
+impl BorshDeserialize for SchemaNode {
+    fn deserialize_reader<R: std::io::Read>(reader: &mut R) -> std::io::Result<Self> {
+        Self::deserialize_with_layout(reader, NodeLayout::Current)
+    }
+}
+
+impl BorshSerialize for SchemaNode {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        self.name.as_ref().serialize(writer)?;
+        self.typ.serialize(writer)?;
+        self.is_hidden.serialize(writer)?;
+        self.serialization_order.serialize(writer)
+    }
+}
+
+impl SchemaNode {
+    pub(crate) fn deserialize_with_layout<R: std::io::Read>(
+        reader: &mut R,
+        layout: NodeLayout,
+    ) -> std::io::Result<Self> {
+        Ok(Self {
+            name: String::deserialize_reader(reader)?.into(),
+            typ: SchemaType::deserialize_with_layout(reader, layout)?,
+            is_hidden: bool::deserialize_reader(reader)?,
+            serialization_order: match layout {
+                NodeLayout::WithoutSerializationOrder => None,
+                NodeLayout::Current => Option::<u32>::deserialize_reader(reader)?,
+            },
+        })
+    }
+}
+
 impl BorshDeserialize for SchemaType {
     fn deserialize_reader<R: std::io::Read>(reader: &mut R) -> std::io::Result<Self> {
+        Self::deserialize_with_layout(reader, NodeLayout::Current)
+    }
+}
+
+impl SchemaType {
+    pub(crate) fn deserialize_with_layout<R: std::io::Read>(
+        reader: &mut R,
+        layout: NodeLayout,
+    ) -> std::io::Result<Self> {
         let tag = u16::deserialize_reader(reader)?;
         let output = match tag {
             0 => SchemaType::Empty,
@@ -23,25 +78,29 @@ impl BorshDeserialize for SchemaType {
             13 => SchemaType::F32,
             14 => SchemaType::F64,
             15 => SchemaType::Bool,
-            16 => SchemaType::Option(Box::new(SchemaType::deserialize_reader(reader)?)),
+            16 => SchemaType::Option(Box::new(SchemaType::deserialize_with_layout(
+                reader, layout,
+            )?)),
             17 => SchemaType::Array(
-                usize::deserialize_reader(reader)?,
-                Box::new(SchemaType::deserialize_reader(reader)?),
+                read_len(reader)?,
+                Box::new(SchemaType::deserialize_with_layout(reader, layout)?),
             ),
             18 => {
-                let len = usize::deserialize_reader(reader)?;
-                let mut types = Vec::with_capacity(len);
+                let len = read_len(reader)?;
+                let mut types = Vec::with_capacity(len.min(PREALLOCATE_LIMIT));
                 for _ in 0..len {
-                    types.push(SchemaType::deserialize_reader(reader)?);
+                    types.push(SchemaType::deserialize_with_layout(reader, layout)?);
                 }
                 SchemaType::Tuple(types)
             }
-            19 => SchemaType::Vec(Box::new(SchemaType::deserialize_reader(reader)?)),
+            19 => SchemaType::Vec(Box::new(SchemaType::deserialize_with_layout(
+                reader, layout,
+            )?)),
             20 | 21 => {
-                let len = usize::deserialize_reader(reader)?;
-                let mut nodes = Vec::with_capacity(len);
+                let len = read_len(reader)?;
+                let mut nodes = Vec::with_capacity(len.min(PREALLOCATE_LIMIT));
                 for _ in 0..len {
-                    nodes.push(SchemaNode::deserialize_reader(reader)?);
+                    nodes.push(SchemaNode::deserialize_with_layout(reader, layout)?);
                 }
                 match tag {
                     20 => SchemaType::Struct(nodes),
@@ -51,10 +110,51 @@ impl BorshDeserialize for SchemaType {
             }
             22 => {
                 let len_ty = SmallVecLen::deserialize_reader(reader)?;
-                let elem = SchemaType::deserialize_reader(reader)?;
+                let elem = SchemaType::deserialize_with_layout(reader, layout)?;
                 SchemaType::SmallVec(len_ty, Box::new(elem))
             }
             23 => SchemaType::RemainingBytes,
+            24 => SchemaType::RemainingVec(Box::new(SchemaType::deserialize_with_layout(
+                reader, layout,
+            )?)),
+            25 => SchemaType::UnixTimestamp,
+            26 => SchemaType::Duration,
+            27 => {
+                let width = IntWidth::deserialize_reader(reader)?;
+                let len = read_len(reader)?;
+                let mut nodes = Vec::with_capacity(len.min(PREALLOCATE_LIMIT));
+                for _ in 0..len {
+                    nodes.push(SchemaNode::deserialize_with_layout(reader, layout)?);
+                }
+                SchemaType::TaggedEnum(width, nodes)
+            }
+            28 => SchemaType::FlaggedOption {
+                flag_width: IntWidth::deserialize_reader(reader)?,
+                inner: Box::new(SchemaType::deserialize_with_layout(reader, layout)?),
+            },
+            29 => SchemaType::LenientOption(Box::new(SchemaType::deserialize_with_layout(
+                reader, layout,
+            )?)),
+            30 => SchemaType::Utf8Remaining,
+            31 => {
+                let len = u32::deserialize_reader(reader)?;
+                let mut entries = BTreeMap::new();
+                for _ in 0..len {
+                    let key = u16::deserialize_reader(reader)?;
+                    entries.insert(key, SchemaNode::deserialize_with_layout(reader, layout)?);
+                }
+                SchemaType::Tlv(entries)
+            }
+            32 => SchemaType::CountedArray {
+                count_from: String::deserialize_reader(reader)?,
+                element: Box::new(SchemaType::deserialize_with_layout(reader, layout)?),
+            },
+            33 => SchemaType::Map(
+                Box::new(SchemaType::deserialize_with_layout(reader, layout)?),
+                Box::new(SchemaType::deserialize_with_layout(reader, layout)?),
+            ),
+            34 => SchemaType::Bitmap(read_len(reader)?),
+            35 => SchemaType::RawString,
             _ => {
                 return Err(std::io::Error::new(
                     std::io::ErrorKind::InvalidData,
@@ -94,33 +194,57 @@ impl BorshSerialize for SchemaType {
             SchemaType::Enum(_) => 21,
             SchemaType::SmallVec(_, _) => 22,
             SchemaType::RemainingBytes => 23,
+            SchemaType::RemainingVec(_) => 24,
+            SchemaType::UnixTimestamp => 25,
+            SchemaType::Duration => 26,
+            SchemaType::TaggedEnum(_, _) => 27,
+            SchemaType::FlaggedOption { .. } => 28,
+            SchemaType::LenientOption(_) => 29,
+            SchemaType::Utf8Remaining => 30,
+            SchemaType::Tlv(_) => 31,
+            SchemaType::CountedArray { .. } => 32,
+            SchemaType::Map(_, _) => 33,
+            SchemaType::Bitmap(_) => 34,
+            SchemaType::RawString => 35,
         };
         BorshSerialize::serialize(&tag, writer)?;
         match self {
-            SchemaType::Option(typ) => {
+            SchemaType::Option(typ) | SchemaType::LenientOption(typ) => {
                 BorshSerialize::serialize(typ, writer)?;
             }
             SchemaType::Array(len, typ) => {
-                BorshSerialize::serialize(&len, writer)?;
+                write_len(*len, writer)?;
                 BorshSerialize::serialize(typ, writer)?;
             }
-            SchemaType::Vec(typ) => {
+            SchemaType::Bitmap(bits) => write_len(*bits, writer)?,
+            SchemaType::Vec(typ) | SchemaType::RemainingVec(typ) => {
                 BorshSerialize::serialize(&typ, writer)?;
             }
             SchemaType::Struct(nodes) => {
-                BorshSerialize::serialize(&nodes.len(), writer)?;
+                write_len(nodes.len(), writer)?;
                 for node in nodes {
                     BorshSerialize::serialize(node, writer)?;
                 }
             }
             SchemaType::Enum(variants) => {
-                BorshSerialize::serialize(&variants.len(), writer)?;
+                write_len(variants.len(), writer)?;
+                for variant in variants {
+                    BorshSerialize::serialize(variant, writer)?;
+                }
+            }
+            SchemaType::TaggedEnum(width, variants) => {
+                BorshSerialize::serialize(width, writer)?;
+                write_len(variants.len(), writer)?;
                 for variant in variants {
                     BorshSerialize::serialize(variant, writer)?;
                 }
             }
+            SchemaType::FlaggedOption { flag_width, inner } => {
+                BorshSerialize::serialize(flag_width, writer)?;
+                BorshSerialize::serialize(inner, writer)?;
+            }
             SchemaType::Tuple(types) => {
-                BorshSerialize::serialize(&types.len(), writer)?;
+                write_len(types.len(), writer)?;
                 for typ in types {
                     BorshSerialize::serialize(typ, writer)?;
                 }
@@ -129,8 +253,38 @@ impl BorshSerialize for SchemaType {
                 borsh::BorshSerialize::serialize(len_ty, writer)?;
                 borsh::BorshSerialize::serialize(&**typ, writer)?;
             }
+            SchemaType::Tlv(entries) => {
+                BorshSerialize::serialize(entries, writer)?;
+            }
+            SchemaType::Map(key, value) => {
+                BorshSerialize::serialize(key, writer)?;
+                BorshSerialize::serialize(value, writer)?;
+            }
+            SchemaType::CountedArray {
+                count_from,
+                element,
+            } => {
+                BorshSerialize::serialize(count_from, writer)?;
+                BorshSerialize::serialize(element, writer)?;
+            }
             _ => (),
         }
         Ok(())
     }
 }
+
+/// Lengths and counts are stored as an explicit u64, so the layout doesn't
+/// depend on how Borsh (or the target) sizes a `usize`
+fn write_len<W: std::io::Write>(len: usize, writer: &mut W) -> std::io::Result<()> {
+    BorshSerialize::serialize(&(len as u64), writer)
+}
+
+fn read_len<R: std::io::Read>(reader: &mut R) -> std::io::Result<usize> {
+    let len = u64::deserialize_reader(reader)?;
+    usize::try_from(len).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Length {} doesn't fit in a usize", len),
+        )
+    })
+}
@@ -0,0 +1,156 @@
+use crate::{
+    parse_idl::{primitive_schema_type, split_bracket_array},
+    schema::{SchemaType, SmallVecLen},
+};
+
+impl SchemaType {
+    /// Parses a Rust-like type string such as `"Vec<u64>"`, `"Option<Pubkey>"`,
+    /// `"[u8; 32]"` or `"(u64, Pubkey)"` into a `SchemaType`.
+    pub fn parse_shorthand(s: &str) -> anyhow::Result<SchemaType> {
+        let s = s.trim();
+
+        if let Some((elem, len)) =
+            split_bracket_array(s).map_err(|e| anyhow::anyhow!("{}: {}", s, e))?
+        {
+            let len = len
+                .parse::<usize>()
+                .map_err(|e| anyhow::anyhow!("Invalid array length in {}: {}", s, e))?;
+            return Ok(SchemaType::array(len, Self::parse_shorthand(elem)?));
+        }
+
+        if let Some(inner) = s.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+            if inner.trim().is_empty() {
+                return Ok(SchemaType::Empty);
+            }
+            let types = split_top_level(inner)
+                .into_iter()
+                .map(Self::parse_shorthand)
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            return Ok(SchemaType::Tuple(types));
+        }
+
+        if let Some((outer, inner)) = s
+            .strip_suffix('>')
+            .and_then(|s| s.split_once('<'))
+            .map(|(outer, inner)| (outer.trim(), inner))
+        {
+            let params = split_top_level(inner);
+            return match (outer, params.as_slice()) {
+                ("Vec", [elem]) => Ok(SchemaType::vec(Self::parse_shorthand(elem)?)),
+                ("Option", [elem]) => Ok(SchemaType::option(Self::parse_shorthand(elem)?)),
+                ("Box", [elem]) => Self::parse_shorthand(elem),
+                ("SmallVec", [len, elem]) => {
+                    let len_ty = match *len {
+                        "u8" => SmallVecLen::U8,
+                        "u16" => SmallVecLen::U16,
+                        "shortU16" => SmallVecLen::ShortU16,
+                        other => {
+                            return Err(anyhow::anyhow!("Unsupported SmallVec len type: {}", other))
+                        }
+                    };
+                    Ok(SchemaType::SmallVec(
+                        len_ty,
+                        Box::new(Self::parse_shorthand(elem)?),
+                    ))
+                }
+                _ => Err(anyhow::anyhow!("Unsupported generic type: {}", s)),
+            };
+        }
+
+        match s {
+            "String" => Some(SchemaType::String),
+            other => primitive_schema_type(other),
+        }
+        .ok_or_else(|| anyhow::anyhow!("Unknown type: {}", s))
+    }
+}
+
+impl TryFrom<&str> for SchemaType {
+    type Error = anyhow::Error;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        Self::parse_shorthand(s)
+    }
+}
+
+/// Splits `s` on commas that aren't nested inside `<>`, `()` or `[]`
+fn split_top_level(s: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '<' | '(' | '[' => depth += 1,
+            '>' | ')' | ']' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                parts.push(s[start..i].trim());
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    parts.push(s[start..].trim());
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::schema::{SchemaType, SmallVecLen};
+
+    #[test]
+    fn parses_generic_shorthand() {
+        assert_eq!(
+            SchemaType::parse_shorthand("Vec<u64>").unwrap(),
+            SchemaType::vec(SchemaType::U64)
+        );
+        assert_eq!(
+            SchemaType::parse_shorthand("Option<Pubkey>").unwrap(),
+            SchemaType::option(SchemaType::Pubkey)
+        );
+        assert_eq!(
+            SchemaType::parse_shorthand("Option<Vec<String>>").unwrap(),
+            SchemaType::option(SchemaType::vec(SchemaType::String))
+        );
+        assert_eq!(
+            SchemaType::parse_shorthand("SmallVec<u16, u8>").unwrap(),
+            SchemaType::SmallVec(SmallVecLen::U16, Box::new(SchemaType::U8))
+        );
+    }
+
+    #[test]
+    fn parses_array_shorthand() {
+        assert_eq!(
+            SchemaType::parse_shorthand("[u8; 32]").unwrap(),
+            SchemaType::array(32, SchemaType::U8)
+        );
+        assert_eq!(
+            SchemaType::parse_shorthand("[Pubkey; 2]").unwrap(),
+            SchemaType::array(2, SchemaType::Pubkey)
+        );
+        assert_eq!(
+            SchemaType::try_from("[Option<u16>; 4]").unwrap(),
+            SchemaType::array(4, SchemaType::option(SchemaType::U16))
+        );
+    }
+
+    #[test]
+    fn parses_tuple_shorthand() {
+        assert_eq!(
+            SchemaType::parse_shorthand("(u64, Pubkey)").unwrap(),
+            SchemaType::Tuple(vec![SchemaType::U64, SchemaType::Pubkey])
+        );
+        assert_eq!(
+            SchemaType::parse_shorthand("(Vec<u8>, Option<(u8, bool)>)").unwrap(),
+            SchemaType::Tuple(vec![
+                SchemaType::vec(SchemaType::U8),
+                SchemaType::option(SchemaType::Tuple(vec![SchemaType::U8, SchemaType::Bool])),
+            ])
+        );
+    }
+
+    #[test]
+    fn unknown_shorthand_is_an_error() {
+        assert!(SchemaType::parse_shorthand("Foo").is_err());
+        assert!(SchemaType::parse_shorthand("HashMap<u8, u8>").is_err());
+    }
+}
@@ -0,0 +1,346 @@
+use std::str::FromStr;
+
+use serde_json::Value;
+use solana_program::pubkey::Pubkey;
+
+use crate::schema::{SchemaNode, SchemaType, SmallVecLen};
+
+impl SchemaType {
+    /// Checks that `value` (e.g. user-supplied instruction args) conforms to
+    /// this schema, in the same JSON shape decoded values are rendered in.
+    /// Every mismatch is reported, prefixed with the path of the offending
+    /// field.
+    pub fn validate_json(&self, value: &Value) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        self.validate_at(value, "$", &mut errors);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn validate_at(&self, value: &Value, path: &str, errors: &mut Vec<String>) {
+        let mut mismatch =
+            |expected: &str| errors.push(format!("{}: expected {}, got {}", path, expected, value));
+        match self {
+            SchemaType::Empty => {
+                if !(value.is_null() || value.as_str() == Some("")) {
+                    mismatch("nothing")
+                }
+            }
+            SchemaType::Pubkey => match value.as_str() {
+                Some(key) => {
+                    if Pubkey::from_str(key).is_err() {
+                        errors.push(format!("{}: invalid base58 pubkey {:?}", path, key))
+                    }
+                }
+                None => mismatch("a base58 pubkey string"),
+            },
+            SchemaType::String | SchemaType::Utf8Remaining | SchemaType::RawString => {
+                if !value.is_string() {
+                    mismatch("a string")
+                }
+            }
+            SchemaType::Bool => {
+                if !value.is_boolean() {
+                    mismatch("a bool")
+                }
+            }
+            SchemaType::I8 => {
+                check_int(value, path, "i8", i8::MIN as i128, i8::MAX as u128, errors)
+            }
+            SchemaType::U8 => check_int(value, path, "u8", 0, u8::MAX as u128, errors),
+            SchemaType::I16 => check_int(
+                value,
+                path,
+                "i16",
+                i16::MIN as i128,
+                i16::MAX as u128,
+                errors,
+            ),
+            SchemaType::U16 => check_int(value, path, "u16", 0, u16::MAX as u128, errors),
+            SchemaType::I32 => check_int(
+                value,
+                path,
+                "i32",
+                i32::MIN as i128,
+                i32::MAX as u128,
+                errors,
+            ),
+            SchemaType::U32 => check_int(value, path, "u32", 0, u32::MAX as u128, errors),
+            SchemaType::I64 | SchemaType::UnixTimestamp | SchemaType::Duration => check_int(
+                value,
+                path,
+                "i64",
+                i64::MIN as i128,
+                i64::MAX as u128,
+                errors,
+            ),
+            SchemaType::U64 => check_int(value, path, "u64", 0, u64::MAX as u128, errors),
+            SchemaType::I128 => {
+                check_int(value, path, "i128", i128::MIN, i128::MAX as u128, errors)
+            }
+            SchemaType::U128 => check_int(value, path, "u128", 0, u128::MAX, errors),
+            SchemaType::F32 | SchemaType::F64 => {
+                let is_float =
+                    value.is_number() || value.as_str().is_some_and(|v| v.parse::<f64>().is_ok());
+                if !is_float {
+                    mismatch("a number")
+                }
+            }
+            SchemaType::Option(inner)
+            | SchemaType::LenientOption(inner)
+            | SchemaType::FlaggedOption { inner, .. } => {
+                // `Option<()>` renders as a presence flag
+                let is_flag = matches!(**inner, SchemaType::Empty) && value.is_boolean();
+                if !value.is_null() && !is_flag {
+                    inner.validate_at(value, path, errors)
+                }
+            }
+            SchemaType::Array(len, inner) => {
+                validate_elements(inner, value, path, Some(*len), errors)
+            }
+            SchemaType::Bitmap(bits) => {
+                validate_elements(&SchemaType::Bool, value, path, Some(*bits), errors)
+            }
+            SchemaType::Vec(inner)
+            | SchemaType::RemainingVec(inner)
+            | SchemaType::CountedArray { element: inner, .. } => {
+                validate_elements(inner, value, path, None, errors)
+            }
+            SchemaType::SmallVec(len_ty, inner) => {
+                let max = match len_ty {
+                    SmallVecLen::U8 => u8::MAX as usize,
+                    SmallVecLen::U16 | SmallVecLen::ShortU16 => u16::MAX as usize,
+                };
+                if value.as_array().is_some_and(|items| items.len() > max) {
+                    errors.push(format!("{}: more than {} elements", path, max));
+                }
+                validate_elements(inner, value, path, None, errors)
+            }
+            SchemaType::RemainingBytes => {
+                validate_elements(&SchemaType::U8, value, path, None, errors)
+            }
+            SchemaType::Tuple(types) => match value.as_array() {
+                Some(items) if items.len() == types.len() => {
+                    for (i, (typ, item)) in types.iter().zip(items).enumerate() {
+                        typ.validate_at(item, &format!("{}[{}]", path, i), errors);
+                    }
+                }
+                _ => mismatch(&format!("an array of {} elements", types.len())),
+            },
+            SchemaType::Struct(fields) => validate_struct(fields, value, path, errors),
+            // keys are object keys, so only the values can be checked
+            SchemaType::Map(_, value_type) => match value.as_object() {
+                Some(object) => {
+                    for (key, entry) in object {
+                        value_type.validate_at(entry, &format!("{}.{}", path, key), errors);
+                    }
+                }
+                None => mismatch("an object"),
+            },
+            // extensions are optional, and unregistered ones pass as bytes
+            SchemaType::Tlv(entries) => match value.as_object() {
+                Some(object) => {
+                    for node in entries.values() {
+                        if let Some(entry) = object.get(&*node.name) {
+                            let entry_path = format!("{}.{}", path, node.name);
+                            node.typ.validate_at(entry, &entry_path, errors);
+                        }
+                    }
+                }
+                None => mismatch("an object"),
+            },
+            SchemaType::Enum(variants) | SchemaType::TaggedEnum(_, variants) => {
+                validate_enum(variants, value, path, errors)
+            }
+        }
+    }
+}
+
+/// Accepts JSON numbers as well as the decimal strings large integers are
+/// rendered as
+fn check_int(
+    value: &Value,
+    path: &str,
+    typename: &str,
+    min: i128,
+    max: u128,
+    errors: &mut Vec<String>,
+) {
+    let in_range = match value {
+        Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+            (Some(v), _) => (v as u128) <= max,
+            (None, Some(v)) => (v as i128) >= min,
+            _ => {
+                errors.push(format!("{}: expected {}, got {}", path, typename, value));
+                return;
+            }
+        },
+        Value::String(s) => match (s.parse::<u128>(), s.parse::<i128>()) {
+            (Ok(v), _) => v <= max,
+            (Err(_), Ok(v)) => v >= min,
+            _ => {
+                errors.push(format!("{}: expected {}, got {}", path, typename, value));
+                return;
+            }
+        },
+        _ => {
+            errors.push(format!("{}: expected {}, got {}", path, typename, value));
+            return;
+        }
+    };
+    if !in_range {
+        errors.push(format!("{}: {} out of range for {}", path, value, typename));
+    }
+}
+
+fn validate_elements(
+    inner: &SchemaType,
+    value: &Value,
+    path: &str,
+    len: Option<usize>,
+    errors: &mut Vec<String>,
+) {
+    let Some(items) = value.as_array() else {
+        errors.push(format!("{}: expected an array, got {}", path, value));
+        return;
+    };
+    if let Some(len) = len {
+        if items.len() != len {
+            errors.push(format!(
+                "{}: expected {} elements, got {}",
+                path,
+                len,
+                items.len()
+            ));
+        }
+    }
+    for (i, item) in items.iter().enumerate() {
+        inner.validate_at(item, &format!("{}[{}]", path, i), errors);
+    }
+}
+
+fn validate_struct(fields: &[SchemaNode], value: &Value, path: &str, errors: &mut Vec<String>) {
+    let Some(object) = value.as_object() else {
+        errors.push(format!("{}: expected an object, got {}", path, value));
+        return;
+    };
+    for field in fields {
+        let field_path = format!("{}.{}", path, field.name);
+        match object.get(&*field.name) {
+            Some(field_value) => field.typ.validate_at(field_value, &field_path, errors),
+            None => errors.push(format!("{}: missing field", field_path)),
+        }
+    }
+    for key in object.keys() {
+        if !fields.iter().any(|field| *field.name == **key) {
+            errors.push(format!("{}.{}: unexpected field", path, key));
+        }
+    }
+}
+
+/// A unit variant is its name; a variant with a payload is
+/// `{ "name": <variant>, "value": <payload> }`
+fn validate_enum(variants: &[SchemaNode], value: &Value, path: &str, errors: &mut Vec<String>) {
+    let (name, payload) = match value {
+        Value::String(name) => (name.as_str(), None),
+        Value::Object(object) => match object.get("name").and_then(|v| v.as_str()) {
+            Some(name) => (name, object.get("value")),
+            None => {
+                errors.push(format!("{}: enum object is missing its variant name", path));
+                return;
+            }
+        },
+        _ => {
+            errors.push(format!("{}: expected an enum variant, got {}", path, value));
+            return;
+        }
+    };
+    let Some(variant) = variants.iter().find(|variant| *variant.name == *name) else {
+        errors.push(format!("{}: unknown variant {:?}", path, name));
+        return;
+    };
+    let variant_path = format!("{}.{}", path, name);
+    match payload {
+        Some(payload) => variant.typ.validate_at(payload, &variant_path, errors),
+        None if matches!(variant.typ, SchemaType::Empty) => (),
+        None => errors.push(format!("{}: missing variant value", variant_path)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::schema::{SchemaNode, SchemaType};
+
+    fn deposit_args() -> SchemaType {
+        SchemaNode::new_struct(
+            "DepositArgs",
+            vec![
+                ("amount", SchemaType::U64),
+                ("owner", SchemaType::Pubkey),
+                ("memo", SchemaType::option(SchemaType::String)),
+                ("fees", SchemaType::vec(SchemaType::U16)),
+                (
+                    "side",
+                    SchemaType::Enum(vec![
+                        SchemaNode::new("Bid", SchemaType::Empty),
+                        SchemaNode::new("Ask", SchemaType::U8),
+                    ]),
+                ),
+            ],
+        )
+        .typ
+    }
+
+    #[test]
+    fn valid_args_pass() {
+        let args = serde_json::json!({
+            "amount": "18446744073709551615",
+            "owner": "CzwQ3dFHekGbHcGYNwUHAjShX9KmhFdWsfJBmYFMHoh7",
+            "memo": null,
+            "fees": [1, 2, 3],
+            "side": {"name": "Ask", "value": 5}
+        });
+        assert_eq!(deposit_args().validate_json(&args), Ok(()));
+
+        let args = serde_json::json!({
+            "amount": 7,
+            "owner": "11111111111111111111111111111111",
+            "memo": "gm",
+            "fees": [],
+            "side": "Bid"
+        });
+        assert_eq!(deposit_args().validate_json(&args), Ok(()));
+    }
+
+    #[test]
+    fn wrong_types_are_reported_per_field() {
+        let args = serde_json::json!({
+            "amount": "-1",
+            "owner": "not-a-pubkey",
+            "memo": 42,
+            "fees": [1, 70000],
+            "side": "Sideways",
+            "extra": true
+        });
+        let errors = deposit_args().validate_json(&args).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                r#"$.amount: "-1" out of range for u64"#.to_string(),
+                r#"$.owner: invalid base58 pubkey "not-a-pubkey""#.to_string(),
+                "$.memo: expected a string, got 42".to_string(),
+                "$.fees[1]: 70000 out of range for u16".to_string(),
+                r#"$.side: unknown variant "Sideways""#.to_string(),
+                "$.extra: unexpected field".to_string(),
+            ]
+        );
+
+        let errors = deposit_args()
+            .validate_json(&serde_json::json!({"amount": 1}))
+            .unwrap_err();
+        assert!(errors.contains(&"$.owner: missing field".to_string()));
+    }
+}
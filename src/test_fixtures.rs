@@ -0,0 +1,43 @@
+//! IDLs and data shared by the unit tests
+
+use solana_program::hash::hash;
+
+use crate::{on_chain_idl::OnChainIdl, parse_idl::parse_idl};
+
+/// A program with one `Counter { count: u64 }` account and an
+/// `increment(by: u64)` instruction on it
+pub(crate) const COUNTER_IDL_JSON: &str = r#"{
+  "version": "0.1.0",
+  "name": "counter",
+  "instructions": [
+    {
+      "name": "increment",
+      "accounts": [{ "name": "counter", "isMut": true, "isSigner": false }],
+      "args": [{ "name": "by", "type": "u64" }]
+    }
+  ],
+  "accounts": [
+    {
+      "name": "Counter",
+      "type": { "kind": "struct", "fields": [{ "name": "count", "type": "u64" }] }
+    }
+  ]
+}"#;
+
+pub(crate) fn counter_idl() -> OnChainIdl {
+    parse_idl(COUNTER_IDL_JSON.to_string()).expect("parse_idl ok")
+}
+
+/// Data of a `Counter` account holding `count`
+pub(crate) fn counter_account(count: u64) -> Vec<u8> {
+    let mut data = hash(b"account:Counter").to_bytes()[..8].to_vec();
+    data.extend_from_slice(&count.to_le_bytes());
+    data
+}
+
+/// Data of an `increment` instruction adding `by`
+pub(crate) fn increment_instruction(by: u64) -> Vec<u8> {
+    let mut data = hash(b"global:increment").to_bytes()[..8].to_vec();
+    data.extend_from_slice(&by.to_le_bytes());
+    data
+}
@@ -1,15 +1,20 @@
+use std::sync::Arc;
+
+use base64::Engine;
 use serde::{ser::SerializeMap, Serialize, Serializer};
 use solana_program::pubkey::Pubkey;
 
 #[derive(Debug, Clone, Serialize, PartialEq)]
 #[repr(C)]
 pub struct ValueNode {
-    pub name: String,
+    /// shared with every other node of the same field when decoded with
+    /// `DecodeOptions::intern_names`
+    pub name: Arc<str>,
     pub value: TypedValue,
 }
 
 impl ValueNode {
-    pub fn new(name: impl Into<String>, value: TypedValue) -> Self {
+    pub fn new(name: impl Into<Arc<str>>, value: TypedValue) -> Self {
         Self {
             name: name.into(),
             value,
@@ -20,7 +25,26 @@ impl ValueNode {
         name: impl Into<String>,
         fields: Vec<(impl Into<String>, TypedValue)>,
     ) -> Self {
-        Self::new(name, TypedValue::new_struct(fields))
+        Self::new(name.into(), TypedValue::new_struct(fields))
+    }
+
+    /// Serializes as a single-key object `{ "<name>": <value> }`, as opposed to
+    /// the derived `{ "name": ..., "value": ... }` form.
+    pub fn to_named_json(&self) -> serde_json::Value {
+        let mut map = serde_json::Map::with_capacity(1);
+        map.insert(
+            self.name.to_string(),
+            // map keys are always field names, so this can't fail
+            serde_json::to_value(&self.value).expect("TypedValue serializes to JSON"),
+        );
+        serde_json::Value::Object(map)
+    }
+
+    /// Every pubkey found in the value at any depth, in decode order
+    pub fn collect_pubkeys(&self) -> Vec<String> {
+        let mut pubkeys = Vec::new();
+        self.value.collect_pubkeys_into(&mut pubkeys);
+        pubkeys
     }
 }
 
@@ -56,6 +80,17 @@ pub enum TypedValue {
     Struct(Vec<ValueNode>),
     /// Vec of bytes
     Bytes(Vec<u8>),
+    /// seconds since the Unix epoch
+    UnixTimestamp(i64),
+    /// seconds
+    Duration(i64),
+    /// UTF-8 text, `lossy` when invalid sequences were replaced with U+FFFD
+    Utf8 {
+        text: String,
+        lossy: bool,
+    },
+    /// key-value pairs in the order they were stored
+    Map(Vec<(TypedValue, TypedValue)>),
 }
 
 impl TypedValue {
@@ -66,6 +101,63 @@ impl TypedValue {
             .collect();
         Self::Struct(nodes)
     }
+
+    fn collect_pubkeys_into(&self, pubkeys: &mut Vec<String>) {
+        match self {
+            TypedValue::Pubkey(v) => pubkeys.push(v.clone()),
+            TypedValue::Option(v) => {
+                if let Some(v) = v.as_ref() {
+                    v.collect_pubkeys_into(pubkeys);
+                }
+            }
+            TypedValue::Array(v) | TypedValue::Tuple(v) | TypedValue::Vec(v) => {
+                v.iter().for_each(|v| v.collect_pubkeys_into(pubkeys))
+            }
+            TypedValue::Enum(v) => v.value.collect_pubkeys_into(pubkeys),
+            TypedValue::Struct(v) => v.iter().for_each(|v| v.value.collect_pubkeys_into(pubkeys)),
+            TypedValue::Map(v) => v.iter().for_each(|(key, value)| {
+                key.collect_pubkeys_into(pubkeys);
+                value.collect_pubkeys_into(pubkeys);
+            }),
+            _ => (),
+        }
+    }
+
+    /// The JSON object key a map key renders as, for scalar keys
+    fn map_key(&self) -> Option<String> {
+        Some(match self {
+            TypedValue::String(v) | TypedValue::Pubkey(v) => v.clone(),
+            TypedValue::Bool(v) => v.to_string(),
+            TypedValue::I8(v) => v.to_string(),
+            TypedValue::U8(v) => v.to_string(),
+            TypedValue::I16(v) => v.to_string(),
+            TypedValue::U16(v) => v.to_string(),
+            TypedValue::I32(v) => v.to_string(),
+            TypedValue::U32(v) => v.to_string(),
+            TypedValue::I64(v) => v.to_string(),
+            TypedValue::U64(v) => v.to_string(),
+            TypedValue::I128(v) => v.to_string(),
+            TypedValue::U128(v) => v.to_string(),
+            _ => return None,
+        })
+    }
+
+    /// Returns the value of a non-negative integer leaf
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            TypedValue::U8(v) => Some(*v as u64),
+            TypedValue::U16(v) => Some(*v as u64),
+            TypedValue::U32(v) => Some(*v as u64),
+            TypedValue::U64(v) => Some(*v),
+            TypedValue::U128(v) => u64::try_from(*v).ok(),
+            TypedValue::I8(v) => u64::try_from(*v).ok(),
+            TypedValue::I16(v) => u64::try_from(*v).ok(),
+            TypedValue::I32(v) => u64::try_from(*v).ok(),
+            TypedValue::I64(v) => u64::try_from(*v).ok(),
+            TypedValue::I128(v) => u64::try_from(*v).ok(),
+            _ => None,
+        }
+    }
 }
 
 macro_rules! type_conversion {
@@ -113,47 +205,757 @@ impl<T: Into<TypedValue>> From<Vec<T>> for TypedValue {
     }
 }
 
+/// How `TypedValue::Bytes` is rendered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BytesEncoding {
+    /// JSON array of numbers
+    #[default]
+    Array,
+    /// lowercase hex string, without a `0x` prefix
+    Hex,
+    /// standard base64 string, matching what the RPC returns
+    Base64,
+}
+
+/// How integers are rendered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NumberMode {
+    /// JSON numbers up to 32 bits, decimal strings for wider integers
+    #[default]
+    Mixed,
+    /// decimal strings for every integer, for consumers that parse JSON
+    /// numbers as doubles
+    AlwaysString,
+}
+
+/// Output knobs applied when serializing decoded values and schemas
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RenderOptions {
+    pub bytes_encoding: BytesEncoding,
+    pub number_mode: NumberMode,
+    /// render timestamps as ISO 8601 date-times and durations as ISO 8601
+    /// durations, instead of raw seconds
+    pub humanize_time: bool,
+    /// fixed number of decimal places for floats, instead of the shortest
+    /// representation that round-trips
+    pub float_precision: Option<usize>,
+    /// in schema output, list each enum variant as `{ "index", "type" }`
+    /// so consumers see its on-chain tag
+    pub enum_variant_indices: bool,
+    /// current time, to render each timestamp as
+    /// `{ "value": <timestamp>, "relative": "2h ago" }` for explorer UIs
+    pub now_unix: Option<i64>,
+    /// match `JSON.stringify` of what the `@coral-xyz/anchor` JS client
+    /// decodes, so Rust- and JS-decoded data can be compared. Differences
+    /// from the default output:
+    /// - 64- and 128-bit integers, timestamps and durations are `BN`s, whose
+    ///   JSON is a zero-padded hex string (`1000` is `"03e8"`, `-1` is `"-01"`)
+    /// - integers up to 32 bits and floats are always JSON numbers
+    /// - struct fields and enum variants are camelCased, and unnamed fields
+    ///   are keyed by their index
+    /// - enums are always `{ variant: { ..fields } }`, `{ variant: {} }`
+    ///   for variants without fields
+    ///
+    /// The other options are ignored, and bytes stay arrays of numbers.
+    pub anchor_compat: bool,
+}
+
+/// A value paired with the options used to serialize it
+pub struct Rendered<'a, T> {
+    pub(crate) value: &'a T,
+    pub(crate) options: &'a RenderOptions,
+}
+
+impl TypedValue {
+    pub fn render<'a>(&'a self, options: &'a RenderOptions) -> Rendered<'a, TypedValue> {
+        Rendered {
+            value: self,
+            options,
+        }
+    }
+}
+
+impl ValueNode {
+    pub fn render<'a>(&'a self, options: &'a RenderOptions) -> Rendered<'a, ValueNode> {
+        Rendered {
+            value: self,
+            options,
+        }
+    }
+}
+
 impl Serialize for TypedValue {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        match &self {
+        self.render(&RenderOptions::default()).serialize(serializer)
+    }
+}
+
+impl Serialize for Rendered<'_, ValueNode> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_map(Some(2))?;
+        state.serialize_entry("name", &self.value.name)?;
+        state.serialize_entry("value", &self.value.value.render(self.options))?;
+        state.end()
+    }
+}
+
+impl Serialize for Rendered<'_, TypedValue> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let options = self.options;
+        if options.anchor_compat {
+            return serialize_anchor_compat(self.value, options, serializer);
+        }
+        match self.value {
             TypedValue::Empty => serializer.serialize_str(""),
             TypedValue::Pubkey(v) => v.serialize(serializer),
             TypedValue::String(v) => v.serialize(serializer),
-            TypedValue::I8(v) => v.serialize(serializer),
-            TypedValue::U8(v) => v.serialize(serializer),
-            TypedValue::I16(v) => v.serialize(serializer),
-            TypedValue::U16(v) => v.serialize(serializer),
-            TypedValue::I32(v) => v.serialize(serializer),
-            TypedValue::U32(v) => v.serialize(serializer),
+            TypedValue::Utf8 { text, .. } => text.serialize(serializer),
+            TypedValue::I8(v) => serialize_small_int(v, options, serializer),
+            TypedValue::U8(v) => serialize_small_int(v, options, serializer),
+            TypedValue::I16(v) => serialize_small_int(v, options, serializer),
+            TypedValue::U16(v) => serialize_small_int(v, options, serializer),
+            TypedValue::I32(v) => serialize_small_int(v, options, serializer),
+            TypedValue::U32(v) => serialize_small_int(v, options, serializer),
             TypedValue::I64(v) => v.to_string().serialize(serializer),
+            TypedValue::UnixTimestamp(v) => {
+                let value = if options.humanize_time {
+                    format_unix_timestamp(*v)
+                } else {
+                    v.to_string()
+                };
+                match options.now_unix {
+                    Some(now) => {
+                        let mut state = serializer.serialize_map(Some(2))?;
+                        state.serialize_entry("value", &value)?;
+                        state.serialize_entry("relative", &format_relative(*v, now))?;
+                        state.end()
+                    }
+                    None => serializer.serialize_str(&value),
+                }
+            }
+            TypedValue::Duration(v) if options.humanize_time => {
+                serializer.serialize_str(&format_duration(*v))
+            }
+            TypedValue::Duration(v) => v.to_string().serialize(serializer),
             TypedValue::U64(v) => v.to_string().serialize(serializer),
             TypedValue::I128(v) => v.to_string().serialize(serializer),
             TypedValue::U128(v) => v.to_string().serialize(serializer),
-            TypedValue::F32(v) => v.to_string().serialize(serializer),
-            TypedValue::F64(v) => v.to_string().serialize(serializer),
+            TypedValue::F32(v) => match options.float_precision {
+                Some(precision) => format!("{:.*}", precision, v).serialize(serializer),
+                None => v.to_string().serialize(serializer),
+            },
+            TypedValue::F64(v) => match options.float_precision {
+                Some(precision) => format!("{:.*}", precision, v).serialize(serializer),
+                None => v.to_string().serialize(serializer),
+            },
             TypedValue::Bool(v) => v.serialize(serializer),
-            TypedValue::Option(v) => v.serialize(serializer),
-            TypedValue::Array(v) => v.serialize(serializer),
-            TypedValue::Tuple(v) => v.serialize(serializer),
+            TypedValue::Option(v) => match v.as_ref() {
+                // a bare presence flag, `Option<()>`
+                Some(TypedValue::Empty) => serializer.serialize_bool(true),
+                Some(v) => serializer.serialize_some(&v.render(options)),
+                None => serializer.serialize_none(),
+            },
+            TypedValue::Array(v) | TypedValue::Tuple(v) | TypedValue::Vec(v) => {
+                serializer.collect_seq(v.iter().map(|v| v.render(options)))
+            }
             TypedValue::Enum(v) => {
                 if matches!(v.value, TypedValue::Empty) {
                     v.name.serialize(serializer)
                 } else {
-                    v.serialize(serializer)
+                    v.render(options).serialize(serializer)
                 }
             }
-            TypedValue::Vec(v) => v.serialize(serializer),
             TypedValue::Struct(v) => {
                 let mut state = serializer.serialize_map(Some(v.len()))?;
                 for field in v {
-                    state.serialize_entry(&field.name, &field.value)?;
+                    state.serialize_entry(&field.name, &field.value.render(options))?;
                 }
                 state.end()
             }
-            TypedValue::Bytes(v) => v.serialize(serializer),
+            TypedValue::Map(entries) => serialize_map_entries(entries, options, serializer),
+            TypedValue::Bytes(v) => match options.bytes_encoding {
+                BytesEncoding::Array => v.serialize(serializer),
+                BytesEncoding::Hex => serializer.serialize_str(&to_hex(v)),
+                BytesEncoding::Base64 => {
+                    serializer.serialize_str(&base64::engine::general_purpose::STANDARD.encode(v))
+                }
+            },
+        }
+    }
+}
+
+fn serialize_map_entries<S>(
+    entries: &[(TypedValue, TypedValue)],
+    options: &RenderOptions,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let keys: Option<Vec<String>> = entries.iter().map(|(k, _)| k.map_key()).collect();
+    match keys {
+        // sorted, so snapshots don't depend on the stored order
+        Some(keys) => {
+            let mut sorted: Vec<_> = keys.iter().zip(entries).collect();
+            sorted.sort_by(|(a, _), (b, _)| a.cmp(b));
+            let mut state = serializer.serialize_map(Some(sorted.len()))?;
+            for (key, (_, value)) in sorted {
+                state.serialize_entry(key, &value.render(options))?;
+            }
+            state.end()
+        }
+        // composite keys can't be object keys: `[[key, value], ...]`
+        None => serializer.collect_seq(
+            entries
+                .iter()
+                .map(|(k, v)| (k.render(options), v.render(options))),
+        ),
+    }
+}
+
+/// `RenderOptions::anchor_compat` output; see there for how it differs
+fn serialize_anchor_compat<S>(
+    value: &TypedValue,
+    options: &RenderOptions,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match value {
+        TypedValue::Empty => serializer.serialize_map(Some(0))?.end(),
+        TypedValue::Pubkey(v) | TypedValue::String(v) => v.serialize(serializer),
+        TypedValue::Utf8 { text, .. } => text.serialize(serializer),
+        TypedValue::I8(v) => v.serialize(serializer),
+        TypedValue::U8(v) => v.serialize(serializer),
+        TypedValue::I16(v) => v.serialize(serializer),
+        TypedValue::U16(v) => v.serialize(serializer),
+        TypedValue::I32(v) => v.serialize(serializer),
+        TypedValue::U32(v) => v.serialize(serializer),
+        TypedValue::I64(v) | TypedValue::UnixTimestamp(v) | TypedValue::Duration(v) => {
+            serializer.serialize_str(&bn_json(*v < 0, v.unsigned_abs() as u128))
+        }
+        TypedValue::U64(v) => serializer.serialize_str(&bn_json(false, *v as u128)),
+        TypedValue::I128(v) => serializer.serialize_str(&bn_json(*v < 0, v.unsigned_abs())),
+        TypedValue::U128(v) => serializer.serialize_str(&bn_json(false, *v)),
+        TypedValue::F32(v) => v.serialize(serializer),
+        TypedValue::F64(v) => v.serialize(serializer),
+        TypedValue::Bool(v) => v.serialize(serializer),
+        TypedValue::Option(v) => match v.as_ref() {
+            Some(v) => serializer.serialize_some(&v.render(options)),
+            None => serializer.serialize_none(),
+        },
+        TypedValue::Array(v) | TypedValue::Tuple(v) | TypedValue::Vec(v) => {
+            serializer.collect_seq(v.iter().map(|v| v.render(options)))
+        }
+        TypedValue::Enum(v) => {
+            let mut state = serializer.serialize_map(Some(1))?;
+            match &v.value {
+                // tuple variants decode to an object keyed by index
+                TypedValue::Tuple(fields) => state
+                    .serialize_entry(&to_camel_case(&v.name), &IndexedFields(fields, options))?,
+                payload => {
+                    state.serialize_entry(&to_camel_case(&v.name), &payload.render(options))?
+                }
+            }
+            state.end()
         }
+        TypedValue::Struct(v) => {
+            let mut state = serializer.serialize_map(Some(v.len()))?;
+            for (i, field) in v.iter().enumerate() {
+                let key = match field.name.is_empty() {
+                    true => i.to_string(),
+                    false => to_camel_case(&field.name),
+                };
+                state.serialize_entry(&key, &field.value.render(options))?;
+            }
+            state.end()
+        }
+        TypedValue::Map(entries) => serialize_map_entries(entries, options, serializer),
+        TypedValue::Bytes(v) => v.serialize(serializer),
+    }
+}
+
+/// Unnamed fields as an object keyed by index, as Anchor's JS client decodes
+/// tuple variants
+struct IndexedFields<'a>(&'a [TypedValue], &'a RenderOptions);
+
+impl Serialize for IndexedFields<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_map(Some(self.0.len()))?;
+        for (i, field) in self.0.iter().enumerate() {
+            state.serialize_entry(&i.to_string(), &field.render(self.1))?;
+        }
+        state.end()
+    }
+}
+
+/// `BN.prototype.toJSON`: hex digits padded to an even count, after any sign
+fn bn_json(negative: bool, magnitude: u128) -> String {
+    let hex = format!("{:x}", magnitude);
+    let pad = if hex.len() % 2 == 1 { "0" } else { "" };
+    let sign = if negative { "-" } else { "" };
+    format!("{}{}{}", sign, pad, hex)
+}
+
+/// Anchor's JS client camelCases IDL names: `bids_size` and `BidsSize`
+/// both become `bidsSize`
+fn to_camel_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut upper_next = false;
+    for c in name.chars() {
+        if c == '_' {
+            upper_next = !out.is_empty();
+        } else if out.is_empty() {
+            out.extend(c.to_lowercase());
+        } else if upper_next {
+            out.extend(c.to_uppercase());
+            upper_next = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Integers of 32 bits or less, which are exact as JSON numbers
+fn serialize_small_int<S, T>(
+    v: &T,
+    options: &RenderOptions,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    T: Serialize + ToString,
+{
+    match options.number_mode {
+        NumberMode::Mixed => v.serialize(serializer),
+        NumberMode::AlwaysString => serializer.serialize_str(&v.to_string()),
+    }
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    use std::fmt::Write;
+    bytes
+        .iter()
+        .fold(String::with_capacity(bytes.len() * 2), |mut out, b| {
+            let _ = write!(out, "{:02x}", b);
+            out
+        })
+}
+
+/// `YYYY-MM-DDTHH:MM:SSZ`, in UTC
+fn format_unix_timestamp(secs: i64) -> String {
+    let days = secs.div_euclid(86_400);
+    let secs_of_day = secs.rem_euclid(86_400);
+
+    // civil-from-days, ref: https://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3_600,
+        secs_of_day % 3_600 / 60,
+        secs_of_day % 60
+    )
+}
+
+/// Distance from `now` in its largest whole unit, such as `2h ago` or `in 3d`
+fn format_relative(secs: i64, now: i64) -> String {
+    let diff = (now as i128 - secs as i128).unsigned_abs();
+    let (amount, unit) = match diff {
+        0..=59 => (diff, "s"),
+        60..=3_599 => (diff / 60, "m"),
+        3_600..=86_399 => (diff / 3_600, "h"),
+        _ => (diff / 86_400, "d"),
+    };
+    if secs <= now {
+        format!("{}{} ago", amount, unit)
+    } else {
+        format!("in {}{}", amount, unit)
+    }
+}
+
+/// ISO 8601 duration such as `P1DT2H3M4S`
+fn format_duration(secs: i64) -> String {
+    use std::fmt::Write;
+    let abs = secs.unsigned_abs();
+    let (days, hours, minutes, seconds) = (
+        abs / 86_400,
+        abs % 86_400 / 3_600,
+        abs % 3_600 / 60,
+        abs % 60,
+    );
+
+    let mut out = String::from(if secs < 0 { "-P" } else { "P" });
+    if days > 0 {
+        let _ = write!(out, "{}D", days);
+    }
+    if hours > 0 || minutes > 0 || seconds > 0 || days == 0 {
+        out.push('T');
+        if hours > 0 {
+            let _ = write!(out, "{}H", hours);
+        }
+        if minutes > 0 {
+            let _ = write!(out, "{}M", minutes);
+        }
+        if seconds > 0 || (hours == 0 && minutes == 0) {
+            let _ = write!(out, "{}S", seconds);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        schema::{SchemaNode, SchemaType},
+        value::{BytesEncoding, NumberMode, RenderOptions, TypedValue, ValueNode},
+    };
+
+    #[test]
+    fn named_json_wraps_value_in_a_single_key() {
+        let schema = SchemaNode::new_struct(
+            "MarketSizeParams",
+            vec![("bidsSize", SchemaType::U64), ("numSeats", SchemaType::U16)],
+        );
+        let mut data = 100u64.to_le_bytes().to_vec();
+        data.extend_from_slice(&10u16.to_le_bytes());
+        let node: ValueNode = schema
+            .deserialize_bytes(&mut data.as_slice(), false)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            node.to_named_json(),
+            serde_json::json!({ "MarketSizeParams": { "bidsSize": "100", "numSeats": 10 } })
+        );
+        // the derived form keeps name and value side by side, without nesting the name twice
+        assert_eq!(
+            serde_json::to_value(&node).unwrap(),
+            serde_json::json!({
+                "name": "MarketSizeParams",
+                "value": { "bidsSize": "100", "numSeats": 10 }
+            })
+        );
+    }
+
+    #[test]
+    fn bytes_render_as_array_hex_or_base64() {
+        let bytes = TypedValue::Bytes(vec![1, 2, 3]);
+        let render = |bytes_encoding| {
+            let options = RenderOptions {
+                bytes_encoding,
+                ..Default::default()
+            };
+            serde_json::to_string(&bytes.render(&options)).unwrap()
+        };
+
+        assert_eq!(serde_json::to_string(&bytes).unwrap(), "[1,2,3]");
+        assert_eq!(render(BytesEncoding::Array), "[1,2,3]");
+        assert_eq!(render(BytesEncoding::Hex), r#""010203""#);
+        assert_eq!(render(BytesEncoding::Base64), r#""AQID""#);
+
+        // nested bytes pick up the options too
+        let node = ValueNode::new_struct("Memo", vec![("data", bytes.clone())]);
+        let options = RenderOptions {
+            bytes_encoding: BytesEncoding::Hex,
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_string(&node.render(&options)).unwrap(),
+            r#"{"name":"Memo","value":{"data":"010203"}}"#
+        );
+    }
+
+    #[test]
+    fn option_of_empty_renders_as_a_presence_flag() {
+        let ty = SchemaType::option(SchemaType::Empty);
+        let mut buf: &[u8] = &[1, 0];
+
+        let present = ty.deserialize_bytes(&mut buf, false).unwrap();
+        assert_eq!(
+            present,
+            TypedValue::Option(Box::new(Some(TypedValue::Empty)))
+        );
+        assert_eq!(
+            serde_json::to_value(&present).unwrap(),
+            serde_json::json!(true)
+        );
+
+        let absent = ty.deserialize_bytes(&mut buf, false).unwrap();
+        assert_eq!(
+            serde_json::to_value(&absent).unwrap(),
+            serde_json::Value::Null
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn always_string_mode_stringifies_small_integers() {
+        let options = RenderOptions {
+            number_mode: NumberMode::AlwaysString,
+            ..Default::default()
+        };
+        let node = ValueNode::new_struct(
+            "Fees",
+            vec![
+                ("tier", TypedValue::U8(200)),
+                ("offset", TypedValue::I32(-7)),
+                ("total", TypedValue::U64(1)),
+            ],
+        );
+
+        assert_eq!(
+            serde_json::to_value(TypedValue::U8(200).render(&options)).unwrap(),
+            serde_json::json!("200")
+        );
+        assert_eq!(
+            serde_json::to_value(node.render(&options)).unwrap()["value"],
+            serde_json::json!({ "tier": "200", "offset": "-7", "total": "1" })
+        );
+        assert_eq!(
+            serde_json::to_value(TypedValue::U8(200)).unwrap(),
+            serde_json::json!(200)
+        );
+    }
+
+    #[test]
+    fn time_values_render_raw_or_humanized() {
+        let timestamp = TypedValue::UnixTimestamp(1_700_000_000);
+        let duration = TypedValue::Duration(93_784);
+        let humanized = RenderOptions {
+            humanize_time: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            serde_json::to_string(&timestamp).unwrap(),
+            r#""1700000000""#
+        );
+        assert_eq!(
+            serde_json::to_string(&timestamp.render(&humanized)).unwrap(),
+            r#""2023-11-14T22:13:20Z""#
+        );
+        assert_eq!(serde_json::to_string(&duration).unwrap(), r#""93784""#);
+        assert_eq!(
+            serde_json::to_string(&duration.render(&humanized)).unwrap(),
+            r#""P1DT2H3M4S""#
+        );
+        assert_eq!(
+            serde_json::to_string(&TypedValue::Duration(-60).render(&humanized)).unwrap(),
+            r#""-PT1M""#
+        );
+        assert_eq!(
+            serde_json::to_string(&TypedValue::UnixTimestamp(-1).render(&humanized)).unwrap(),
+            r#""1969-12-31T23:59:59Z""#
+        );
+    }
+
+    #[test]
+    fn timestamps_render_relative_to_now() {
+        let timestamp = TypedValue::UnixTimestamp(1_700_000_000);
+        let relative = |now_unix, humanize_time| {
+            let options = RenderOptions {
+                now_unix: Some(now_unix),
+                humanize_time,
+                ..Default::default()
+            };
+            serde_json::to_value(timestamp.render(&options)).unwrap()
+        };
+
+        assert_eq!(
+            relative(1_700_007_200, false),
+            serde_json::json!({ "value": "1700000000", "relative": "2h ago" })
+        );
+        assert_eq!(
+            relative(1_700_000_000 - 3 * 86_400, true),
+            serde_json::json!({ "value": "2023-11-14T22:13:20Z", "relative": "in 3d" })
+        );
+        assert_eq!(relative(1_700_000_059, false)["relative"], "59s ago");
+        // durations aren't points in time
+        let options = RenderOptions {
+            now_unix: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_string(&TypedValue::Duration(60).render(&options)).unwrap(),
+            r#""60""#
+        );
+    }
+
+    #[test]
+    fn collects_pubkeys_at_any_depth() {
+        let owner = "CzwQ3dFHekGbHcGYNwUHAjShX9KmhFdWsfJBmYFMHoh7".to_string();
+        let mint = "So11111111111111111111111111111111111111112".to_string();
+        let signer = "11111111111111111111111111111111".to_string();
+
+        let node = ValueNode::new_struct(
+            "Args",
+            vec![
+                ("amount", TypedValue::U64(5)),
+                (
+                    "config",
+                    TypedValue::new_struct(vec![
+                        ("owner", TypedValue::Pubkey(owner.clone())),
+                        (
+                            "mint",
+                            TypedValue::Option(Box::new(Some(TypedValue::Pubkey(mint.clone())))),
+                        ),
+                    ]),
+                ),
+                (
+                    "signers",
+                    TypedValue::Vec(vec![
+                        TypedValue::Pubkey(signer.clone()),
+                        TypedValue::Pubkey(owner.clone()),
+                    ]),
+                ),
+                ("memo", TypedValue::String("not a pubkey".to_string())),
+            ],
+        );
+
+        assert_eq!(
+            node.collect_pubkeys(),
+            vec![owner.clone(), mint, signer, owner]
+        );
+    }
+
+    #[test]
+    #[allow(clippy::approx_constant)] // a truncated pi, not a use of it
+    fn floats_render_with_optional_precision() {
+        let value = TypedValue::F64(3.14159265358979);
+        assert_eq!(
+            serde_json::to_string(&value).unwrap(),
+            r#""3.14159265358979""#
+        );
+
+        let options = RenderOptions {
+            float_precision: Some(4),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_string(&value.render(&options)).unwrap(),
+            r#""3.1416""#
+        );
+        assert_eq!(
+            serde_json::to_string(&TypedValue::F32(2.5).render(&options)).unwrap(),
+            r#""2.5000""#
+        );
+    }
+
+    #[test]
+    fn decoded_maps_render_with_sorted_keys() {
+        use borsh::BorshSerialize;
+
+        let ty = SchemaType::Map(Box::new(SchemaType::String), Box::new(SchemaType::U64));
+        // stored out of order, as a Rust HashMap would write them
+        let mut data = Vec::new();
+        3u32.serialize(&mut data).unwrap();
+        for (key, value) in [("sol", 1u64), ("bonk", 2), ("usdc", 3)] {
+            key.to_string().serialize(&mut data).unwrap();
+            value.serialize(&mut data).unwrap();
+        }
+
+        let value = ty.deserialize_bytes(&mut data.as_slice(), false).unwrap();
+        assert_eq!(
+            value,
+            TypedValue::Map(vec![
+                (TypedValue::String("sol".to_string()), TypedValue::U64(1)),
+                (TypedValue::String("bonk".to_string()), TypedValue::U64(2)),
+                (TypedValue::String("usdc".to_string()), TypedValue::U64(3)),
+            ])
+        );
+        assert_eq!(
+            serde_json::to_string(&value).unwrap(),
+            r#"{"bonk":"2","sol":"1","usdc":"3"}"#
+        );
+    }
+
+    #[test]
+    fn anchor_compat_matches_the_anchor_js_client() {
+        let side = SchemaType::Enum(vec![
+            SchemaNode::new("Bid", SchemaType::Empty),
+            SchemaNode::new_struct("Ask", vec![("limit_price", SchemaType::U64)]),
+            SchemaNode::new("Pegged", SchemaType::Tuple(vec![SchemaType::I64])),
+        ]);
+        let schema = SchemaNode::new_struct(
+            "Order",
+            vec![
+                ("base_lots", SchemaType::U64),
+                ("side", side),
+                ("slot_offset", SchemaType::I64),
+                ("num_fills", SchemaType::U8),
+            ],
+        );
+        let decode = |side: &[u8]| {
+            let mut data = 1_000u64.to_le_bytes().to_vec();
+            data.extend_from_slice(side);
+            data.extend_from_slice(&(-1i64).to_le_bytes());
+            data.push(3);
+            schema
+                .deserialize_bytes(&mut data.as_slice(), false)
+                .unwrap()
+                .unwrap()
+                .value
+        };
+        let options = RenderOptions {
+            anchor_compat: true,
+            number_mode: NumberMode::AlwaysString,
+            ..Default::default()
+        };
+
+        let bid = decode(&[0]);
+        assert_eq!(
+            serde_json::to_value(bid.render(&options)).unwrap(),
+            serde_json::json!({
+                "baseLots": "03e8",
+                "side": { "bid": {} },
+                "slotOffset": "-01",
+                "numFills": 3
+            })
+        );
+        // the default output, for contrast
+        assert_eq!(
+            serde_json::to_value(&bid).unwrap()["side"],
+            serde_json::json!("Bid")
+        );
+
+        let mut ask = vec![1];
+        ask.extend_from_slice(&255u64.to_le_bytes());
+        assert_eq!(
+            serde_json::to_value(decode(&ask).render(&options)).unwrap()["side"],
+            serde_json::json!({ "ask": { "limitPrice": "ff" } })
+        );
+
+        let mut pegged = vec![2];
+        pegged.extend_from_slice(&4096i64.to_le_bytes());
+        assert_eq!(
+            serde_json::to_value(decode(&pegged).render(&options)).unwrap()["side"],
+            serde_json::json!({ "pegged": { "0": "1000" } })
+        );
     }
 }
@@ -50,3 +50,42 @@ fn test_parse_idl() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn drift_instruction_args_encode_back_to_their_bytes() -> anyhow::Result<()> {
+    let drift_idl = parse_idl::parse_idl_file("tests/idls/drift.json")
+        .map_err(|e| anyhow::anyhow!("Failed to parse IDL file: {}", e))?;
+
+    let ix_data = vec![
+        213, 51, 1, 187, 108, 220, 230, 224, 4, 1, 0, 0, 0, 16, 165, 212, 232, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 10, 0, 0, 0, 0, 0, 0, 0, 1, 31, 87, 1, 0, 1, 20, 1, 239, 218, 0, 0, 0, 0, 0, 0,
+        1, 31, 87, 1, 0, 0, 0, 0, 0, 0,
+    ];
+    let parsed_ix = drift_idl.get_parsed_instruction(ix_data.clone(), &[], true)?;
+
+    let args = parsed_ix.schema.serialize_value(&parsed_ix.value)?;
+    assert_eq!(args, ix_data[8..]);
+    Ok(())
+}
+
+#[test]
+fn phoenix_cancel_instruction_passes_self_check() -> anyhow::Result<()> {
+    let phoenix_idl = parse_idl::parse_idl_file("tests/idls/phoenix_v1.json")
+        .map_err(|e| anyhow::anyhow!("Failed to parse IDL file: {}", e))?;
+
+    let phoenix_cancel_ix_data = vec![
+        11, 8, 0, 0, 0, 0, 115, 3, 0, 0, 0, 0, 0, 0, 57, 84, 239, 255, 255, 255, 255, 255, 0, 114,
+        3, 0, 0, 0, 0, 0, 0, 56, 84, 239, 255, 255, 255, 255, 255, 0, 112, 3, 0, 0, 0, 0, 0, 0, 55,
+        84, 239, 255, 255, 255, 255, 255, 0, 111, 3, 0, 0, 0, 0, 0, 0, 54, 84, 239, 255, 255, 255,
+        255, 255, 1, 121, 3, 0, 0, 0, 0, 0, 0, 202, 171, 16, 0, 0, 0, 0, 0, 1, 122, 3, 0, 0, 0, 0,
+        0, 0, 203, 171, 16, 0, 0, 0, 0, 0, 1, 123, 3, 0, 0, 0, 0, 0, 0, 204, 171, 16, 0, 0, 0, 0,
+        0, 1, 125, 3, 0, 0, 0, 0, 0, 0, 205, 171, 16, 0, 0, 0, 0, 0,
+    ];
+
+    let results = phoenix_idl.self_check(&[(phoenix_cancel_ix_data, vec![])]);
+    assert_eq!(results.len(), 1);
+    for result in results {
+        result?;
+    }
+    Ok(())
+}
@@ -137,16 +137,15 @@ fn scope_update_mapping_and_metadata_deserializes_correctly() {
     //
     // The instruction data is from a real Scope/Hubble transaction that
     // calls updateMappingAndMetadata with 5 MappingRefPrice entries.
-    let idl = parse_idl::parse_idl(scope_idl_fragment())
-        .expect("parse_idl should succeed for scope IDL");
+    let idl =
+        parse_idl::parse_idl(scope_idl_fragment()).expect("parse_idl should succeed for scope IDL");
 
     // 86-byte instruction data (8-byte Anchor disc + 78 bytes of args)
     let instruction_data: Vec<u8> = vec![
         // Anchor discriminator for updateMappingAndMetadata
         0x9e, 0x51, 0x95, 0x92, 0xce, 0x9a, 0x5b, 0x38,
         // feedName = "klend" (length-prefixed string)
-        0x05, 0x00, 0x00, 0x00, 0x6b, 0x6c, 0x65, 0x6e, 0x64,
-        // updates vec length = 5
+        0x05, 0x00, 0x00, 0x00, 0x6b, 0x6c, 0x65, 0x6e, 0x64, // updates vec length = 5
         0x05, 0x00, 0x00, 0x00,
         // Entry 0: entryId=135, 1 update, MappingRefPrice(Some(17), Some(500))
         0x87, 0x00, 0x01, 0x00, 0x00, 0x00, 0x04, 0x01, 0x11, 0x00, 0x01, 0xf4, 0x01,
@@ -246,7 +245,7 @@ fn array_of_defined_type_preserves_array_wrapper() {
 
     match &dec.instruction_args_parser.typ {
         SchemaType::Struct(fields) => {
-            let items = fields.iter().find(|f| f.name == "items").unwrap();
+            let items = fields.iter().find(|f| &*f.name == "items").unwrap();
             match &items.typ {
                 SchemaType::Array(3, inner) => {
                     assert!(